@@ -34,7 +34,7 @@ CREATE TABLE payments (
 
     -- Timestamps for tracking
     created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP, callback_url TEXT, request_fingerprint TEXT, hold_reason TEXT, execute_after TIMESTAMP, failure_code TEXT,
 
     FOREIGN KEY (payment_batch_id) REFERENCES payment_batches(id),
     -- Ensures a client can't accidentally submit the same payment twice.
@@ -83,6 +83,305 @@ CREATE TABLE payment_batches (
     -- Timestamps
     created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
     updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
-);
+, lease_expires_at TIMESTAMP, approvals_required INTEGER, missing_since TIMESTAMP, resubmission_count INTEGER NOT NULL DEFAULT 0, next_retry_at TIMESTAMP, parent_batch_id TEXT REFERENCES payment_batches(id), submitted_at TIMESTAMP, utxos_locked_until TIMESTAMP);
 CREATE INDEX idx_payments_status ON payments(status);
 CREATE INDEX idx_payment_batches_status ON payment_batches(status);
+CREATE INDEX idx_payments_created_at ON payments(created_at, id);
+CREATE INDEX idx_payments_account_name_created_at ON payments(account_name, created_at, id);
+CREATE INDEX idx_payments_status_created_at ON payments(status, created_at, id);
+CREATE INDEX idx_payments_recipient_address ON payments(recipient_address);
+CREATE INDEX idx_payments_client_id ON payments(client_id);
+CREATE INDEX idx_payment_batches_created_at ON payment_batches(created_at, id);
+CREATE INDEX idx_payment_batches_account_name_created_at ON payment_batches(account_name, created_at, id);
+CREATE INDEX idx_payments_payment_batch_id ON payments(payment_batch_id);
+CREATE TABLE operator_actions (
+    -- The unique ID for this action.
+    id TEXT PRIMARY KEY NOT NULL,
+
+    -- The batch the action was applied to.
+    payment_batch_id TEXT NOT NULL,
+
+    -- The action taken.
+    -- Actions: RETRY, RELEASE_PAYMENTS, FORCE_FAIL
+    action TEXT NOT NULL,
+
+    -- Action-specific parameters, e.g. the stage a retry was started from.
+    details TEXT,
+
+    -- Who performed the action and why.
+    operator TEXT NOT NULL,
+    reason TEXT NOT NULL,
+
+    -- The batch status before and after the action.
+    from_status TEXT NOT NULL,
+    to_status TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (payment_batch_id) REFERENCES payment_batches(id)
+);
+CREATE INDEX idx_operator_actions_payment_batch_id ON operator_actions(payment_batch_id, created_at);
+CREATE TABLE payment_events (
+    -- Monotonic ID, giving the order in which events were recorded.
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    payment_id TEXT NOT NULL,
+
+    -- The status before and after the change. from_status is NULL when the payment was created.
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+
+    -- The worker, API or operator that made the change.
+    actor TEXT NOT NULL,
+
+    -- The failure reason or other context for the change.
+    detail TEXT,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (payment_id) REFERENCES payments(id)
+);
+CREATE TABLE sqlite_sequence(name,seq);
+CREATE TABLE batch_events (
+    -- Monotonic ID, giving the order in which events were recorded.
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    payment_batch_id TEXT NOT NULL,
+
+    -- The status before and after the change. from_status is NULL when the batch was created.
+    -- Retries are recorded with an unchanged status.
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+
+    -- The worker, API or operator that made the change.
+    actor TEXT NOT NULL,
+
+    -- The error message or other context for the change.
+    detail TEXT,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (payment_batch_id) REFERENCES payment_batches(id)
+);
+CREATE INDEX idx_payment_events_payment_id ON payment_events(payment_id, id);
+CREATE INDEX idx_batch_events_payment_batch_id ON batch_events(payment_batch_id, id);
+CREATE TRIGGER payment_events_no_update BEFORE UPDATE ON payment_events
+BEGIN
+    SELECT RAISE(ABORT, 'payment_events is append-only');
+END;
+CREATE TRIGGER payment_events_no_delete BEFORE DELETE ON payment_events
+BEGIN
+    SELECT RAISE(ABORT, 'payment_events is append-only');
+END;
+CREATE TRIGGER batch_events_no_update BEFORE UPDATE ON batch_events
+BEGIN
+    SELECT RAISE(ABORT, 'batch_events is append-only');
+END;
+CREATE TRIGGER batch_events_no_delete BEFORE DELETE ON batch_events
+BEGIN
+    SELECT RAISE(ABORT, 'batch_events is append-only');
+END;
+CREATE TABLE webhook_subscriptions (
+    -- The unique ID for this subscription.
+    id TEXT PRIMARY KEY NOT NULL,
+
+    -- The PR account whose payments are reported.
+    account_name TEXT NOT NULL,
+
+    -- The endpoint that receives the webhook.
+    url TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE (account_name, url)
+);
+CREATE TABLE webhook_deliveries (
+    -- Sequential ID, also sent to the receiver so it can discard duplicates.
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    -- The payment this delivery reports on.
+    payment_id TEXT NOT NULL,
+
+    -- The event being reported, e.g. payment.confirmed or payment.failed.
+    event_type TEXT NOT NULL,
+
+    -- Where the delivery is sent and the exact JSON body that is signed and sent.
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+
+    -- The delivery state.
+    -- States:
+    -- PENDING: Not yet delivered, sent once next_attempt_at has passed.
+    -- DELIVERED: The receiver acknowledged the delivery with a 2xx response.
+    -- DEAD_LETTERED: Every attempt failed. Kept for inspection.
+    status TEXT NOT NULL,
+
+    -- Retry tracking with exponential backoff.
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_error TEXT,
+
+    delivered_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (payment_id) REFERENCES payments(id)
+);
+CREATE INDEX idx_webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at);
+CREATE INDEX idx_webhook_deliveries_payment_id ON webhook_deliveries(payment_id);
+CREATE TABLE api_keys (
+    -- The unique ID for this key, used to revoke it.
+    id TEXT PRIMARY KEY NOT NULL,
+
+    -- A human-readable name for the key holder. Recorded as the operator for admin actions.
+    name TEXT NOT NULL,
+
+    -- SHA-256 hash of the key. The key itself is only shown once, when it is issued.
+    key_hash TEXT NOT NULL UNIQUE,
+
+    -- The start of the key, kept so it can be recognised in listings.
+    key_prefix TEXT NOT NULL,
+
+    -- JSON array of the account names the key may act on. "*" grants every account.
+    account_names TEXT NOT NULL,
+
+    -- JSON array of the scopes granted to the key.
+    -- Scopes: payments:read, payments:write, admin
+    scopes TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- Set when the key is revoked. Revoked keys are rejected.
+    revoked_at TIMESTAMP
+);
+CREATE TABLE account_alerts (
+    -- Sequential ID, so alerts are ordered even within the same second.
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    -- The PR account the alert is about.
+    account_name TEXT NOT NULL,
+
+    -- What went wrong.
+    -- Kinds:
+    -- INSUFFICIENT_FUNDS: The account's balance does not cover its queued payments.
+    kind TEXT NOT NULL,
+
+    -- Human-readable details, e.g. the shortfall.
+    detail TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_account_alerts_account_name ON account_alerts(account_name, id);
+CREATE TABLE batch_policies (
+    -- The PR account the policy applies to.
+    account_name TEXT PRIMARY KEY NOT NULL,
+
+    -- The most payments a single batch may contain.
+    max_recipients INTEGER NOT NULL,
+
+    -- The most a single batch may pay out in total, in µT. NULL for no limit.
+    max_total_amount BIGINT,
+
+    -- How many payments must be queued before a batch is created, unless the oldest has waited
+    -- max_age_secs.
+    min_recipients INTEGER NOT NULL,
+
+    -- How long the oldest queued payment may wait before a batch is created regardless of
+    -- min_recipients. Only NULL when min_recipients is 1, so payments never wait indefinitely.
+    max_age_secs INTEGER,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_payments_status_execute_after ON payments(status, execute_after);
+CREATE TABLE payout_schedules (
+    -- The unique ID for this schedule.
+    id TEXT PRIMARY KEY NOT NULL,
+
+    -- The PR account the payments are made from.
+    account_name TEXT NOT NULL,
+
+    -- The payment created on each occurrence.
+    recipient_address TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    payment_id TEXT,
+    callback_url TEXT,
+
+    -- A five-field cron expression evaluated in UTC, e.g. '0 9 * * 1' for every Monday at 09:00.
+    cadence TEXT NOT NULL,
+
+    -- Paused schedules create no payments. On resuming, runs due while paused are not reported
+    -- as missed.
+    is_paused BOOLEAN NOT NULL DEFAULT 0,
+
+    -- The next occurrence that has not been run yet.
+    next_run_at TIMESTAMP NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_payout_schedules_due ON payout_schedules(is_paused, next_run_at);
+CREATE INDEX idx_payout_schedules_account_name ON payout_schedules(account_name, created_at);
+CREATE TABLE payout_schedule_runs (
+    schedule_id TEXT NOT NULL,
+
+    -- The occurrence of the cadence this run is for.
+    scheduled_for TIMESTAMP NOT NULL,
+
+    -- What happened to the occurrence.
+    -- Statuses:
+    -- CREATED: The payment was created.
+    -- MISSED: The occurrence passed while the processor was not running and was not paid. Only
+    --         the latest occurrence is paid after downtime, the earlier ones are reported in a
+    --         MISSED_PAYOUT_RUNS account alert.
+    status TEXT NOT NULL,
+
+    -- The payment created for the occurrence.
+    payment_id TEXT,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (schedule_id, scheduled_for),
+    FOREIGN KEY (schedule_id) REFERENCES payout_schedules(id) ON DELETE CASCADE,
+    FOREIGN KEY (payment_id) REFERENCES payments(id)
+);
+CREATE TABLE approval_policies (
+    -- The PR account the policy applies to.
+    account_name TEXT PRIMARY KEY NOT NULL,
+
+    -- How many distinct operators must approve a batch.
+    required_approvals INTEGER NOT NULL,
+
+    -- A batch needs approval when it pays out at least this much in total, in µT, or has at least
+    -- min_recipients payments. When both are NULL every batch of the account needs approval.
+    min_total_amount BIGINT,
+    min_recipients INTEGER,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE TABLE batch_approvals (
+    payment_batch_id TEXT NOT NULL,
+    pr_idempotency_key TEXT NOT NULL,
+
+    -- The name of the API key of the approving operator.
+    approver TEXT NOT NULL,
+    reason TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (payment_batch_id, pr_idempotency_key, approver),
+    FOREIGN KEY (payment_batch_id) REFERENCES payment_batches(id)
+);
+CREATE INDEX idx_payment_batches_status_mined_height ON payment_batches(status, mined_height);
+CREATE TABLE payment_batch_stage_retries (
+    payment_batch_id TEXT NOT NULL,
+
+    -- CREATE_UNSIGNED_TX, SIGN or BROADCAST.
+    stage TEXT NOT NULL,
+
+    retries INTEGER NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (payment_batch_id, stage),
+    FOREIGN KEY (payment_batch_id) REFERENCES payment_batches(id)
+);
@@ -0,0 +1,4 @@
+-- Lease tracking for batches handed off to the signing or broadcast worker.
+-- Set when a worker moves a batch to SIGNING_IN_PROGRESS or BROADCASTING and cleared on the next
+-- status change. A batch whose lease has expired is returned to the state it was claimed from.
+ALTER TABLE payment_batches ADD COLUMN lease_expires_at TIMESTAMP;
@@ -0,0 +1,6 @@
+-- Indexes backing the filtered, newest-first payment listing (GET /v1/payments).
+CREATE INDEX IF NOT EXISTS idx_payments_created_at ON payments(created_at, id);
+CREATE INDEX IF NOT EXISTS idx_payments_account_name_created_at ON payments(account_name, created_at, id);
+CREATE INDEX IF NOT EXISTS idx_payments_status_created_at ON payments(status, created_at, id);
+CREATE INDEX IF NOT EXISTS idx_payments_recipient_address ON payments(recipient_address);
+CREATE INDEX IF NOT EXISTS idx_payments_client_id ON payments(client_id);
@@ -0,0 +1,4 @@
+-- Indexes backing the newest-first payment batch listing (GET /v1/batches).
+CREATE INDEX IF NOT EXISTS idx_payment_batches_created_at ON payment_batches(created_at, id);
+CREATE INDEX IF NOT EXISTS idx_payment_batches_account_name_created_at ON payment_batches(account_name, created_at, id);
+CREATE INDEX IF NOT EXISTS idx_payments_payment_batch_id ON payments(payment_batch_id);
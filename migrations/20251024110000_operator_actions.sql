@@ -0,0 +1,29 @@
+-- Audit log of manual interventions on payment batches made through the admin API.
+CREATE TABLE IF NOT EXISTS operator_actions (
+    -- The unique ID for this action.
+    id TEXT PRIMARY KEY NOT NULL,
+
+    -- The batch the action was applied to.
+    payment_batch_id TEXT NOT NULL,
+
+    -- The action taken.
+    -- Actions: RETRY, RELEASE_PAYMENTS, FORCE_FAIL
+    action TEXT NOT NULL,
+
+    -- Action-specific parameters, e.g. the stage a retry was started from.
+    details TEXT,
+
+    -- Who performed the action and why.
+    operator TEXT NOT NULL,
+    reason TEXT NOT NULL,
+
+    -- The batch status before and after the action.
+    from_status TEXT NOT NULL,
+    to_status TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (payment_batch_id) REFERENCES payment_batches(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_operator_actions_payment_batch_id ON operator_actions(payment_batch_id, created_at);
@@ -0,0 +1,71 @@
+-- Append-only history of payment status changes.
+CREATE TABLE IF NOT EXISTS payment_events (
+    -- Monotonic ID, giving the order in which events were recorded.
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    payment_id TEXT NOT NULL,
+
+    -- The status before and after the change. from_status is NULL when the payment was created.
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+
+    -- The worker, API or operator that made the change.
+    actor TEXT NOT NULL,
+
+    -- The failure reason or other context for the change.
+    detail TEXT,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (payment_id) REFERENCES payments(id)
+);
+
+-- Append-only history of payment batch status changes and retries.
+CREATE TABLE IF NOT EXISTS batch_events (
+    -- Monotonic ID, giving the order in which events were recorded.
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    payment_batch_id TEXT NOT NULL,
+
+    -- The status before and after the change. from_status is NULL when the batch was created.
+    -- Retries are recorded with an unchanged status.
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+
+    -- The worker, API or operator that made the change.
+    actor TEXT NOT NULL,
+
+    -- The error message or other context for the change.
+    detail TEXT,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (payment_batch_id) REFERENCES payment_batches(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_payment_events_payment_id ON payment_events(payment_id, id);
+CREATE INDEX IF NOT EXISTS idx_batch_events_payment_batch_id ON batch_events(payment_batch_id, id);
+
+-- Events are never changed once written.
+CREATE TRIGGER IF NOT EXISTS payment_events_no_update BEFORE UPDATE ON payment_events
+BEGIN
+    SELECT RAISE(ABORT, 'payment_events is append-only');
+END;
+CREATE TRIGGER IF NOT EXISTS payment_events_no_delete BEFORE DELETE ON payment_events
+BEGIN
+    SELECT RAISE(ABORT, 'payment_events is append-only');
+END;
+CREATE TRIGGER IF NOT EXISTS batch_events_no_update BEFORE UPDATE ON batch_events
+BEGIN
+    SELECT RAISE(ABORT, 'batch_events is append-only');
+END;
+CREATE TRIGGER IF NOT EXISTS batch_events_no_delete BEFORE DELETE ON batch_events
+BEGIN
+    SELECT RAISE(ABORT, 'batch_events is append-only');
+END;
+
+-- Give rows that predate the history a starting point in their current state.
+INSERT INTO payment_events (payment_id, from_status, to_status, actor, detail, created_at)
+SELECT id, NULL, status, 'migration', failure_reason, updated_at FROM payments;
+INSERT INTO batch_events (payment_batch_id, from_status, to_status, actor, detail, created_at)
+SELECT id, NULL, status, 'migration', error_message, updated_at FROM payment_batches;
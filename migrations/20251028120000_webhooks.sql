@@ -0,0 +1,56 @@
+-- Optional per-payment webhook, called when the payment reaches CONFIRMED or FAILED.
+ALTER TABLE payments ADD COLUMN callback_url TEXT;
+
+-- Webhooks called for every payment of an account that reaches CONFIRMED or FAILED.
+CREATE TABLE IF NOT EXISTS webhook_subscriptions (
+    -- The unique ID for this subscription.
+    id TEXT PRIMARY KEY NOT NULL,
+
+    -- The PR account whose payments are reported.
+    account_name TEXT NOT NULL,
+
+    -- The endpoint that receives the webhook.
+    url TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE (account_name, url)
+);
+
+-- Transactional outbox of webhook deliveries. Rows are written in the same transaction as the
+-- payment status change they report and sent by the webhook dispatcher.
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    -- Sequential ID, also sent to the receiver so it can discard duplicates.
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    -- The payment this delivery reports on.
+    payment_id TEXT NOT NULL,
+
+    -- The event being reported, e.g. payment.confirmed or payment.failed.
+    event_type TEXT NOT NULL,
+
+    -- Where the delivery is sent and the exact JSON body that is signed and sent.
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+
+    -- The delivery state.
+    -- States:
+    -- PENDING: Not yet delivered, sent once next_attempt_at has passed.
+    -- DELIVERED: The receiver acknowledged the delivery with a 2xx response.
+    -- DEAD_LETTERED: Every attempt failed. Kept for inspection.
+    status TEXT NOT NULL,
+
+    -- Retry tracking with exponential backoff.
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_error TEXT,
+
+    delivered_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (payment_id) REFERENCES payments(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_payment_id ON webhook_deliveries(payment_id);
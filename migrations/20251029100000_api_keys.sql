@@ -0,0 +1,26 @@
+-- API keys used to authenticate requests to the HTTP API. Issued and revoked through the CLI.
+CREATE TABLE IF NOT EXISTS api_keys (
+    -- The unique ID for this key, used to revoke it.
+    id TEXT PRIMARY KEY NOT NULL,
+
+    -- A human-readable name for the key holder. Recorded as the operator for admin actions.
+    name TEXT NOT NULL,
+
+    -- SHA-256 hash of the key. The key itself is only shown once, when it is issued.
+    key_hash TEXT NOT NULL UNIQUE,
+
+    -- The start of the key, kept so it can be recognised in listings.
+    key_prefix TEXT NOT NULL,
+
+    -- JSON array of the account names the key may act on. "*" grants every account.
+    account_names TEXT NOT NULL,
+
+    -- JSON array of the scopes granted to the key.
+    -- Scopes: payments:read, payments:write, admin
+    scopes TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- Set when the key is revoked. Revoked keys are rejected.
+    revoked_at TIMESTAMP
+);
@@ -0,0 +1,4 @@
+-- SHA-256 of the normalized request that created the payment. A replay of the same
+-- (account_name, client_id) with a different fingerprint is rejected instead of silently
+-- returning the original payment. NULL for payments created before fingerprints were stored.
+ALTER TABLE payments ADD COLUMN request_fingerprint TEXT;
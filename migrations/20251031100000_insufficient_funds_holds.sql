@@ -0,0 +1,23 @@
+-- Why a RECEIVED payment is being held back from batching, e.g. 'insufficient_funds' when the
+-- account's balance does not cover it. NULL while the payment is eligible for batching.
+ALTER TABLE payments ADD COLUMN hold_reason TEXT;
+
+CREATE TABLE account_alerts (
+    -- Sequential ID, so alerts are ordered even within the same second.
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    -- The PR account the alert is about.
+    account_name TEXT NOT NULL,
+
+    -- What went wrong.
+    -- Kinds:
+    -- INSUFFICIENT_FUNDS: The account's balance does not cover its queued payments.
+    kind TEXT NOT NULL,
+
+    -- Human-readable details, e.g. the shortfall.
+    detail TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_account_alerts_account_name ON account_alerts(account_name, id);
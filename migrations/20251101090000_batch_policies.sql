@@ -0,0 +1,23 @@
+-- Per-account overrides of the batching policy. Accounts without a row use the default policy
+-- from the environment.
+CREATE TABLE IF NOT EXISTS batch_policies (
+    -- The PR account the policy applies to.
+    account_name TEXT PRIMARY KEY NOT NULL,
+
+    -- The most payments a single batch may contain.
+    max_recipients INTEGER NOT NULL,
+
+    -- The most a single batch may pay out in total, in µT. NULL for no limit.
+    max_total_amount BIGINT,
+
+    -- How many payments must be queued before a batch is created, unless the oldest has waited
+    -- max_age_secs.
+    min_recipients INTEGER NOT NULL,
+
+    -- How long the oldest queued payment may wait before a batch is created regardless of
+    -- min_recipients. Only NULL when min_recipients is 1, so payments never wait indefinitely.
+    max_age_secs INTEGER,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,5 @@
+-- The earliest time a payment may be batched. Payments created with a future execute_after wait in
+-- status SCHEDULED until it passes. NULL for payments that may be batched at once.
+ALTER TABLE payments ADD COLUMN execute_after TIMESTAMP;
+
+CREATE INDEX idx_payments_status_execute_after ON payments(status, execute_after);
@@ -0,0 +1,55 @@
+-- Recurring payouts. The payout scheduler creates a payment for each occurrence of the cadence.
+CREATE TABLE payout_schedules (
+    -- The unique ID for this schedule.
+    id TEXT PRIMARY KEY NOT NULL,
+
+    -- The PR account the payments are made from.
+    account_name TEXT NOT NULL,
+
+    -- The payment created on each occurrence.
+    recipient_address TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    payment_id TEXT,
+    callback_url TEXT,
+
+    -- A five-field cron expression evaluated in UTC, e.g. '0 9 * * 1' for every Monday at 09:00.
+    cadence TEXT NOT NULL,
+
+    -- Paused schedules create no payments. On resuming, runs due while paused are not reported
+    -- as missed.
+    is_paused BOOLEAN NOT NULL DEFAULT 0,
+
+    -- The next occurrence that has not been run yet.
+    next_run_at TIMESTAMP NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_payout_schedules_due ON payout_schedules(is_paused, next_run_at);
+CREATE INDEX idx_payout_schedules_account_name ON payout_schedules(account_name, created_at);
+
+-- One row per occurrence of a schedule, so no occurrence is paid twice.
+CREATE TABLE payout_schedule_runs (
+    schedule_id TEXT NOT NULL,
+
+    -- The occurrence of the cadence this run is for.
+    scheduled_for TIMESTAMP NOT NULL,
+
+    -- What happened to the occurrence.
+    -- Statuses:
+    -- CREATED: The payment was created.
+    -- MISSED: The occurrence passed while the processor was not running and was not paid. Only
+    --         the latest occurrence is paid after downtime, the earlier ones are reported in a
+    --         MISSED_PAYOUT_RUNS account alert.
+    status TEXT NOT NULL,
+
+    -- The payment created for the occurrence.
+    payment_id TEXT,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (schedule_id, scheduled_for),
+    FOREIGN KEY (schedule_id) REFERENCES payout_schedules(id) ON DELETE CASCADE,
+    FOREIGN KEY (payment_id) REFERENCES payments(id)
+);
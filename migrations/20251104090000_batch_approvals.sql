@@ -0,0 +1,42 @@
+-- Operator approval of high-value batches before they are signed.
+-- Batches that need approval wait in the AWAITING_APPROVAL status, between PENDING_BATCHING and
+-- AWAITING_SIGNATURE.
+
+-- Per-account rules for which batches need approval. Accounts without a row never need approval.
+CREATE TABLE IF NOT EXISTS approval_policies (
+    -- The PR account the policy applies to.
+    account_name TEXT PRIMARY KEY NOT NULL,
+
+    -- How many distinct operators must approve a batch.
+    required_approvals INTEGER NOT NULL,
+
+    -- A batch needs approval when it pays out at least this much in total, in µT, or has at least
+    -- min_recipients payments. When both are NULL every batch of the account needs approval.
+    min_total_amount BIGINT,
+    min_recipients INTEGER,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- The number of approvals the batch's current unsigned transaction needs. Set when the batch
+-- enters AWAITING_APPROVAL, so later policy changes do not affect it. NULL when no approval applied.
+ALTER TABLE payment_batches ADD COLUMN approvals_required INTEGER;
+
+-- Approvals given to a batch. An approval covers the unsigned transaction built under one PR
+-- idempotency key, so a rebuilt batch must be approved again.
+CREATE TABLE IF NOT EXISTS batch_approvals (
+    payment_batch_id TEXT NOT NULL,
+    pr_idempotency_key TEXT NOT NULL,
+
+    -- The name of the API key of the approving operator.
+    approver TEXT NOT NULL,
+    reason TEXT NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (payment_batch_id, pr_idempotency_key, approver),
+    FOREIGN KEY (payment_batch_id) REFERENCES payment_batches(id)
+);
+
+-- Approvals and rejections are also recorded in operator_actions, with the actions APPROVE and REJECT.
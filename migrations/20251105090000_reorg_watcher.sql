@@ -0,0 +1,7 @@
+-- Chain reorganization detection.
+-- mined_height, mined_header_hash and mined_timestamp are now recorded when a batch's transaction is
+-- first seen mined, not only once it is confirmed. A batch whose block left the best chain and whose
+-- transaction the base node no longer knows moves to the REORGED status.
+
+-- The reorg watcher re-checks confirmed batches mined above a height.
+CREATE INDEX IF NOT EXISTS idx_payment_batches_status_mined_height ON payment_batches(status, mined_height);
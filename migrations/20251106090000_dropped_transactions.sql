@@ -0,0 +1,9 @@
+-- Resubmission and expiry of transactions that disappear from the mempool.
+-- A batch whose transaction stays unknown to the base node past the configured timeout, and which the
+-- base node will not take back, moves to the DROPPED status.
+
+-- When the base node was first found not to know the batch's transaction. NULL while it is known.
+ALTER TABLE payment_batches ADD COLUMN missing_since TIMESTAMP;
+
+-- How many times the batch's transaction was resubmitted after going missing.
+ALTER TABLE payment_batches ADD COLUMN resubmission_count INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,18 @@
+-- Exponential backoff between failed attempts, with a separate retry budget per pipeline stage.
+
+-- When a batch's failed stage is attempted again. Workers skip the batch until then; NULL means now.
+ALTER TABLE payment_batches ADD COLUMN next_retry_at TIMESTAMP;
+
+-- Failed attempts per batch and stage, so e.g. broadcast failures do not use up the retries of the
+-- unsigned transaction creation. payment_batches.retry_count keeps counting all of them.
+CREATE TABLE IF NOT EXISTS payment_batch_stage_retries (
+    payment_batch_id TEXT NOT NULL,
+
+    -- CREATE_UNSIGNED_TX, SIGN or BROADCAST.
+    stage TEXT NOT NULL,
+
+    retries INTEGER NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (payment_batch_id, stage),
+    FOREIGN KEY (payment_batch_id) REFERENCES payment_batches(id)
+);
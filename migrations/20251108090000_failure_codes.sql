@@ -0,0 +1,3 @@
+-- Machine-readable cause of a FAILED payment, next to the human-readable failure_reason.
+-- One of the snake_case codes of FailureCode, e.g. account_not_found or signer_unavailable.
+ALTER TABLE payments ADD COLUMN failure_code TEXT;
@@ -0,0 +1,3 @@
+-- Batches that PR refuses in a way a single payment could cause are split into two halves to isolate the
+-- payment. The original batch moves to the SPLIT status and the halves point back to it.
+ALTER TABLE payment_batches ADD COLUMN parent_batch_id TEXT REFERENCES payment_batches(id);
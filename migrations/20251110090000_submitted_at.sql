@@ -0,0 +1,11 @@
+-- When a batch's transaction was first submitted to the base node. Once set, the transaction may be on chain,
+-- so operators can no longer fail the batch.
+ALTER TABLE payment_batches ADD COLUMN submitted_at TIMESTAMP;
+
+-- Batches that were claimed for broadcasting before the column existed may have been submitted.
+UPDATE payment_batches
+SET submitted_at = (
+    SELECT MIN(created_at)
+    FROM batch_events
+    WHERE batch_events.payment_batch_id = payment_batches.id AND batch_events.to_status = 'BROADCASTING'
+);
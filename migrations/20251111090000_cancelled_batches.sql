@@ -0,0 +1,8 @@
+-- Until when PR keeps the UTXOs of a batch's unsigned transaction reserved. A batch rebuilt after a payment was
+-- cancelled waits until then, since PR has no call to release them sooner.
+ALTER TABLE payment_batches ADD COLUMN utxos_locked_until TIMESTAMP;
+
+-- Batches whose payments were all cancelled moved to FAILED before the CANCELLED status existed.
+UPDATE payment_batches
+SET status = 'CANCELLED'
+WHERE status = 'FAILED' AND error_message = 'All payments of the batch were cancelled';
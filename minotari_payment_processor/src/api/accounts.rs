@@ -0,0 +1,68 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    api::{auth::Authenticated, error::ApiError, pagination},
+    db::{
+        api_key::ApiScope,
+        event::{AccountAlert, AlertKind},
+    },
+};
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AccountAlertResponse {
+    pub kind: AlertKind,
+    pub detail: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<AccountAlert> for AccountAlertResponse {
+    fn from(alert: AccountAlert) -> Self {
+        AccountAlertResponse {
+            kind: alert.kind,
+            detail: alert.detail,
+            created_at: alert.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListAccountAlertsQuery {
+    /// Number of alerts to return, defaults to 50 and is capped at 500.
+    pub limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/accounts/{account_name}/alerts",
+    params(ListAccountAlertsQuery),
+    responses(
+        (status = 200, description = "Most recent alerts of the account, newest first", body = Vec<AccountAlertResponse>),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_list_account_alerts(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(account_name): Path<String>,
+    Query(query): Query<ListAccountAlertsQuery>,
+) -> Result<Json<Vec<AccountAlertResponse>>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    auth.require_account(&account_name)?;
+    let limit = pagination::page_limit(query.limit)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let alerts = AccountAlert::find_by_account_name(&mut conn, &account_name, limit).await?;
+
+    Ok(Json(alerts.into_iter().map(AccountAlertResponse::from).collect()))
+}
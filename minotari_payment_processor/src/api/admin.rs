@@ -0,0 +1,653 @@
+use axum::{
+    Json,
+    extract::{FromRequestParts, Path, State},
+    http::request::Parts,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{SqliteConnection, SqlitePool};
+use utoipa::ToSchema;
+
+use crate::{
+    api::{auth::Authenticated, batches::PaymentBatchResponse, error::ApiError},
+    db::{
+        api_key::ApiScope,
+        approval::BatchApproval,
+        event::Actor,
+        operator_action::{OperatorAction, OperatorActionKind},
+        payment::{FailureCode, Payment},
+        payment_batch::{PaymentBatch, PaymentBatchStatus, RetryStage},
+    },
+};
+
+/// An API key with the `admin` scope. Its name is recorded as the operator of admin actions.
+pub struct Operator {
+    pub name: String,
+    pub auth: Authenticated,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Operator {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let auth = Authenticated::from_request_parts(parts, state).await?;
+        auth.require_scope(ApiScope::Admin)?;
+        Ok(Operator {
+            name: auth.0.name.clone(),
+            auth,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct RetryBatchRequest {
+    pub stage: RetryStage,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct BatchActionRequest {
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BatchApprovalResponse {
+    pub approver: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<BatchApproval> for BatchApprovalResponse {
+    fn from(approval: BatchApproval) -> Self {
+        BatchApprovalResponse {
+            approver: approval.approver,
+            reason: approval.reason,
+            created_at: approval.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BatchApprovalListResponse {
+    /// How many approvals the batch needs. Absent when no approval policy applied to it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approvals_required: Option<i64>,
+    /// Approvals of the batch's current unsigned transaction, oldest first.
+    pub approvals: Vec<BatchApprovalResponse>,
+}
+
+fn validate_reason(reason: &str) -> Result<(), ApiError> {
+    if reason.trim().is_empty() {
+        return Err(ApiError::BadRequest("A reason is required".to_string()));
+    }
+    Ok(())
+}
+
+/// Whether a failed batch's submitted transaction was rejected because another transaction spent its inputs,
+/// which the broadcaster only concludes after the base node did not know the transaction's kernel.
+async fn inputs_spent_elsewhere(conn: &mut SqliteConnection, batch: &PaymentBatch) -> Result<bool, sqlx::Error> {
+    if batch.status != PaymentBatchStatus::Failed {
+        return Ok(false);
+    }
+    let payments = Payment::find_by_batch_id(conn, &batch.id).await?;
+    let inputs_spent = FailureCode::InputsSpent.to_string();
+    Ok(!payments.is_empty()
+        && payments
+            .iter()
+            .all(|payment| payment.failure_code.as_deref() == Some(inputs_spent.as_str())))
+}
+
+fn concurrent_change(batch_id: &str) -> ApiError {
+    ApiError::Conflict(format!(
+        "Batch {} changed status while the action was applied, reload it and try again",
+        batch_id
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/admin/batches/{batch_id}/retry",
+    request_body = RetryBatchRequest,
+    responses(
+        (status = 200, description = "Batch restarted from the requested stage", body = PaymentBatchResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope", body = ApiError),
+        (status = 404, description = "Payment batch not found", body = ApiError),
+        (status = 409, description = "Batch cannot be retried from the requested stage, e.g. because its transaction was submitted", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_retry_batch(
+    State(db_pool): State<SqlitePool>,
+    Operator { name: operator, auth }: Operator,
+    Path(batch_id): Path<String>,
+    Json(request): Json<RetryBatchRequest>,
+) -> Result<Json<PaymentBatchResponse>, ApiError> {
+    validate_reason(&request.reason)?;
+    let mut transaction = db_pool.begin().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    if !matches!(
+        batch.status,
+        PaymentBatchStatus::Failed | PaymentBatchStatus::VerificationFailed | PaymentBatchStatus::Reorged
+    ) {
+        return Err(ApiError::Conflict(
+            "Only FAILED, VERIFICATION_FAILED and REORGED batches can be retried".to_string(),
+        ));
+    }
+    // A reorged transaction can still be mined again, so only the same transaction may be sent; a new one
+    // could pay the recipients twice.
+    if batch.status == PaymentBatchStatus::Reorged && request.stage != RetryStage::Broadcast {
+        return Err(ApiError::Conflict(
+            "A REORGED batch can only be retried from BROADCAST".to_string(),
+        ));
+    }
+    if batch.mined_height.is_some() {
+        return Err(ApiError::Conflict(
+            "Batch transaction has already been mined".to_string(),
+        ));
+    }
+    match request.stage {
+        RetryStage::CreateUnsignedTx => {},
+        RetryStage::Sign if batch.unsigned_tx_json.is_none() => {
+            return Err(ApiError::Conflict(
+                "Batch has no unsigned transaction to sign".to_string(),
+            ));
+        },
+        RetryStage::Broadcast if batch.signed_tx_json.is_none() => {
+            return Err(ApiError::Conflict(
+                "Batch has no signed transaction to broadcast".to_string(),
+            ));
+        },
+        RetryStage::Sign | RetryStage::Broadcast => {},
+    }
+    // A transaction that was ever submitted may still be in a mempool, and a new one could pay the recipients
+    // twice. Only a double spend rejection for which the kernel was not found proves it can never be mined.
+    if request.stage != RetryStage::Broadcast
+        && PaymentBatch::was_submitted(&mut transaction, &batch_id).await?
+        && !inputs_spent_elsewhere(&mut transaction, &batch).await?
+    {
+        return Err(ApiError::Conflict(format!(
+            "The batch's transaction was submitted to the base node and may still be mined, so it can only be \
+             retried from BROADCAST, not {}",
+            request.stage
+        )));
+    }
+
+    // A signed transaction is verified again before every broadcast, so retrying a
+    // VERIFICATION_FAILED batch from BROADCAST only helps once the cause has been fixed.
+    if !PaymentBatch::retry_from_stage(
+        &mut transaction,
+        &batch_id,
+        batch.status.clone(),
+        request.stage,
+        &Actor::Operator(operator.clone()),
+        Some(&request.reason),
+    )
+    .await?
+    {
+        return Err(concurrent_change(&batch_id));
+    }
+    // A batch retried from signing may have gone back for approval rather than to the stage's status.
+    let retried = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    let stage = request.stage.to_string();
+    OperatorAction::create(
+        &mut transaction,
+        &batch_id,
+        OperatorActionKind::Retry,
+        Some(&stage),
+        &operator,
+        &request.reason,
+        &batch.status,
+        &retried.status,
+    )
+    .await?;
+    let batch = retried;
+
+    transaction.commit().await?;
+    println!(
+        "Operator {} retried batch {} from stage {}: {}",
+        operator, batch_id, stage, request.reason
+    );
+
+    Ok(Json(PaymentBatchResponse::from_batch(batch, false)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/admin/batches/{batch_id}/release",
+    request_body = BatchActionRequest,
+    responses(
+        (status = 200, description = "Batch failed and its payments returned to RECEIVED", body = PaymentBatchResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope", body = ApiError),
+        (status = 404, description = "Payment batch not found", body = ApiError),
+        (status = 409, description = "Batch payments cannot be released", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_release_batch_payments(
+    State(db_pool): State<SqlitePool>,
+    Operator { name: operator, auth }: Operator,
+    Path(batch_id): Path<String>,
+    Json(request): Json<BatchActionRequest>,
+) -> Result<Json<PaymentBatchResponse>, ApiError> {
+    validate_reason(&request.reason)?;
+    let mut transaction = db_pool.begin().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    // Once a transaction has been signed it may reach the chain, so paying the same
+    // recipients from a new batch could pay them twice.
+    let releasable = matches!(
+        batch.status,
+        PaymentBatchStatus::PendingBatching
+            | PaymentBatchStatus::AwaitingApproval
+            | PaymentBatchStatus::AwaitingSignature
+            | PaymentBatchStatus::Failed
+    ) && batch.signed_tx_json.is_none();
+    if !releasable {
+        return Err(ApiError::Conflict(format!(
+            "Payments of a {} batch with a signed transaction cannot be released",
+            batch.status
+        )));
+    }
+
+    let error_message = format!("Payments released by operator {}: {}", operator, request.reason);
+    if !PaymentBatch::release_payments(
+        &mut transaction,
+        &batch_id,
+        batch.status.clone(),
+        &error_message,
+        &Actor::Operator(operator.clone()),
+    )
+    .await?
+    {
+        return Err(concurrent_change(&batch_id));
+    }
+    OperatorAction::create(
+        &mut transaction,
+        &batch_id,
+        OperatorActionKind::ReleasePayments,
+        None,
+        &operator,
+        &request.reason,
+        &batch.status,
+        &PaymentBatchStatus::Failed,
+    )
+    .await?;
+    let batch = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+
+    transaction.commit().await?;
+    println!("{} (batch {})", error_message, batch_id);
+
+    Ok(Json(PaymentBatchResponse::from_batch(batch, false)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/admin/batches/{batch_id}/fail",
+    request_body = BatchActionRequest,
+    responses(
+        (status = 200, description = "Batch and its payments marked as FAILED", body = PaymentBatchResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope", body = ApiError),
+        (status = 404, description = "Payment batch not found", body = ApiError),
+        (status = 409, description = "Batch cannot be failed, e.g. because its transaction was submitted", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_force_fail_batch(
+    State(db_pool): State<SqlitePool>,
+    Operator { name: operator, auth }: Operator,
+    Path(batch_id): Path<String>,
+    Json(request): Json<BatchActionRequest>,
+) -> Result<Json<PaymentBatchResponse>, ApiError> {
+    validate_reason(&request.reason)?;
+    let mut transaction = db_pool.begin().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    match batch.status {
+        PaymentBatchStatus::Confirmed
+        | PaymentBatchStatus::Failed
+        | PaymentBatchStatus::Dropped
+        | PaymentBatchStatus::Split
+        | PaymentBatchStatus::Cancelled => {
+            return Err(ApiError::Conflict(format!("Batch is already {}", batch.status)));
+        },
+        // The transaction may already be on its way to the chain; failing its payments would
+        // invite the client to pay the same recipients again.
+        PaymentBatchStatus::Broadcasting | PaymentBatchStatus::AwaitingConfirmation | PaymentBatchStatus::Reorged => {
+            return Err(ApiError::Conflict(format!(
+                "A {} batch may already be on chain and cannot be failed",
+                batch.status
+            )));
+        },
+        _ => {},
+    }
+    // Likewise once any broadcast attempt submitted the transaction, even if the batch was sent back since.
+    if PaymentBatch::was_submitted(&mut transaction, &batch_id).await? {
+        return Err(ApiError::Conflict(
+            "The batch's transaction was submitted to the base node and may already be on chain, so it cannot be failed"
+                .to_string(),
+        ));
+    }
+
+    let error_message = format!("Failed by operator {}: {}", operator, request.reason);
+    if !PaymentBatch::update_to_failed_from(
+        &mut transaction,
+        &batch_id,
+        batch.status.clone(),
+        FailureCode::OperatorFailed,
+        &error_message,
+        &Actor::Operator(operator.clone()),
+    )
+    .await?
+    {
+        return Err(concurrent_change(&batch_id));
+    }
+    OperatorAction::create(
+        &mut transaction,
+        &batch_id,
+        OperatorActionKind::ForceFail,
+        None,
+        &operator,
+        &request.reason,
+        &batch.status,
+        &PaymentBatchStatus::Failed,
+    )
+    .await?;
+    let batch = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+
+    transaction.commit().await?;
+    println!("{} (batch {})", error_message, batch_id);
+
+    Ok(Json(PaymentBatchResponse::from_batch(batch, false)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/batches/{batch_id}/approvals",
+    responses(
+        (status = 200, description = "Approvals of the batch's current unsigned transaction", body = BatchApprovalListResponse),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope", body = ApiError),
+        (status = 404, description = "Payment batch not found", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_list_batch_approvals(
+    State(db_pool): State<SqlitePool>,
+    Operator { auth, .. }: Operator,
+    Path(batch_id): Path<String>,
+) -> Result<Json<BatchApprovalListResponse>, ApiError> {
+    let mut conn = db_pool.acquire().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut conn, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    let approvals = BatchApproval::find_by_batch(&mut conn, &batch_id, &batch.pr_idempotency_key).await?;
+
+    Ok(Json(BatchApprovalListResponse {
+        approvals_required: batch.approvals_required,
+        approvals: approvals.into_iter().map(BatchApprovalResponse::from).collect(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/admin/batches/{batch_id}/approve",
+    request_body = BatchActionRequest,
+    responses(
+        (status = 200, description = "Approval recorded. The batch moves to AWAITING_SIGNATURE once it has enough approvals", body = PaymentBatchResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope", body = ApiError),
+        (status = 404, description = "Payment batch not found", body = ApiError),
+        (status = 409, description = "Batch is not awaiting approval or the operator already approved it", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_approve_batch(
+    State(db_pool): State<SqlitePool>,
+    Operator { name: operator, auth }: Operator,
+    Path(batch_id): Path<String>,
+    Json(request): Json<BatchActionRequest>,
+) -> Result<Json<PaymentBatchResponse>, ApiError> {
+    validate_reason(&request.reason)?;
+    let mut transaction = db_pool.begin().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    if batch.status != PaymentBatchStatus::AwaitingApproval {
+        return Err(ApiError::Conflict(
+            "Only AWAITING_APPROVAL batches can be approved".to_string(),
+        ));
+    }
+
+    // Approvals are tied to the unsigned transaction, so a rebuilt batch starts from none.
+    if !BatchApproval::record(
+        &mut transaction,
+        &batch_id,
+        &batch.pr_idempotency_key,
+        &operator,
+        &request.reason,
+    )
+    .await?
+    {
+        return Err(ApiError::Conflict(format!(
+            "Operator {} already approved batch {}",
+            operator, batch_id
+        )));
+    }
+    let approvers: Vec<String> = BatchApproval::find_by_batch(&mut transaction, &batch_id, &batch.pr_idempotency_key)
+        .await?
+        .into_iter()
+        .map(|approval| approval.approver)
+        .collect();
+    let approvals_required = batch.approvals_required.unwrap_or(1);
+    let progress = format!("{} of {} approvals", approvers.len(), approvals_required);
+
+    let to_status = if approvers.len() as i64 >= approvals_required {
+        let detail = format!("Approved by {}", approvers.join(", "));
+        if !PaymentBatch::update_to_approved(
+            &mut transaction,
+            &batch_id,
+            &batch.pr_idempotency_key,
+            &Actor::Operator(operator.clone()),
+            Some(&detail),
+        )
+        .await?
+        {
+            return Err(concurrent_change(&batch_id));
+        }
+        PaymentBatchStatus::AwaitingSignature
+    } else {
+        PaymentBatchStatus::AwaitingApproval
+    };
+    OperatorAction::create(
+        &mut transaction,
+        &batch_id,
+        OperatorActionKind::Approve,
+        Some(&progress),
+        &operator,
+        &request.reason,
+        &batch.status,
+        &to_status,
+    )
+    .await?;
+    let batch = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+
+    transaction.commit().await?;
+    println!(
+        "Operator {} approved batch {} ({}): {}",
+        operator, batch_id, progress, request.reason
+    );
+
+    Ok(Json(PaymentBatchResponse::from_batch(batch, false)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/admin/batches/{batch_id}/reject",
+    request_body = BatchActionRequest,
+    responses(
+        (status = 200, description = "Batch and its payments marked as FAILED", body = PaymentBatchResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope", body = ApiError),
+        (status = 404, description = "Payment batch not found", body = ApiError),
+        (status = 409, description = "Batch is not awaiting approval", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_reject_batch(
+    State(db_pool): State<SqlitePool>,
+    Operator { name: operator, auth }: Operator,
+    Path(batch_id): Path<String>,
+    Json(request): Json<BatchActionRequest>,
+) -> Result<Json<PaymentBatchResponse>, ApiError> {
+    validate_reason(&request.reason)?;
+    let mut transaction = db_pool.begin().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    if batch.status != PaymentBatchStatus::AwaitingApproval {
+        return Err(ApiError::Conflict(
+            "Only AWAITING_APPROVAL batches can be rejected".to_string(),
+        ));
+    }
+
+    // A single rejection is final, whatever approvals the batch already has.
+    let error_message = format!("Rejected by operator {}: {}", operator, request.reason);
+    if !PaymentBatch::update_to_failed_from(
+        &mut transaction,
+        &batch_id,
+        PaymentBatchStatus::AwaitingApproval,
+        FailureCode::ApprovalRejected,
+        &error_message,
+        &Actor::Operator(operator.clone()),
+    )
+    .await?
+    {
+        return Err(concurrent_change(&batch_id));
+    }
+    OperatorAction::create(
+        &mut transaction,
+        &batch_id,
+        OperatorActionKind::Reject,
+        None,
+        &operator,
+        &request.reason,
+        &batch.status,
+        &PaymentBatchStatus::Failed,
+    )
+    .await?;
+    let batch = PaymentBatch::find_by_id(&mut transaction, &batch_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+
+    transaction.commit().await?;
+    println!("{} (batch {})", error_message, batch_id);
+
+    Ok(Json(PaymentBatchResponse::from_batch(batch, false)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    async fn operator(conn: &mut SqliteConnection) -> Operator {
+        Operator {
+            name: "alice".to_string(),
+            auth: Authenticated::for_scopes(conn, &[ApiScope::Admin]).await,
+        }
+    }
+
+    /// A FAILED batch whose signed transaction was submitted, with its payments failed with `code`.
+    async fn failed_submitted_batch(conn: &mut SqliteConnection, code: FailureCode) -> PaymentBatch {
+        let batch = test_support::create_batch(conn, 2, 1_000).await;
+        sqlx::query("UPDATE payment_batches SET unsigned_tx_json = '{}' WHERE id = ?")
+            .bind(&batch.id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        test_support::set_batch_signed_tx(conn, &batch.id, r#"{"kernel":"admin-test"}"#).await;
+        PaymentBatch::record_submission(conn, &batch.id).await.unwrap();
+        test_support::set_batch_status(conn, &batch.id, PaymentBatchStatus::Failed).await;
+        Payment::fail_payments_in_batch(conn, &batch.id, code, "rejected", &Actor::Broadcaster)
+            .await
+            .unwrap();
+        batch
+    }
+
+    async fn retry(pool: &SqlitePool, batch_id: &str, stage: RetryStage) -> Result<PaymentBatchResponse, ApiError> {
+        let mut conn = pool.acquire().await.unwrap();
+        let request = RetryBatchRequest {
+            stage,
+            reason: "test".to_string(),
+        };
+        api_retry_batch(
+            State(pool.clone()),
+            operator(&mut conn).await,
+            Path(batch_id.to_string()),
+            Json(request),
+        )
+        .await
+        .map(|Json(batch)| batch)
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn submitted_batch_is_only_retried_from_broadcast(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = failed_submitted_batch(&mut conn, FailureCode::InvalidTransaction).await;
+
+        for stage in [RetryStage::CreateUnsignedTx, RetryStage::Sign] {
+            let result = retry(&pool, &batch.id, stage).await;
+            assert!(matches!(result, Err(ApiError::Conflict(_))), "{}", stage);
+        }
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Failed);
+
+        retry(&pool, &batch.id, RetryStage::Broadcast).await.unwrap();
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::AwaitingBroadcast);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn submitted_batch_with_inputs_spent_elsewhere_is_rebuilt(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = failed_submitted_batch(&mut conn, FailureCode::InputsSpent).await;
+
+        retry(&pool, &batch.id, RetryStage::CreateUnsignedTx).await.unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::PendingBatching);
+        assert!(batch.signed_tx_json.is_none());
+    }
+}
@@ -0,0 +1,145 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+use crate::{
+    api::{admin::Operator, error::ApiError},
+    db::approval::{AccountApprovalPolicy, ApprovalPolicy},
+};
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AccountApprovalPolicyResponse {
+    pub account_name: String,
+    pub policy: ApprovalPolicy,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<AccountApprovalPolicy> for AccountApprovalPolicyResponse {
+    fn from(account_policy: AccountApprovalPolicy) -> Self {
+        AccountApprovalPolicyResponse {
+            account_name: account_policy.account_name,
+            policy: account_policy.policy,
+            updated_at: account_policy.updated_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/approval-policies",
+    responses(
+        (status = 200, description = "Approval policies of all accounts that have one", body = Vec<AccountApprovalPolicyResponse>),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_list_approval_policies(
+    State(db_pool): State<SqlitePool>,
+    Operator { auth, .. }: Operator,
+) -> Result<Json<Vec<AccountApprovalPolicyResponse>>, ApiError> {
+    let mut conn = db_pool.acquire().await?;
+
+    let policies = AccountApprovalPolicy::list(&mut conn).await?;
+
+    Ok(Json(
+        policies
+            .into_iter()
+            .filter(|account_policy| auth.can_see(&account_policy.account_name))
+            .map(AccountApprovalPolicyResponse::from)
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/approval-policies/{account_name}",
+    responses(
+        (status = 200, description = "The account's approval policy", body = AccountApprovalPolicyResponse),
+        (status = 404, description = "Account has no approval policy", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_get_approval_policy(
+    State(db_pool): State<SqlitePool>,
+    Operator { auth, .. }: Operator,
+    Path(account_name): Path<String>,
+) -> Result<Json<AccountApprovalPolicyResponse>, ApiError> {
+    auth.require_account(&account_name)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let account_policy = AccountApprovalPolicy::find_by_account_name(&mut conn, &account_name)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Account has no approval policy".to_string()))?;
+
+    Ok(Json(AccountApprovalPolicyResponse::from(account_policy)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/admin/approval-policies/{account_name}",
+    request_body = ApprovalPolicy,
+    responses(
+        (status = 200, description = "Approval policy set", body = AccountApprovalPolicyResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_set_approval_policy(
+    State(db_pool): State<SqlitePool>,
+    Operator { name: operator, auth }: Operator,
+    Path(account_name): Path<String>,
+    Json(policy): Json<ApprovalPolicy>,
+) -> Result<Json<AccountApprovalPolicyResponse>, ApiError> {
+    auth.require_account(&account_name)?;
+    policy.validate().map_err(ApiError::BadRequest)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let account_policy = AccountApprovalPolicy::upsert(&mut conn, &account_name, &policy).await?;
+    println!(
+        "Operator {} set the approval policy of account {}: {:?}",
+        operator, account_name, policy
+    );
+
+    Ok(Json(AccountApprovalPolicyResponse::from(account_policy)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/admin/approval-policies/{account_name}",
+    responses(
+        (status = 204, description = "Approval policy removed, new batches of the account no longer need approval"),
+        (status = 404, description = "Account has no approval policy", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_delete_approval_policy(
+    State(db_pool): State<SqlitePool>,
+    Operator { name: operator, auth }: Operator,
+    Path(account_name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    auth.require_account(&account_name)?;
+    let mut conn = db_pool.acquire().await?;
+
+    if !AccountApprovalPolicy::delete(&mut conn, &account_name).await? {
+        return Err(ApiError::NotFound("Account has no approval policy".to_string()));
+    }
+    println!(
+        "Operator {} removed the approval policy of account {}",
+        operator, account_name
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
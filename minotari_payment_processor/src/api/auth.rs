@@ -0,0 +1,91 @@
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{
+    api::{AppState, error::ApiError},
+    db::api_key::{ApiKey, ApiScope},
+};
+
+/// Authenticates every request with an `Authorization: Bearer <api key>` header and makes the
+/// key available to handlers through the [`Authenticated`] extractor.
+pub async fn authenticate(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let secret = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::Unauthorized("Missing bearer token".to_string()))?;
+
+    let mut conn = state.db_pool.acquire().await?;
+    let api_key = ApiKey::find_active_by_secret(&mut conn, secret)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("Invalid or revoked API key".to_string()))?;
+    drop(conn);
+
+    request.extensions_mut().insert(api_key);
+    Ok(next.run(request).await)
+}
+
+/// The API key that authenticated the request.
+pub struct Authenticated(pub ApiKey);
+
+impl<S: Send + Sync> FromRequestParts<S> for Authenticated {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ApiKey>()
+            .cloned()
+            .map(Authenticated)
+            .ok_or_else(|| ApiError::Unauthorized("Missing bearer token".to_string()))
+    }
+}
+
+#[cfg(test)]
+impl Authenticated {
+    /// A new API key with `scopes` for every account, as [`authenticate`] hands it to handlers.
+    pub(crate) async fn for_scopes(conn: &mut sqlx::SqliteConnection, scopes: &[ApiScope]) -> Self {
+        let all_accounts = [crate::db::api_key::ALL_ACCOUNTS.to_string()];
+        let (api_key, _) = ApiKey::create(conn, "test", &all_accounts, scopes).await.unwrap();
+        Authenticated(api_key)
+    }
+}
+
+impl Authenticated {
+    pub fn require_scope(&self, scope: ApiScope) -> Result<(), ApiError> {
+        if !self.0.has_scope(scope) {
+            return Err(ApiError::Forbidden(format!("API key lacks the {} scope", scope)));
+        }
+        Ok(())
+    }
+
+    pub fn require_account(&self, account_name: &str) -> Result<(), ApiError> {
+        if !self.0.can_access_account(account_name) {
+            return Err(ApiError::Forbidden(format!(
+                "API key is not authorized for account {}",
+                account_name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether a resource of the given account is visible to this key. Resources of other
+    /// accounts are reported as not found, so their existence is not revealed.
+    pub fn can_see(&self, account_name: &str) -> bool {
+        self.0.can_access_account(account_name)
+    }
+
+    /// The accounts listings must be restricted to, or `None` if the key sees every account.
+    pub fn account_restriction(&self) -> Option<&[String]> {
+        self.0.account_restriction()
+    }
+}
@@ -0,0 +1,164 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+use crate::{
+    api::{admin::Operator, error::ApiError},
+    db::batch_policy::{AccountBatchPolicy, BatchPolicy},
+};
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AccountBatchPolicyResponse {
+    pub account_name: String,
+    pub policy: BatchPolicy,
+    /// Set when the account has no override and uses the default policy.
+    pub is_default: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<AccountBatchPolicy> for AccountBatchPolicyResponse {
+    fn from(account_policy: AccountBatchPolicy) -> Self {
+        AccountBatchPolicyResponse {
+            account_name: account_policy.account_name,
+            policy: account_policy.policy,
+            is_default: false,
+            updated_at: Some(account_policy.updated_at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BatchPolicyListResponse {
+    /// Applies to every account without an override. Set through the environment.
+    pub default_policy: BatchPolicy,
+    pub overrides: Vec<AccountBatchPolicyResponse>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/batch-policies",
+    responses(
+        (status = 200, description = "Default policy and per-account overrides", body = BatchPolicyListResponse),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_list_batch_policies(
+    State(db_pool): State<SqlitePool>,
+    State(default_policy): State<BatchPolicy>,
+    Operator { auth, .. }: Operator,
+) -> Result<Json<BatchPolicyListResponse>, ApiError> {
+    let mut conn = db_pool.acquire().await?;
+
+    let overrides = AccountBatchPolicy::list(&mut conn).await?;
+
+    Ok(Json(BatchPolicyListResponse {
+        default_policy,
+        overrides: overrides
+            .into_iter()
+            .filter(|account_policy| auth.can_see(&account_policy.account_name))
+            .map(AccountBatchPolicyResponse::from)
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/batch-policies/{account_name}",
+    responses(
+        (status = 200, description = "The policy in effect for the account", body = AccountBatchPolicyResponse),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_get_batch_policy(
+    State(db_pool): State<SqlitePool>,
+    State(default_policy): State<BatchPolicy>,
+    Operator { auth, .. }: Operator,
+    Path(account_name): Path<String>,
+) -> Result<Json<AccountBatchPolicyResponse>, ApiError> {
+    auth.require_account(&account_name)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let response = match AccountBatchPolicy::find_by_account_name(&mut conn, &account_name).await? {
+        Some(account_policy) => AccountBatchPolicyResponse::from(account_policy),
+        None => AccountBatchPolicyResponse {
+            account_name,
+            policy: default_policy,
+            is_default: true,
+            updated_at: None,
+        },
+    };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/admin/batch-policies/{account_name}",
+    request_body = BatchPolicy,
+    responses(
+        (status = 200, description = "Policy override set", body = AccountBatchPolicyResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_set_batch_policy(
+    State(db_pool): State<SqlitePool>,
+    Operator { name: operator, auth }: Operator,
+    Path(account_name): Path<String>,
+    Json(policy): Json<BatchPolicy>,
+) -> Result<Json<AccountBatchPolicyResponse>, ApiError> {
+    auth.require_account(&account_name)?;
+    policy.validate().map_err(ApiError::BadRequest)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let account_policy = AccountBatchPolicy::upsert(&mut conn, &account_name, &policy).await?;
+    println!(
+        "Operator {} set the batch policy of account {}: {:?}",
+        operator, account_name, policy
+    );
+
+    Ok(Json(AccountBatchPolicyResponse::from(account_policy)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/admin/batch-policies/{account_name}",
+    responses(
+        (status = 204, description = "Policy override removed, the account uses the default policy"),
+        (status = 404, description = "Account has no policy override", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the admin scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_delete_batch_policy(
+    State(db_pool): State<SqlitePool>,
+    Operator { name: operator, auth }: Operator,
+    Path(account_name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    auth.require_account(&account_name)?;
+    let mut conn = db_pool.acquire().await?;
+
+    if !AccountBatchPolicy::delete(&mut conn, &account_name).await? {
+        return Err(ApiError::NotFound("Account has no batch policy override".to_string()));
+    }
+    println!(
+        "Operator {} removed the batch policy override of account {}",
+        operator, account_name
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,425 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    api::{auth::Authenticated, error::ApiError, pagination, payments::PaymentResponse},
+    db::{
+        api_key::ApiScope,
+        event::Actor,
+        payment::Payment,
+        payment_batch::{PaymentBatch, PaymentBatchFilter, PaymentBatchStatus},
+    },
+    signer::{SignerBackend, Signers, verify_signed_tx},
+};
+
+/// Signed transactions of large batches need more than axum's default 2 MB body limit.
+pub(crate) const SIGNED_TX_BODY_LIMIT_BYTES: usize = 8 * 1024 * 1024;
+
+/// The stages a batch passes through on its way to confirmation, in order.
+const PIPELINE: [PaymentBatchStatus; 8] = [
+    PaymentBatchStatus::PendingBatching,
+    PaymentBatchStatus::AwaitingApproval,
+    PaymentBatchStatus::AwaitingSignature,
+    PaymentBatchStatus::SigningInProgress,
+    PaymentBatchStatus::AwaitingBroadcast,
+    PaymentBatchStatus::Broadcasting,
+    PaymentBatchStatus::AwaitingConfirmation,
+    PaymentBatchStatus::Confirmed,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TimelineStepState {
+    Completed,
+    Current,
+    Pending,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BatchTimelineStep {
+    pub status: PaymentBatchStatus,
+    pub state: TimelineStepState,
+    /// When the batch entered this stage, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PaymentBatchResponse {
+    pub batch_id: String,
+    pub account_name: String,
+    pub status: PaymentBatchStatus,
+    pub pr_idempotency_key: String,
+    /// Failed attempts since the batch was created or last retried by an operator, across all stages.
+    pub retry_count: i64,
+    /// When the failed stage is attempted again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_retry_at: Option<DateTime<Utc>>,
+    /// The batch this one was split from to isolate a failing payment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_batch_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mined_height: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mined_header_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mined_timestamp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_expires_at: Option<DateTime<Utc>>,
+    /// How many operator approvals the batch needs before it is signed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approvals_required: Option<i64>,
+    /// The unsigned transaction returned by PR. Only included when fetching a single batch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub unsigned_tx: Option<serde_json::Value>,
+    /// The signed transaction. Only included when fetching a single batch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub signed_tx: Option<serde_json::Value>,
+    pub timeline: Vec<BatchTimelineStep>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PaymentBatchResponse {
+    pub fn from_batch(batch: PaymentBatch, include_tx: bool) -> Self {
+        let timeline = build_timeline(&batch);
+        let (unsigned_tx, signed_tx) = if include_tx {
+            (
+                batch.unsigned_tx_json.as_deref().map(parse_tx_json),
+                batch.signed_tx_json.as_deref().map(parse_tx_json),
+            )
+        } else {
+            (None, None)
+        };
+
+        PaymentBatchResponse {
+            batch_id: batch.id,
+            account_name: batch.account_name,
+            status: batch.status,
+            pr_idempotency_key: batch.pr_idempotency_key,
+            retry_count: batch.retry_count,
+            next_retry_at: batch.next_retry_at,
+            parent_batch_id: batch.parent_batch_id,
+            error_message: batch.error_message,
+            mined_height: batch.mined_height,
+            mined_header_hash: batch.mined_header_hash,
+            mined_timestamp: batch.mined_timestamp,
+            lease_expires_at: batch.lease_expires_at,
+            approvals_required: batch.approvals_required,
+            unsigned_tx,
+            signed_tx,
+            timeline,
+            created_at: batch.created_at,
+            updated_at: batch.updated_at,
+        }
+    }
+}
+
+fn parse_tx_json(json: &str) -> serde_json::Value {
+    serde_json::from_str(json).unwrap_or_else(|_| serde_json::Value::String(json.to_string()))
+}
+
+/// Builds the batch's progress through the pipeline. A failed batch shows the stages it got
+/// through, inferred from the transactions it holds, followed by the FAILED, VERIFICATION_FAILED, REORGED,
+/// DROPPED, SPLIT or CANCELLED stage.
+/// The approval stage is only shown for batches that needed approval.
+fn build_timeline(batch: &PaymentBatch) -> Vec<BatchTimelineStep> {
+    let pipeline: Vec<&PaymentBatchStatus> = PIPELINE
+        .iter()
+        .filter(|status| **status != PaymentBatchStatus::AwaitingApproval || batch.approvals_required.is_some())
+        .collect();
+    let stage_index = |status: &PaymentBatchStatus| pipeline.iter().position(|s| *s == status);
+    let reached = stage_index(&batch.status)
+        .or_else(|| {
+            if matches!(batch.status, PaymentBatchStatus::Reorged | PaymentBatchStatus::Dropped) {
+                stage_index(&PaymentBatchStatus::AwaitingConfirmation)
+            } else if batch.signed_tx_json.is_some() {
+                stage_index(&PaymentBatchStatus::AwaitingBroadcast)
+            } else if batch.unsigned_tx_json.is_some() && batch.approvals_required.is_some() {
+                stage_index(&PaymentBatchStatus::AwaitingApproval)
+            } else if batch.unsigned_tx_json.is_some() {
+                stage_index(&PaymentBatchStatus::AwaitingSignature)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(0);
+    let failed = matches!(
+        batch.status,
+        PaymentBatchStatus::Failed
+            | PaymentBatchStatus::VerificationFailed
+            | PaymentBatchStatus::Reorged
+            | PaymentBatchStatus::Dropped
+            | PaymentBatchStatus::Split
+            | PaymentBatchStatus::Cancelled
+    );
+
+    let mut timeline: Vec<BatchTimelineStep> = pipeline
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !failed || *index <= reached)
+        .map(|(index, status)| {
+            let state = if index < reached || failed {
+                TimelineStepState::Completed
+            } else if index == reached {
+                TimelineStepState::Current
+            } else {
+                TimelineStepState::Pending
+            };
+            let entered_at = if index == 0 {
+                Some(batch.created_at)
+            } else if state == TimelineStepState::Current {
+                Some(batch.updated_at)
+            } else {
+                None
+            };
+            BatchTimelineStep {
+                status: (*status).clone(),
+                state,
+                entered_at,
+            }
+        })
+        .collect();
+
+    if failed {
+        timeline.push(BatchTimelineStep {
+            status: batch.status.clone(),
+            state: TimelineStepState::Current,
+            entered_at: Some(batch.updated_at),
+        });
+    }
+
+    timeline
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListBatchesQuery {
+    pub account_name: Option<String>,
+    pub status: Option<PaymentBatchStatus>,
+    /// Opaque cursor from a previous response's `next_cursor`.
+    pub cursor: Option<String>,
+    /// Page size, defaults to 50 and is capped at 500.
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PaymentBatchListResponse {
+    pub batches: Vec<PaymentBatchResponse>,
+    /// Pass as `cursor` to fetch the next page. Absent on the last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/batches",
+    params(ListBatchesQuery),
+    responses(
+        (status = 200, description = "Payment batches retrieved successfully", body = PaymentBatchListResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_list_batches(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Query(query): Query<ListBatchesQuery>,
+) -> Result<Json<PaymentBatchListResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    let limit = pagination::page_limit(query.limit)?;
+    let cursor = query.cursor.as_deref().map(pagination::decode_cursor).transpose()?;
+
+    let filter = PaymentBatchFilter {
+        account_name: query.account_name.as_deref(),
+        account_names: auth.account_restriction(),
+        status: query.status,
+    };
+
+    let mut conn = db_pool.acquire().await?;
+    // Fetch one extra row to find out whether another page follows.
+    let mut batches = PaymentBatch::list(&mut conn, &filter, cursor.as_ref(), limit + 1).await?;
+    let next_cursor = pagination::next_cursor(&mut batches, limit, |b| (b.created_at, b.id.as_str()));
+
+    Ok(Json(PaymentBatchListResponse {
+        batches: batches
+            .into_iter()
+            .map(|batch| PaymentBatchResponse::from_batch(batch, false))
+            .collect(),
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/batches/{batch_id}",
+    responses(
+        (status = 200, description = "Payment batch retrieved successfully", body = PaymentBatchResponse),
+        (status = 404, description = "Payment batch not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_get_batch(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(batch_id): Path<String>,
+) -> Result<Json<PaymentBatchResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut conn, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+
+    Ok(Json(PaymentBatchResponse::from_batch(batch, true)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/batches/{batch_id}/payments",
+    responses(
+        (status = 200, description = "Payments in the batch retrieved successfully", body = Vec<PaymentResponse>),
+        (status = 404, description = "Payment batch not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_get_batch_payments(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(batch_id): Path<String>,
+) -> Result<Json<Vec<PaymentResponse>>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut conn, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    let payments = Payment::find_by_batch_id(&mut conn, &batch_id).await?;
+
+    Ok(Json(
+        payments
+            .into_iter()
+            .map(|payment| PaymentResponse::from_payment_and_batch(payment, Some(batch.clone())))
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/batches/{batch_id}/unsigned-tx",
+    responses(
+        (status = 200, description = "The batch's unsigned transaction, to be signed offline", body = Object),
+        (status = 404, description = "Payment batch not found or has no unsigned transaction yet", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_get_unsigned_tx(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(batch_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut conn, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    let unsigned_tx_json = batch
+        .unsigned_tx_json
+        .ok_or_else(|| ApiError::NotFound("Payment batch has no unsigned transaction yet".to_string()))?;
+
+    // Returned byte for byte, so the offline signer sees exactly what the upload is checked against.
+    Ok(([(header::CONTENT_TYPE, "application/json")], unsigned_tx_json))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/batches/{batch_id}/signed-tx",
+    request_body(content = Object, description = "The `SignedOneSidedTransactionResult` produced offline"),
+    responses(
+        (status = 200, description = "Signed transaction accepted, the batch is awaiting broadcast", body = PaymentBatchResponse),
+        (status = 400, description = "Signed transaction does not match the batch's unsigned transaction", body = ApiError),
+        (status = 404, description = "Payment batch not found", body = ApiError),
+        (status = 409, description = "Account is not signed manually or batch is not awaiting a signature", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_submit_signed_tx(
+    State(db_pool): State<SqlitePool>,
+    State(signers): State<Arc<Signers>>,
+    auth: Authenticated,
+    Path(batch_id): Path<String>,
+    signed_tx_json: String,
+) -> Result<Json<PaymentBatchResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let batch = PaymentBatch::find_by_id(&mut conn, &batch_id)
+        .await?
+        .filter(|batch| auth.can_see(&batch.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    // Automatic signers may be working on the batch at the same time.
+    if signers.backend_for(&batch.account_name) != SignerBackend::Manual {
+        return Err(ApiError::Conflict(format!(
+            "Account {} is not signed manually",
+            batch.account_name
+        )));
+    }
+    if batch.status != PaymentBatchStatus::AwaitingSignature {
+        return Err(ApiError::Conflict(format!(
+            "Batch is {}, only AWAITING_SIGNATURE batches accept a signed transaction",
+            batch.status
+        )));
+    }
+    let unsigned_tx_json = batch
+        .unsigned_tx_json
+        .as_deref()
+        .ok_or_else(|| ApiError::InternalServerError("Batch has no unsigned transaction".to_string()))?;
+
+    verify_signed_tx(unsigned_tx_json, &signed_tx_json).map_err(ApiError::BadRequest)?;
+
+    if !PaymentBatch::submit_signed_tx(
+        &mut conn,
+        &batch_id,
+        unsigned_tx_json,
+        &signed_tx_json,
+        &Actor::Operator(auth.0.name.clone()),
+    )
+    .await?
+    {
+        return Err(ApiError::Conflict(
+            "Batch changed while the signed transaction was being checked".to_string(),
+        ));
+    }
+
+    let batch = PaymentBatch::find_by_id(&mut conn, &batch_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payment batch not found".to_string()))?;
+    Ok(Json(PaymentBatchResponse::from_batch(batch, true)))
+}
@@ -18,6 +18,15 @@ pub enum ApiError {
     NotFound(String),
     #[error("Bad request: {0}")]
     BadRequest(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    /// A conflict with the fields that differ from the stored resource.
+    #[error("Conflict: {0}")]
+    ConflictWithDiff(String, #[schema(value_type = Object)] serde_json::Value),
 }
 
 impl From<sqlx::Error> for ApiError {
@@ -28,11 +37,22 @@ impl From<sqlx::Error> for ApiError {
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        if let ApiError::ConflictWithDiff(msg, diff) = self {
+            let body = Json(json!({
+                "error": msg,
+                "diff": diff,
+            }));
+            return (StatusCode::CONFLICT, body).into_response();
+        }
+
         let (status, error_message) = match self {
             ApiError::InternalServerError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
             ApiError::DbError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            ApiError::Conflict(msg) | ApiError::ConflictWithDiff(msg, _) => (StatusCode::CONFLICT, msg),
         };
 
         let body = Json(json!({
@@ -1,19 +1,47 @@
 use axum::{
     Router,
-    extract::FromRef,
-    routing::{get, post},
+    extract::{DefaultBodyLimit, FromRef},
+    middleware,
+    routing::{delete, get, post},
 };
 use sqlx::SqlitePool;
-use utoipa::OpenApi;
+use std::sync::Arc;
+use tari_transaction_components::tari_common::configuration::Network;
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
 use utoipa_swagger_ui::SwaggerUi;
 
+use crate::{
+    db::{approval::ApprovalPolicy, batch_policy::BatchPolicy},
+    signer::Signers,
+};
+
+mod accounts;
+mod admin;
+mod approval_policies;
+mod auth;
+mod batch_policies;
+mod batches;
 mod error;
+mod pagination;
 mod payments;
+mod payout_schedules;
 mod version;
+mod webhooks;
+
+pub use payments::normalize_stored_addresses;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: SqlitePool,
+    /// The Tari network recipient addresses must belong to.
+    pub network: Network,
+    /// The batching policy of accounts without an override.
+    pub default_batch_policy: BatchPolicy,
+    /// Which backend signs each account's transactions.
+    pub signers: Arc<Signers>,
 }
 
 impl FromRef<AppState> for SqlitePool {
@@ -22,33 +50,217 @@ impl FromRef<AppState> for SqlitePool {
     }
 }
 
+impl FromRef<AppState> for Network {
+    fn from_ref(state: &AppState) -> Self {
+        state.network
+    }
+}
+
+impl FromRef<AppState> for BatchPolicy {
+    fn from_ref(state: &AppState) -> Self {
+        state.default_batch_policy.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Signers> {
+    fn from_ref(state: &AppState) -> Self {
+        state.signers.clone()
+    }
+}
+
 #[derive(OpenApi)]
 #[openapi(
     paths(
         version::api_get_version,
         payments::api_create_payment,
+        payments::api_create_payments_bulk,
         payments::api_get_payment,
+        payments::api_update_scheduled_payment,
+        payments::api_list_payments,
+        payments::api_get_payment_events,
+        payments::api_cancel_payment,
+        accounts::api_list_account_alerts,
+        payout_schedules::api_create_payout_schedule,
+        payout_schedules::api_list_payout_schedules,
+        payout_schedules::api_get_payout_schedule,
+        payout_schedules::api_update_payout_schedule,
+        payout_schedules::api_delete_payout_schedule,
+        payout_schedules::api_list_payout_runs,
+        batches::api_list_batches,
+        batches::api_get_batch,
+        batches::api_get_batch_payments,
+        batches::api_get_unsigned_tx,
+        batches::api_submit_signed_tx,
+        admin::api_retry_batch,
+        admin::api_release_batch_payments,
+        admin::api_force_fail_batch,
+        admin::api_list_batch_approvals,
+        admin::api_approve_batch,
+        admin::api_reject_batch,
+        approval_policies::api_list_approval_policies,
+        approval_policies::api_get_approval_policy,
+        approval_policies::api_set_approval_policy,
+        approval_policies::api_delete_approval_policy,
+        batch_policies::api_list_batch_policies,
+        batch_policies::api_get_batch_policy,
+        batch_policies::api_set_batch_policy,
+        batch_policies::api_delete_batch_policy,
+        webhooks::api_create_webhook,
+        webhooks::api_list_webhooks,
+        webhooks::api_delete_webhook,
     ),
     components(
         schemas(
             version::ServiceVersion,
             payments::PaymentRequest,
             payments::PaymentResponse,
+            payments::CancelPaymentResponse,
+            payments::ScheduledPaymentUpdateRequest,
+            payments::BulkPaymentRequest,
+            payments::BulkPaymentOutcome,
+            payments::BulkPaymentResult,
+            payments::BulkPaymentResponse,
+            payments::PaymentListResponse,
+            payments::PaymentEventResponse,
+            accounts::AccountAlertResponse,
+            payout_schedules::PayoutScheduleRequest,
+            payout_schedules::PayoutScheduleUpdateRequest,
+            payout_schedules::PayoutScheduleResponse,
+            payout_schedules::PayoutRunResponse,
+            batches::PaymentBatchResponse,
+            batches::PaymentBatchListResponse,
+            batches::BatchTimelineStep,
+            batches::TimelineStepState,
+            admin::RetryBatchRequest,
+            admin::BatchActionRequest,
+            admin::BatchApprovalResponse,
+            admin::BatchApprovalListResponse,
+            approval_policies::AccountApprovalPolicyResponse,
+            ApprovalPolicy,
+            batch_policies::AccountBatchPolicyResponse,
+            batch_policies::BatchPolicyListResponse,
+            BatchPolicy,
+            webhooks::WebhookSubscriptionRequest,
+            webhooks::WebhookSubscriptionResponse,
         )
     ),
+    modifiers(&ApiKeySecurity),
+    security(("api_key" = [])),
     tags(
         (name = "minotari-payment-processor", description = "Minotari Payment Processor API"),
     )
 )]
 pub struct ApiDoc;
 
-pub fn create_router(db_pool: SqlitePool) -> Router {
-    let app_state = AppState { db_pool };
+struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+pub fn create_router(
+    db_pool: SqlitePool,
+    network: Network,
+    default_batch_policy: BatchPolicy,
+    signers: Arc<Signers>,
+) -> Router {
+    let app_state = AppState {
+        db_pool,
+        network,
+        default_batch_policy,
+        signers,
+    };
+
+    // Every /v1 endpoint requires an API key.
+    let v1 = Router::new()
+        .route(
+            "/v1/payments",
+            post(payments::api_create_payment).get(payments::api_list_payments),
+        )
+        .route(
+            "/v1/payments/bulk",
+            post(payments::api_create_payments_bulk).layer(DefaultBodyLimit::max(payments::BULK_BODY_LIMIT_BYTES)),
+        )
+        .route(
+            "/v1/payments/{payment_id}",
+            get(payments::api_get_payment).patch(payments::api_update_scheduled_payment),
+        )
+        .route(
+            "/v1/payments/{payment_id}/events",
+            get(payments::api_get_payment_events),
+        )
+        .route("/v1/payments/{payment_id}/cancel", post(payments::api_cancel_payment))
+        .route(
+            "/v1/accounts/{account_name}/alerts",
+            get(accounts::api_list_account_alerts),
+        )
+        .route(
+            "/v1/payout-schedules",
+            post(payout_schedules::api_create_payout_schedule).get(payout_schedules::api_list_payout_schedules),
+        )
+        .route(
+            "/v1/payout-schedules/{schedule_id}",
+            get(payout_schedules::api_get_payout_schedule)
+                .patch(payout_schedules::api_update_payout_schedule)
+                .delete(payout_schedules::api_delete_payout_schedule),
+        )
+        .route(
+            "/v1/payout-schedules/{schedule_id}/runs",
+            get(payout_schedules::api_list_payout_runs),
+        )
+        .route("/v1/batches", get(batches::api_list_batches))
+        .route("/v1/batches/{batch_id}", get(batches::api_get_batch))
+        .route("/v1/batches/{batch_id}/payments", get(batches::api_get_batch_payments))
+        .route("/v1/batches/{batch_id}/unsigned-tx", get(batches::api_get_unsigned_tx))
+        .route(
+            "/v1/batches/{batch_id}/signed-tx",
+            post(batches::api_submit_signed_tx).layer(DefaultBodyLimit::max(batches::SIGNED_TX_BODY_LIMIT_BYTES)),
+        )
+        .route("/v1/admin/batches/{batch_id}/retry", post(admin::api_retry_batch))
+        .route(
+            "/v1/admin/batches/{batch_id}/release",
+            post(admin::api_release_batch_payments),
+        )
+        .route("/v1/admin/batches/{batch_id}/fail", post(admin::api_force_fail_batch))
+        .route(
+            "/v1/admin/batches/{batch_id}/approvals",
+            get(admin::api_list_batch_approvals),
+        )
+        .route("/v1/admin/batches/{batch_id}/approve", post(admin::api_approve_batch))
+        .route("/v1/admin/batches/{batch_id}/reject", post(admin::api_reject_batch))
+        .route(
+            "/v1/admin/approval-policies",
+            get(approval_policies::api_list_approval_policies),
+        )
+        .route(
+            "/v1/admin/approval-policies/{account_name}",
+            get(approval_policies::api_get_approval_policy)
+                .put(approval_policies::api_set_approval_policy)
+                .delete(approval_policies::api_delete_approval_policy),
+        )
+        .route("/v1/admin/batch-policies", get(batch_policies::api_list_batch_policies))
+        .route(
+            "/v1/admin/batch-policies/{account_name}",
+            get(batch_policies::api_get_batch_policy)
+                .put(batch_policies::api_set_batch_policy)
+                .delete(batch_policies::api_delete_batch_policy),
+        )
+        .route(
+            "/v1/webhooks",
+            post(webhooks::api_create_webhook).get(webhooks::api_list_webhooks),
+        )
+        .route("/v1/webhooks/{subscription_id}", delete(webhooks::api_delete_webhook))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth::authenticate));
 
     Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/openapi.json", ApiDoc::openapi()))
         .route("/health/version", get(version::api_get_version))
-        .route("/v1/payments", post(payments::api_create_payment))
-        .route("/v1/payments/{payment_id}", get(payments::api_get_payment))
+        .merge(v1)
         .with_state(app_state)
 }
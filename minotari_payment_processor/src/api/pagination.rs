@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+
+use crate::{api::error::ApiError, db::PageCursor};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Validates the requested page size, falling back to the default.
+pub fn page_limit(limit: Option<i64>) -> Result<i64, ApiError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(ApiError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    Ok(limit)
+}
+
+pub fn encode_cursor(created_at: DateTime<Utc>, id: &str) -> String {
+    hex::encode(format!("{}|{}", created_at.timestamp(), id))
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<PageCursor, ApiError> {
+    let invalid = || ApiError::BadRequest("Invalid cursor".to_string());
+    let decoded = String::from_utf8(hex::decode(cursor).map_err(|_| invalid())?).map_err(|_| invalid())?;
+    let (timestamp, id) = decoded.split_once('|').ok_or_else(invalid)?;
+    let created_at = timestamp
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .ok_or_else(invalid)?;
+
+    Ok(PageCursor {
+        created_at,
+        id: id.to_string(),
+    })
+}
+
+/// Trims a page fetched with `limit + 1` rows back to `limit` and returns the cursor for the
+/// next page, or `None` if this is the last one.
+pub fn next_cursor<T>(rows: &mut Vec<T>, limit: i64, key: impl Fn(&T) -> (DateTime<Utc>, &str)) -> Option<String> {
+    if rows.len() as i64 <= limit {
+        return None;
+    }
+    rows.truncate(limit as usize);
+    rows.last().map(|row| {
+        let (created_at, id) = key(row);
+        encode_cursor(created_at, id)
+    })
+}
@@ -1,27 +1,146 @@
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SubsecRound, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::SqlitePool;
-use utoipa::ToSchema;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::{collections::HashSet, str::FromStr};
+use tari_transaction_components::tari_common::configuration::Network;
+use tari_transaction_components::tari_common_types::tari_address::TariAddress;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    api::error::ApiError,
+    api::{auth::Authenticated, error::ApiError, pagination, webhooks},
     db::{
-        payment::{Payment, PaymentStatus},
-        payment_batch::PaymentBatch,
+        api_key::ApiScope,
+        event::{Actor, PaymentEvent},
+        payment::{FailureCode, HoldReason, NewPayment, Payment, PaymentFilter, PaymentStatus, ScheduledPaymentUpdate},
+        payment_batch::{PaymentBatch, PaymentBatchStatus},
     },
 };
+
 #[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct PaymentRequest {
     pub client_id: String,
     pub account_name: String,
+    /// A Tari address on the configured network, in emoji, base58 or hex form.
+    /// Stored and returned in base58 form.
     pub recipient_address: String,
     pub amount: i64,
+    /// A reference the recipient sees on-chain, sent as the transaction's payment ID.
+    /// At most 250 bytes of printable UTF-8. Also accepted as `memo`.
+    #[serde(alias = "memo")]
+    pub payment_id: Option<String>,
+    /// Receives a signed POST when the payment reaches CONFIRMED, FAILED or CANCELLED, in addition to the
+    /// account's webhook subscriptions.
+    pub callback_url: Option<String>,
+    /// When in the future, the payment is SCHEDULED and not batched before this time.
+    /// Stored with second precision.
+    pub execute_after: Option<DateTime<Utc>>,
+}
+
+/// Changes to a SCHEDULED payment. Omitted fields are left unchanged.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ScheduledPaymentUpdateRequest {
+    pub recipient_address: Option<String>,
+    pub amount: Option<i64>,
+    #[serde(alias = "memo")]
+    pub payment_id: Option<String>,
+    pub callback_url: Option<String>,
+    /// Must be in the future.
+    pub execute_after: Option<DateTime<Utc>>,
+}
+
+// The payment ID travels in the output's encrypted data, whose payment ID part Tari caps at 256 bytes
+// (`MAX_ENCRYPTED_DATA_SIZE` less the fixed-size fields, see `transaction_components/encrypted_data.rs`).
+// The wallet's serialization of the payment ID adds a type tag and a transaction type to the user data,
+// so a few bytes are kept back for that framing.
+const MAX_PAYMENT_ID_BYTES: usize = 250;
+
+/// The most payments accepted in one bulk request.
+const MAX_BULK_PAYMENTS: usize = 5_000;
+/// Bulk requests need more than axum's default 2 MB body limit.
+pub(crate) const BULK_BODY_LIMIT_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct BulkPaymentRequest {
+    /// Up to 5000 payments, each validated and deduplicated by `client_id` on its own.
+    pub payments: Vec<PaymentRequest>,
+    /// When set, no payment is created unless every item is accepted or is an exact replay.
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BulkPaymentOutcome {
+    /// The payment was created.
+    Accepted,
+    /// An identical request with this `client_id` already created the payment.
+    Duplicate,
+    /// The item was invalid or conflicts with an earlier request with this `client_id`.
+    Rejected,
+    /// The item was valid, but was rolled back because another item of an atomic request was
+    /// rejected.
+    NotApplied,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BulkPaymentResult {
+    /// The item's position in the request.
+    pub index: usize,
+    pub client_id: String,
+    pub outcome: BulkPaymentOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment: Option<PaymentResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// For conflicts, the fields that differ from the existing payment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub diff: Option<serde_json::Value>,
+}
+
+impl BulkPaymentResult {
+    fn with_payment(index: usize, outcome: BulkPaymentOutcome, payment: Payment) -> Self {
+        BulkPaymentResult {
+            index,
+            client_id: payment.client_id.clone(),
+            outcome,
+            payment: Some(PaymentResponse::from(payment)),
+            error: None,
+            diff: None,
+        }
+    }
+
+    fn rejected(index: usize, client_id: &str, error: ApiError) -> Self {
+        let message = error.to_string();
+        let diff = match error {
+            ApiError::ConflictWithDiff(_, diff) => Some(diff),
+            _ => None,
+        };
+        BulkPaymentResult {
+            index,
+            client_id: client_id.to_string(),
+            outcome: BulkPaymentOutcome::Rejected,
+            payment: None,
+            error: Some(message),
+            diff,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BulkPaymentResponse {
+    pub accepted: usize,
+    pub duplicates: usize,
+    pub rejected: usize,
+    pub results: Vec<BulkPaymentResult>,
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -32,8 +151,23 @@ pub struct PaymentResponse {
     pub account_name: String,
     pub recipient_address: String,
     pub amount: i64,
+    /// The payment ID sent on-chain, as given in the request's `payment_id`. Named apart from it
+    /// because `payment_id` here identifies the payment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub failure_reason: Option<String>,
+    /// Machine-readable cause of a FAILED payment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_code: Option<FailureCode>,
+    /// Why a RECEIVED payment is not being batched yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hold_reason: Option<HoldReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// The earliest time the payment is batched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execute_after: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mined_height: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -59,7 +193,12 @@ impl PaymentResponse {
             account_name: payment.account_name,
             recipient_address: payment.recipient_address,
             amount: payment.amount,
+            memo: payment.payment_id,
             failure_reason: payment.failure_reason,
+            failure_code: payment.failure_code.map(FailureCode::from),
+            hold_reason: payment.hold_reason.map(HoldReason::from),
+            callback_url: payment.callback_url,
+            execute_after: payment.execute_after,
             mined_height,
             mined_header_hash,
             mined_timestamp,
@@ -75,6 +214,269 @@ impl From<Payment> for PaymentResponse {
     }
 }
 
+/// A single status change in a payment's history.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PaymentEventResponse {
+    /// Absent for the event that created the payment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_status: Option<PaymentStatus>,
+    pub to_status: PaymentStatus,
+    /// The worker, `api`, or `operator:<name>` that made the change.
+    pub actor: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<PaymentEvent> for PaymentEventResponse {
+    fn from(event: PaymentEvent) -> Self {
+        PaymentEventResponse {
+            from_status: event.from_status,
+            to_status: event.to_status,
+            actor: event.actor,
+            detail: event.detail,
+            created_at: event.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPaymentsQuery {
+    pub account_name: Option<String>,
+    pub status: Option<PaymentStatus>,
+    /// A Tari address in emoji, base58 or hex form.
+    pub recipient_address: Option<String>,
+    /// Only return payments whose client_id starts with this prefix.
+    pub client_id_prefix: Option<String>,
+    /// Inclusive lower bound on created_at.
+    pub created_after: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on created_at.
+    pub created_before: Option<DateTime<Utc>>,
+    /// Opaque cursor from a previous response's `next_cursor`.
+    pub cursor: Option<String>,
+    /// Page size, defaults to 50 and is capped at 500.
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CancelPaymentResponse {
+    #[serde(flatten)]
+    pub payment: PaymentResponse,
+    /// Set when the payment was taken out of a batch whose unsigned transaction PR had already built.
+    /// PR cannot release the UTXOs it reserved for that transaction early and keeps them until this time.
+    /// The batch's other payments are rebuilt into a new transaction at once, but if the account cannot
+    /// fund it without the reserved UTXOs, the batch waits until this time before it is built.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_utxos_locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PaymentListResponse {
+    pub payments: Vec<PaymentResponse>,
+    /// Pass as `cursor` to fetch the next page. Absent on the last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Parses a Tari address in any of its encodings and returns its canonical base58 form,
+/// rejecting addresses that belong to a different network.
+pub(crate) fn canonical_address(address: &str, network: Network) -> Result<String, ApiError> {
+    let address = TariAddress::from_str(address.trim())
+        .map_err(|e| ApiError::BadRequest(format!("Invalid recipient address: {}", e)))?;
+    if address.network() != network {
+        return Err(ApiError::BadRequest(format!(
+            "Recipient address is for the {} network, expected {}",
+            address.network(),
+            network
+        )));
+    }
+    Ok(address.to_base58())
+}
+
+/// Rewrites the recipient addresses of open payments stored before addresses were validated, e.g. in emoji or
+/// hex form, into the canonical form [`canonical_address`] gives new payments, so they compare equal to the ones
+/// in requests and transactions. Each change is recorded in the payment's history. Finished payments keep the
+/// address they were made to, so once every open payment is rewritten this finds nothing to do. Addresses that
+/// are invalid or of another network are left as they are and reported.
+pub async fn normalize_stored_addresses(db_pool: &SqlitePool, network: Network) -> Result<(), anyhow::Error> {
+    let mut tx = db_pool.begin().await?;
+    let mut normalized = 0;
+    for address in Payment::find_distinct_open_recipient_addresses(&mut tx).await? {
+        match canonical_address(&address, network) {
+            Ok(canonical) if canonical != address => {
+                normalized +=
+                    Payment::replace_recipient_address(&mut tx, &address, &canonical, &Actor::Startup).await?;
+            },
+            Ok(_) => {},
+            Err(e) => eprintln!("Stored recipient address {} left as is: {}", address, e),
+        }
+    }
+    tx.commit().await?;
+
+    if normalized > 0 {
+        println!("Normalized the recipient address of {} payments.", normalized);
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_payment_id(payment_id: &str) -> Result<(), ApiError> {
+    if payment_id.is_empty() {
+        return Err(ApiError::BadRequest("payment_id must not be empty".to_string()));
+    }
+    if payment_id.len() > MAX_PAYMENT_ID_BYTES {
+        return Err(ApiError::BadRequest(format!(
+            "payment_id must be at most {} bytes, got {}",
+            MAX_PAYMENT_ID_BYTES,
+            payment_id.len()
+        )));
+    }
+    if payment_id.chars().any(char::is_control) {
+        return Err(ApiError::BadRequest(
+            "payment_id must not contain control characters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The fields of a payment request that identify it. Reusing a `client_id` with any of them
+/// changed is rejected rather than treated as a retry.
+#[derive(Serialize)]
+struct RequestFingerprint<'a> {
+    account_name: &'a str,
+    client_id: &'a str,
+    recipient_address: &'a str,
+    amount: i64,
+    // The request's `payment_id`, under its original name so earlier requests keep their digest.
+    memo: Option<&'a str>,
+    callback_url: Option<&'a str>,
+    // Left out when unset so requests made before scheduling existed keep their digest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    execute_after: Option<DateTime<Utc>>,
+}
+
+impl RequestFingerprint<'_> {
+    fn digest(&self) -> String {
+        hex::encode(Sha256::digest(serde_json::to_vec(self).unwrap()))
+    }
+
+    /// The fields that differ from the payment created by an earlier request with the same
+    /// `client_id`, each as `{"existing": ..., "requested": ...}`.
+    fn diff(&self, existing: &Payment) -> serde_json::Map<String, serde_json::Value> {
+        let mut diff = serde_json::Map::new();
+        let mut compare = |field: &str, existing: serde_json::Value, requested: serde_json::Value| {
+            if existing != requested {
+                diff.insert(
+                    field.to_string(),
+                    json!({ "existing": existing, "requested": requested }),
+                );
+            }
+        };
+        compare(
+            "recipient_address",
+            json!(existing.recipient_address),
+            json!(self.recipient_address),
+        );
+        compare("amount", json!(existing.amount), json!(self.amount));
+        compare("payment_id", json!(existing.payment_id), json!(self.memo));
+        compare("callback_url", json!(existing.callback_url), json!(self.callback_url));
+        compare(
+            "execute_after",
+            json!(existing.execute_after),
+            json!(self.execute_after),
+        );
+        diff
+    }
+}
+
+/// Answers a request whose `client_id` was already used: an exact replay gets the existing
+/// payment back, anything else is a conflict.
+fn replayed_payment(existing: Payment, fingerprint: &RequestFingerprint, digest: &str) -> Result<Payment, ApiError> {
+    // Payments created before fingerprints were stored are compared field by field.
+    if existing.request_fingerprint.as_deref() != Some(digest) {
+        let diff = fingerprint.diff(&existing);
+        if !diff.is_empty() {
+            return Err(ApiError::ConflictWithDiff(
+                format!(
+                    "client_id {} was already used for a payment with different parameters",
+                    existing.client_id
+                ),
+                diff.into(),
+            ));
+        }
+    }
+    Ok(existing)
+}
+
+/// Checks a payment request and returns its recipient address in canonical form.
+fn validate_payment_request(request: &PaymentRequest, network: Network) -> Result<String, ApiError> {
+    if request.amount <= 0 {
+        return Err(ApiError::BadRequest("Amount must be positive".to_string()));
+    }
+    let recipient_address = canonical_address(&request.recipient_address, network)?;
+    if let Some(payment_id) = &request.payment_id {
+        validate_payment_id(payment_id)?;
+    }
+    if let Some(callback_url) = &request.callback_url {
+        webhooks::validate_webhook_url(callback_url)?;
+    }
+    Ok(recipient_address)
+}
+
+enum Submission {
+    Created(Payment),
+    /// The request repeated an earlier one with the same `client_id`.
+    Replayed(Payment),
+}
+
+/// Creates the payment described by a validated request, unless an earlier request with the
+/// same `client_id` already did. `conn` must hold the write lock, see [`begin_write`], so no
+/// concurrent request can insert the same `client_id` between the check and the insert.
+async fn submit_payment(
+    conn: &mut SqliteConnection,
+    request: &PaymentRequest,
+    recipient_address: &str,
+) -> Result<Submission, ApiError> {
+    // Truncated to the precision it is stored with, so a replay compares equal.
+    let execute_after = request.execute_after.map(|at| at.trunc_subsecs(0));
+    let fingerprint = RequestFingerprint {
+        account_name: &request.account_name,
+        client_id: &request.client_id,
+        recipient_address,
+        amount: request.amount,
+        memo: request.payment_id.as_deref(),
+        callback_url: request.callback_url.as_deref(),
+        execute_after,
+    };
+    let digest = fingerprint.digest();
+
+    // Idempotency check
+    if let Some(existing_payment) = Payment::get_by_client_id(conn, &request.client_id, &request.account_name).await? {
+        return replayed_payment(existing_payment, &fingerprint, &digest).map(Submission::Replayed);
+    }
+
+    let new_payment = NewPayment {
+        client_id: &request.client_id,
+        account_name: &request.account_name,
+        recipient_address,
+        amount: request.amount,
+        payment_id: request.payment_id.as_deref(),
+        callback_url: request.callback_url.as_deref(),
+        request_fingerprint: Some(&digest),
+        execute_after,
+    };
+    let payment = Payment::create(conn, &new_payment, &Actor::Api).await?;
+    Ok(Submission::Created(payment))
+}
+
+/// Begins a transaction that takes SQLite's write lock at once. A deferred transaction that reads
+/// first would fail with `SQLITE_BUSY` on its first write once a concurrent request has written, and
+/// could not see that request's payment anyway. Taking the lock up front makes a concurrent request
+/// with the same `client_id` wait, and then find the payment and get the idempotent answer.
+async fn begin_write(db_pool: &SqlitePool) -> Result<sqlx::Transaction<'static, sqlx::Sqlite>, sqlx::Error> {
+    db_pool.begin_with("BEGIN IMMEDIATE").await
+}
+
 #[utoipa::path(
     post,
     path = "/v1/payments",
@@ -83,41 +485,117 @@ impl From<Payment> for PaymentResponse {
         (status = 202, description = "Payment request accepted for processing", body = PaymentResponse),
         (status = 200, description = "Payment request already exists (idempotent)", body = PaymentResponse),
         (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 409, description = "client_id was already used with different parameters", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     )
 )]
 pub async fn api_create_payment(
     State(db_pool): State<SqlitePool>,
+    State(network): State<Network>,
+    auth: Authenticated,
     Json(request): Json<PaymentRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let mut transaction = db_pool.begin().await?;
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    auth.require_account(&request.account_name)?;
+    let recipient_address = validate_payment_request(&request, network)?;
 
-    // Idempotency check
-    if let Some(existing_payment) =
-        Payment::get_by_client_id(&mut transaction, &request.client_id, &request.account_name).await?
-    {
-        transaction.commit().await?;
-        return Ok((StatusCode::OK, Json(PaymentResponse::from(existing_payment))));
-    }
+    let mut transaction = begin_write(&db_pool).await?;
+    let submission = submit_payment(&mut transaction, &request, &recipient_address).await?;
+    transaction.commit().await?;
 
-    // Validate amount
-    if request.amount <= 0 {
-        return Err(ApiError::BadRequest("Amount must be positive".to_string()));
-    }
+    Ok(match submission {
+        Submission::Created(payment) => (StatusCode::ACCEPTED, Json(PaymentResponse::from(payment))),
+        Submission::Replayed(payment) => (StatusCode::OK, Json(PaymentResponse::from(payment))),
+    })
+}
 
-    let new_payment = Payment::create(
-        &mut transaction,
-        &request.client_id,
-        &request.account_name,
-        &request.recipient_address,
-        request.amount,
-        None, // payment_id is generated internally
+#[utoipa::path(
+    post,
+    path = "/v1/payments/bulk",
+    request_body = BulkPaymentRequest,
+    responses(
+        (status = 200, description = "Each payment's outcome, in request order", body = BulkPaymentResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope", body = ApiError),
+        (status = 413, description = "Request body too large"),
+        (status = 500, description = "Internal server error", body = ApiError)
     )
-    .await?;
+)]
+pub async fn api_create_payments_bulk(
+    State(db_pool): State<SqlitePool>,
+    State(network): State<Network>,
+    auth: Authenticated,
+    Json(request): Json<BulkPaymentRequest>,
+) -> Result<Json<BulkPaymentResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    if request.payments.is_empty() || request.payments.len() > MAX_BULK_PAYMENTS {
+        return Err(ApiError::BadRequest(format!(
+            "Expected between 1 and {} payments, got {}",
+            MAX_BULK_PAYMENTS,
+            request.payments.len()
+        )));
+    }
 
-    transaction.commit().await?;
+    // All items share one transaction, so the write lock is taken once for the whole request.
+    let mut transaction = begin_write(&db_pool).await?;
+    let mut created_ids = HashSet::new();
+    let mut results = Vec::with_capacity(request.payments.len());
+
+    for (index, item) in request.payments.iter().enumerate() {
+        let submission = match auth
+            .require_account(&item.account_name)
+            .and_then(|()| validate_payment_request(item, network))
+        {
+            Ok(recipient_address) => submit_payment(&mut transaction, item, &recipient_address).await,
+            Err(e) => Err(e),
+        };
 
-    Ok((StatusCode::ACCEPTED, Json(PaymentResponse::from(new_payment))))
+        let result = match submission {
+            Ok(Submission::Created(payment)) => {
+                created_ids.insert(payment.id.clone());
+                BulkPaymentResult::with_payment(index, BulkPaymentOutcome::Accepted, payment)
+            },
+            Ok(Submission::Replayed(payment)) => {
+                BulkPaymentResult::with_payment(index, BulkPaymentOutcome::Duplicate, payment)
+            },
+            // The transaction can't be trusted after a database error, so give up on the request.
+            Err(e @ ApiError::DbError(_)) => return Err(e),
+            Err(e) => BulkPaymentResult::rejected(index, &item.client_id, e),
+        };
+        results.push(result);
+    }
+
+    let rejected = results
+        .iter()
+        .filter(|r| r.outcome == BulkPaymentOutcome::Rejected)
+        .count();
+    if request.atomic && rejected > 0 {
+        // Dropping the transaction rolls back every payment created by this request.
+        drop(transaction);
+        for result in &mut results {
+            if result
+                .payment
+                .as_ref()
+                .is_some_and(|payment| created_ids.contains(&payment.payment_id))
+            {
+                result.outcome = BulkPaymentOutcome::NotApplied;
+                result.payment = None;
+            }
+        }
+    } else {
+        transaction.commit().await?;
+    }
+
+    let count = |outcome: BulkPaymentOutcome| results.iter().filter(|r| r.outcome == outcome).count();
+    Ok(Json(BulkPaymentResponse {
+        accepted: count(BulkPaymentOutcome::Accepted),
+        duplicates: count(BulkPaymentOutcome::Duplicate),
+        rejected,
+        results,
+    }))
 }
 
 #[utoipa::path(
@@ -126,18 +604,521 @@ pub async fn api_create_payment(
     responses(
         (status = 200, description = "Payment status retrieved successfully", body = PaymentResponse),
         (status = 404, description = "Payment not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     )
 )]
 pub async fn api_get_payment(
     State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
     Path(payment_id): Path<String>,
 ) -> Result<Json<PaymentResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
     let mut conn = db_pool.acquire().await?;
 
     let (payment, payment_batch) = Payment::get_by_id_with_batch_info(&mut conn, &payment_id)
         .await?
+        .filter(|(payment, _)| auth.can_see(&payment.account_name))
         .ok_or_else(|| ApiError::NotFound("Payment not found".to_string()))?;
 
     Ok(Json(PaymentResponse::from_payment_and_batch(payment, payment_batch)))
 }
+
+#[utoipa::path(
+    patch,
+    path = "/v1/payments/{payment_id}",
+    request_body = ScheduledPaymentUpdateRequest,
+    responses(
+        (status = 200, description = "Scheduled payment updated", body = PaymentResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 404, description = "Payment not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 409, description = "Payment is not scheduled or is already due", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_update_scheduled_payment(
+    State(db_pool): State<SqlitePool>,
+    State(network): State<Network>,
+    auth: Authenticated,
+    Path(payment_id): Path<String>,
+    Json(request): Json<ScheduledPaymentUpdateRequest>,
+) -> Result<Json<PaymentResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    if request.amount.is_some_and(|amount| amount <= 0) {
+        return Err(ApiError::BadRequest("Amount must be positive".to_string()));
+    }
+    let recipient_address = request
+        .recipient_address
+        .as_deref()
+        .map(|address| canonical_address(address, network))
+        .transpose()?;
+    if let Some(payment_id) = &request.payment_id {
+        validate_payment_id(payment_id)?;
+    }
+    if let Some(callback_url) = &request.callback_url {
+        webhooks::validate_webhook_url(callback_url)?;
+    }
+    let execute_after = request.execute_after.map(|at| at.trunc_subsecs(0));
+    if execute_after.is_some_and(|at| at <= Utc::now()) {
+        return Err(ApiError::BadRequest("execute_after must be in the future".to_string()));
+    }
+
+    let fields: Vec<&str> = [
+        ("recipient_address", recipient_address.is_some()),
+        ("amount", request.amount.is_some()),
+        ("payment_id", request.payment_id.is_some()),
+        ("callback_url", request.callback_url.is_some()),
+        ("execute_after", execute_after.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(field, is_set)| is_set.then_some(field))
+    .collect();
+    if fields.is_empty() {
+        return Err(ApiError::BadRequest("No fields to update".to_string()));
+    }
+
+    let mut transaction = db_pool.begin().await?;
+    let payment = Payment::get_by_id(&mut transaction, &payment_id)
+        .await?
+        .filter(|payment| auth.can_see(&payment.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment not found".to_string()))?;
+    if payment.status != PaymentStatus::Scheduled {
+        return Err(ApiError::Conflict(format!(
+            "A {} payment cannot be edited, only SCHEDULED payments can",
+            payment.status
+        )));
+    }
+
+    let update = ScheduledPaymentUpdate {
+        recipient_address: recipient_address.as_deref(),
+        amount: request.amount,
+        payment_id: request.payment_id.as_deref(),
+        callback_url: request.callback_url.as_deref(),
+        execute_after,
+    };
+    let detail = format!("Edited {} with API key {}", fields.join(", "), auth.0.name);
+    if !Payment::update_scheduled(&mut transaction, &payment_id, &update, &Actor::Api, Some(&detail)).await? {
+        return Err(ApiError::Conflict(
+            "Payment is already due and can no longer be edited".to_string(),
+        ));
+    }
+
+    let payment = Payment::get_by_id(&mut transaction, &payment_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payment not found".to_string()))?;
+    transaction.commit().await?;
+
+    Ok(Json(PaymentResponse::from(payment)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/payments",
+    params(ListPaymentsQuery),
+    responses(
+        (status = 200, description = "Payments retrieved successfully", body = PaymentListResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_list_payments(
+    State(db_pool): State<SqlitePool>,
+    State(network): State<Network>,
+    auth: Authenticated,
+    Query(query): Query<ListPaymentsQuery>,
+) -> Result<Json<PaymentListResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    let limit = pagination::page_limit(query.limit)?;
+    let cursor = query.cursor.as_deref().map(pagination::decode_cursor).transpose()?;
+    // Addresses are stored in canonical form, so any encoding of the address finds the payments.
+    let recipient_address = query
+        .recipient_address
+        .as_deref()
+        .map(|address| canonical_address(address, network))
+        .transpose()?;
+
+    let filter = PaymentFilter {
+        account_name: query.account_name.as_deref(),
+        account_names: auth.account_restriction(),
+        status: query.status,
+        recipient_address: recipient_address.as_deref(),
+        client_id_prefix: query.client_id_prefix.as_deref(),
+        created_after: query.created_after,
+        created_before: query.created_before,
+    };
+
+    let mut conn = db_pool.acquire().await?;
+    // Fetch one extra row to find out whether another page follows.
+    let mut payments = Payment::list(&mut conn, &filter, cursor.as_ref(), limit + 1).await?;
+    let next_cursor = pagination::next_cursor(&mut payments, limit, |p| (p.created_at, p.id.as_str()));
+
+    Ok(Json(PaymentListResponse {
+        payments: payments.into_iter().map(PaymentResponse::from).collect(),
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/payments/{payment_id}/events",
+    responses(
+        (status = 200, description = "Payment history retrieved successfully, oldest first", body = Vec<PaymentEventResponse>),
+        (status = 404, description = "Payment not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_get_payment_events(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(payment_id): Path<String>,
+) -> Result<Json<Vec<PaymentEventResponse>>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let payment = Payment::get_by_id(&mut conn, &payment_id).await?;
+    if !payment.is_some_and(|payment| auth.can_see(&payment.account_name)) {
+        return Err(ApiError::NotFound("Payment not found".to_string()));
+    }
+    let events = PaymentEvent::find_by_payment_id(&mut conn, &payment_id).await?;
+
+    Ok(Json(events.into_iter().map(PaymentEventResponse::from).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/payments/{payment_id}/cancel",
+    responses(
+        (status = 200, description = "Payment cancelled, or already cancelled", body = CancelPaymentResponse),
+        (status = 404, description = "Payment not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 409, description = "Payment can no longer be cancelled, or PR is building its batch's transaction right now and it can be cancelled shortly", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_cancel_payment(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(payment_id): Path<String>,
+) -> Result<Json<CancelPaymentResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    let mut transaction = db_pool.begin().await?;
+
+    let (payment, payment_batch) = Payment::get_by_id_with_batch_info(&mut transaction, &payment_id)
+        .await?
+        .filter(|(payment, _)| auth.can_see(&payment.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payment not found".to_string()))?;
+    let detail = format!("Cancelled with API key {}", auth.0.name);
+    let batch_id = payment.payment_batch_id.clone();
+
+    let cancelled = match (payment.status.clone(), payment_batch) {
+        (PaymentStatus::Cancelled, _) => {
+            transaction.commit().await?;
+            return Ok(Json(CancelPaymentResponse {
+                payment: PaymentResponse::from(payment),
+                batch_utxos_locked_until: None,
+            }));
+        },
+        (status @ (PaymentStatus::Scheduled | PaymentStatus::Received), _) => {
+            Payment::cancel(&mut transaction, &payment_id, status, &Actor::Api, Some(&detail)).await?
+        },
+        // PR may reserve UTXOs for the batch under its current key at any moment, so it is not rebuilt now.
+        (PaymentStatus::Batched, Some(batch))
+            if batch.status == PaymentBatchStatus::PendingBatching
+                && batch.lease_expires_at.is_some_and(|expires_at| expires_at > Utc::now()) =>
+        {
+            return Err(ApiError::Conflict(
+                "PR is building the transaction of the payment's batch right now, try again shortly".to_string(),
+            ));
+        },
+        // Once a transaction has been signed it may reach the chain, so the payment can no longer be stopped.
+        (PaymentStatus::Batched, Some(batch))
+            if matches!(
+                batch.status,
+                PaymentBatchStatus::PendingBatching
+                    | PaymentBatchStatus::AwaitingApproval
+                    | PaymentBatchStatus::AwaitingSignature
+            ) && batch.signed_tx_json.is_none() =>
+        {
+            PaymentBatch::cancel_payment(
+                &mut transaction,
+                &batch.id,
+                batch.status,
+                &payment_id,
+                &Actor::Api,
+                Some(&detail),
+            )
+            .await?
+        },
+        (PaymentStatus::Batched, _) => {
+            return Err(ApiError::Conflict(
+                "Payment cannot be cancelled once its transaction is being signed".to_string(),
+            ));
+        },
+        (status, _) => {
+            return Err(ApiError::Conflict(format!("A {} payment cannot be cancelled", status)));
+        },
+    };
+    if !cancelled {
+        return Err(ApiError::Conflict(
+            "Payment changed status while it was cancelled, reload it and try again".to_string(),
+        ));
+    }
+
+    let payment = Payment::get_by_id(&mut transaction, &payment_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Payment not found".to_string()))?;
+    let batch_utxos_locked_until = match batch_id {
+        Some(batch_id) => PaymentBatch::utxos_locked_until(&mut transaction, &batch_id).await?,
+        None => None,
+    };
+    transaction.commit().await?;
+
+    Ok(Json(CancelPaymentResponse {
+        payment: PaymentResponse::from(payment),
+        batch_utxos_locked_until,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use tari_crypto::keys::PublicKey as _;
+    use tari_transaction_components::tari_common_types::types::{PrivateKey, PublicKey};
+
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn payment_id_length_is_counted_in_bytes() {
+        assert!(validate_payment_id(&"a".repeat(MAX_PAYMENT_ID_BYTES)).is_ok());
+        assert!(validate_payment_id(&"a".repeat(MAX_PAYMENT_ID_BYTES + 1)).is_err());
+
+        // 'é' is two bytes in UTF-8, so 126 of them exceed the limit.
+        assert!(validate_payment_id(&"é".repeat(MAX_PAYMENT_ID_BYTES / 2)).is_ok());
+        assert!(validate_payment_id(&"é".repeat(MAX_PAYMENT_ID_BYTES / 2 + 1)).is_err());
+    }
+
+    #[test]
+    fn payment_id_must_be_printable() {
+        assert!(validate_payment_id("").is_err());
+        assert!(validate_payment_id("invoice\n42").is_err());
+        assert!(validate_payment_id("invoice\u{0}42").is_err());
+        assert!(validate_payment_id("invoice\u{7f}42").is_err());
+        assert!(validate_payment_id("Invoice #42 — ünïcödé 🎉").is_ok());
+    }
+
+    fn recipient_address(secret: u64) -> String {
+        let spend_key = PublicKey::from_secret_key(&PrivateKey::from(secret));
+        TariAddress::new_single_address_with_default_features(spend_key, Network::LocalNet).to_base58()
+    }
+
+    async fn create_payment(pool: &SqlitePool, auth: &Authenticated, amount: i64) -> (StatusCode, serde_json::Value) {
+        let request = serde_json::from_value::<PaymentRequest>(json!({
+            "client_id": "order-1",
+            "account_name": "default",
+            "recipient_address": recipient_address(1),
+            "amount": amount,
+        }))
+        .unwrap();
+        let response = match api_create_payment(
+            State(pool.clone()),
+            State(Network::LocalNet),
+            Authenticated(auth.0.clone()),
+            Json(request),
+        )
+        .await
+        {
+            Ok(response) => response.into_response(),
+            Err(e) => e.into_response(),
+        };
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn concurrent_identical_requests_create_one_payment(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let auth = Authenticated::for_scopes(&mut conn, &[ApiScope::PaymentsWrite]).await;
+        drop(conn);
+
+        let ((first_status, first), (second_status, second)) =
+            tokio::join!(create_payment(&pool, &auth, 1_000), create_payment(&pool, &auth, 1_000));
+
+        let mut statuses = [first_status, second_status];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::ACCEPTED]);
+        assert!(first["payment_id"].is_string());
+        assert_eq!(first["payment_id"], second["payment_id"]);
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM payments")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn concurrent_conflicting_requests_get_a_conflict(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let auth = Authenticated::for_scopes(&mut conn, &[ApiScope::PaymentsWrite]).await;
+        drop(conn);
+
+        let ((first_status, _), (second_status, _)) =
+            tokio::join!(create_payment(&pool, &auth, 1_000), create_payment(&pool, &auth, 2_000));
+
+        let mut statuses = [first_status, second_status];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::ACCEPTED, StatusCode::CONFLICT]);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn only_open_payments_get_normalized_addresses(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let payments = test_support::create_payments(&mut conn, 2, 1_000).await;
+        let canonical = recipient_address(1);
+        let hex = TariAddress::from_str(&canonical).unwrap().to_hex();
+        sqlx::query("UPDATE payments SET recipient_address = ?")
+            .bind(&hex)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE payments SET status = 'CONFIRMED' WHERE id = ?")
+            .bind(&payments[1].id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        normalize_stored_addresses(&pool, Network::LocalNet).await.unwrap();
+        normalize_stored_addresses(&pool, Network::LocalNet).await.unwrap();
+
+        let open = Payment::get_by_id(&mut conn, &payments[0].id).await.unwrap().unwrap();
+        assert_eq!(open.recipient_address, canonical);
+        let confirmed = Payment::get_by_id(&mut conn, &payments[1].id).await.unwrap().unwrap();
+        assert_eq!(confirmed.recipient_address, hex);
+        let events: Vec<(String, String)> =
+            sqlx::query_as("SELECT payment_id, actor FROM payment_events WHERE actor = 'startup'")
+                .fetch_all(&mut *conn)
+                .await
+                .unwrap();
+        assert_eq!(events, vec![(payments[0].id.clone(), "startup".to_string())]);
+    }
+
+    async fn cancel_payment(pool: &SqlitePool, payment_id: &str) -> (StatusCode, serde_json::Value) {
+        let mut conn = pool.acquire().await.unwrap();
+        let auth = Authenticated::for_scopes(&mut conn, &[ApiScope::PaymentsWrite]).await;
+        drop(conn);
+        let response = match api_cancel_payment(State(pool.clone()), auth, Path(payment_id.to_string())).await {
+            Ok(response) => response.into_response(),
+            Err(e) => e.into_response(),
+        };
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn cancel_rebuilds_pending_batch_at_once(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 2, 1_000).await;
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+
+        let (status, body) = cancel_payment(&pool, &payments[0].id).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "CANCELLED");
+        assert!(body.get("batch_utxos_locked_until").is_none());
+        let rebuilt = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(rebuilt.status, PaymentBatchStatus::PendingBatching);
+        assert_ne!(rebuilt.pr_idempotency_key, batch.pr_idempotency_key);
+        assert_eq!(rebuilt.next_retry_at, None);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn cancel_rebuilds_built_batch_at_once_and_shows_utxo_lock(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 2, 1_000).await;
+        assert!(
+            PaymentBatch::update_to_awaiting_signature(
+                &mut conn,
+                &batch.id,
+                &batch.pr_idempotency_key,
+                "{}",
+                None,
+                3_600,
+                &Actor::UnsignedTxCreator,
+            )
+            .await
+            .unwrap()
+        );
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+
+        let (status, body) = cancel_payment(&pool, &payments[0].id).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "CANCELLED");
+        let locked_until: DateTime<Utc> = serde_json::from_value(body["batch_utxos_locked_until"].clone()).unwrap();
+        let lock = locked_until - Utc::now();
+        assert!(lock > chrono::Duration::seconds(3_500) && lock <= chrono::Duration::seconds(3_600));
+        let rebuilt = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(rebuilt.status, PaymentBatchStatus::PendingBatching);
+        assert_ne!(rebuilt.pr_idempotency_key, batch.pr_idempotency_key);
+        assert_eq!(rebuilt.unsigned_tx_json, None);
+        assert_eq!(rebuilt.next_retry_at, None);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn cancel_is_refused_while_pr_builds_the_batch(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 2, 1_000).await;
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+        PaymentBatch::claim_for_unsigned_tx(&mut conn, &batch.id, &batch.pr_idempotency_key)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (status, _) = cancel_payment(&pool, &payments[0].id).await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        let payment = Payment::get_by_id(&mut conn, &payments[0].id).await.unwrap().unwrap();
+        assert_eq!(payment.status, PaymentStatus::Batched);
+        let unchanged = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(unchanged.pr_idempotency_key, batch.pr_idempotency_key);
+        // The transaction PR builds under the claimed key is still accepted.
+        assert!(
+            PaymentBatch::update_to_awaiting_signature(
+                &mut conn,
+                &batch.id,
+                &batch.pr_idempotency_key,
+                "{}",
+                None,
+                3_600,
+                &Actor::UnsignedTxCreator,
+            )
+            .await
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn payment_id_is_also_accepted_as_memo() {
+        let request = |field: &str| {
+            serde_json::from_value::<PaymentRequest>(json!({
+                "client_id": "order-1",
+                "account_name": "default",
+                "recipient_address": "address",
+                "amount": 1,
+                field: "invoice 42",
+            }))
+            .unwrap()
+        };
+        assert_eq!(request("payment_id").payment_id.as_deref(), Some("invoice 42"));
+        assert_eq!(request("memo").payment_id.as_deref(), Some("invoice 42"));
+    }
+}
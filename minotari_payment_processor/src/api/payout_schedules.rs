@@ -0,0 +1,371 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{SqliteConnection, SqlitePool};
+use tari_transaction_components::tari_common::configuration::Network;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    api::{
+        auth::Authenticated,
+        error::ApiError,
+        pagination,
+        payments::{canonical_address, validate_payment_id},
+        webhooks,
+    },
+    db::{
+        api_key::ApiScope,
+        cadence::Cadence,
+        payout_schedule::{NewPayoutSchedule, PayoutRun, PayoutRunStatus, PayoutSchedule, PayoutScheduleUpdate},
+    },
+};
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct PayoutScheduleRequest {
+    pub account_name: String,
+    /// A Tari address on the configured network, in emoji, base58 or hex form.
+    pub recipient_address: String,
+    pub amount: i64,
+    /// Sent as the payment ID of every payment. Also accepted as `memo`.
+    #[serde(alias = "memo")]
+    pub payment_id: Option<String>,
+    pub callback_url: Option<String>,
+    /// A five-field cron expression evaluated in UTC, e.g. `0 9 * * 1` for every Monday at 09:00,
+    /// or one of `@hourly`, `@daily`, `@weekly` and `@monthly`.
+    pub cadence: String,
+    /// Creates the schedule without running it.
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Changes to a payout schedule. Omitted fields are left unchanged.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct PayoutScheduleUpdateRequest {
+    pub recipient_address: Option<String>,
+    pub amount: Option<i64>,
+    #[serde(alias = "memo")]
+    pub payment_id: Option<String>,
+    pub callback_url: Option<String>,
+    /// Runs from the next occurrence after now.
+    pub cadence: Option<String>,
+    /// Resuming runs from the next occurrence after now, so runs due while paused are neither paid
+    /// nor reported as missed.
+    pub paused: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PayoutScheduleResponse {
+    pub schedule_id: String,
+    pub account_name: String,
+    pub recipient_address: String,
+    pub amount: i64,
+    /// The payment ID sent with every payment, named `memo` as in payment responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    pub cadence: String,
+    pub paused: bool,
+    /// Absent while the schedule is paused.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<PayoutSchedule> for PayoutScheduleResponse {
+    fn from(schedule: PayoutSchedule) -> Self {
+        PayoutScheduleResponse {
+            schedule_id: schedule.id,
+            account_name: schedule.account_name,
+            recipient_address: schedule.recipient_address,
+            amount: schedule.amount,
+            memo: schedule.payment_id,
+            callback_url: schedule.callback_url,
+            cadence: schedule.cadence,
+            paused: schedule.is_paused,
+            next_run_at: (!schedule.is_paused).then_some(schedule.next_run_at),
+            created_at: schedule.created_at,
+            updated_at: schedule.updated_at,
+        }
+    }
+}
+
+/// One occurrence of a payout schedule.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PayoutRunResponse {
+    pub scheduled_for: DateTime<Utc>,
+    pub status: PayoutRunStatus,
+    /// The payment created for the occurrence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<PayoutRun> for PayoutRunResponse {
+    fn from(run: PayoutRun) -> Self {
+        PayoutRunResponse {
+            scheduled_for: run.scheduled_for,
+            status: run.status,
+            payment_id: run.payment_id,
+            created_at: run.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPayoutSchedulesQuery {
+    pub account_name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPayoutRunsQuery {
+    /// Number of runs to return, defaults to 50 and is capped at 500.
+    pub limit: Option<i64>,
+}
+
+/// Parses a cadence and returns it with its next occurrence.
+fn parse_cadence(cadence: &str) -> Result<(Cadence, DateTime<Utc>), ApiError> {
+    let cadence = cadence.parse::<Cadence>().map_err(ApiError::BadRequest)?;
+    let next_run_at = cadence
+        .next_after(Utc::now())
+        .ok_or_else(|| ApiError::BadRequest(format!("Cadence '{}' never occurs", cadence)))?;
+    Ok((cadence, next_run_at))
+}
+
+async fn find_visible_schedule(
+    conn: &mut SqliteConnection,
+    auth: &Authenticated,
+    schedule_id: &str,
+) -> Result<PayoutSchedule, ApiError> {
+    PayoutSchedule::find_by_id(conn, schedule_id)
+        .await?
+        .filter(|schedule| auth.can_see(&schedule.account_name))
+        .ok_or_else(|| ApiError::NotFound("Payout schedule not found".to_string()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/payout-schedules",
+    request_body = PayoutScheduleRequest,
+    responses(
+        (status = 201, description = "Payout schedule created", body = PayoutScheduleResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_create_payout_schedule(
+    State(db_pool): State<SqlitePool>,
+    State(network): State<Network>,
+    auth: Authenticated,
+    Json(request): Json<PayoutScheduleRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    auth.require_account(&request.account_name)?;
+    if request.amount <= 0 {
+        return Err(ApiError::BadRequest("Amount must be positive".to_string()));
+    }
+    let recipient_address = canonical_address(&request.recipient_address, network)?;
+    if let Some(payment_id) = &request.payment_id {
+        validate_payment_id(payment_id)?;
+    }
+    if let Some(callback_url) = &request.callback_url {
+        webhooks::validate_webhook_url(callback_url)?;
+    }
+    let (cadence, next_run_at) = parse_cadence(&request.cadence)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let new_schedule = NewPayoutSchedule {
+        account_name: &request.account_name,
+        recipient_address: &recipient_address,
+        amount: request.amount,
+        payment_id: request.payment_id.as_deref(),
+        callback_url: request.callback_url.as_deref(),
+        cadence: &cadence,
+        is_paused: request.paused,
+        next_run_at,
+    };
+    let schedule = PayoutSchedule::create(&mut conn, &new_schedule).await?;
+
+    Ok((StatusCode::CREATED, Json(PayoutScheduleResponse::from(schedule))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/payout-schedules",
+    params(ListPayoutSchedulesQuery),
+    responses(
+        (status = 200, description = "Payout schedules of the account, oldest first", body = Vec<PayoutScheduleResponse>),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_list_payout_schedules(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Query(query): Query<ListPayoutSchedulesQuery>,
+) -> Result<Json<Vec<PayoutScheduleResponse>>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    auth.require_account(&query.account_name)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let schedules = PayoutSchedule::find_by_account_name(&mut conn, &query.account_name).await?;
+
+    Ok(Json(schedules.into_iter().map(PayoutScheduleResponse::from).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/payout-schedules/{schedule_id}",
+    responses(
+        (status = 200, description = "Payout schedule retrieved successfully", body = PayoutScheduleResponse),
+        (status = 404, description = "Payout schedule not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_get_payout_schedule(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(schedule_id): Path<String>,
+) -> Result<Json<PayoutScheduleResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let schedule = find_visible_schedule(&mut conn, &auth, &schedule_id).await?;
+
+    Ok(Json(PayoutScheduleResponse::from(schedule)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/v1/payout-schedules/{schedule_id}",
+    request_body = PayoutScheduleUpdateRequest,
+    responses(
+        (status = 200, description = "Payout schedule updated", body = PayoutScheduleResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 404, description = "Payout schedule not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_update_payout_schedule(
+    State(db_pool): State<SqlitePool>,
+    State(network): State<Network>,
+    auth: Authenticated,
+    Path(schedule_id): Path<String>,
+    Json(request): Json<PayoutScheduleUpdateRequest>,
+) -> Result<Json<PayoutScheduleResponse>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    if request.amount.is_some_and(|amount| amount <= 0) {
+        return Err(ApiError::BadRequest("Amount must be positive".to_string()));
+    }
+    let recipient_address = request
+        .recipient_address
+        .as_deref()
+        .map(|address| canonical_address(address, network))
+        .transpose()?;
+    if let Some(payment_id) = &request.payment_id {
+        validate_payment_id(payment_id)?;
+    }
+    if let Some(callback_url) = &request.callback_url {
+        webhooks::validate_webhook_url(callback_url)?;
+    }
+    let new_cadence = request.cadence.as_deref().map(parse_cadence).transpose()?;
+
+    let mut transaction = db_pool.begin().await?;
+    let schedule = find_visible_schedule(&mut transaction, &auth, &schedule_id).await?;
+
+    // The schedule restarts from the next occurrence after now when its cadence changes or it is
+    // resumed, so the time it spent paused is not reported as missed runs.
+    let resumed = schedule.is_paused && request.paused == Some(false);
+    let next_run_at = match &new_cadence {
+        Some((_, next_run_at)) => Some(*next_run_at),
+        None if resumed => Some(parse_cadence(&schedule.cadence)?.1),
+        None => None,
+    };
+    let update = PayoutScheduleUpdate {
+        recipient_address: recipient_address.as_deref(),
+        amount: request.amount,
+        payment_id: request.payment_id.as_deref(),
+        callback_url: request.callback_url.as_deref(),
+        cadence: new_cadence.as_ref().map(|(cadence, _)| cadence),
+        is_paused: request.paused,
+        next_run_at,
+    };
+    PayoutSchedule::update(&mut transaction, &schedule_id, &update).await?;
+
+    let schedule = find_visible_schedule(&mut transaction, &auth, &schedule_id).await?;
+    transaction.commit().await?;
+
+    Ok(Json(PayoutScheduleResponse::from(schedule)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/payout-schedules/{schedule_id}",
+    responses(
+        (status = 204, description = "Payout schedule deleted. Payments it created are kept."),
+        (status = 404, description = "Payout schedule not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_delete_payout_schedule(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(schedule_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    let mut conn = db_pool.acquire().await?;
+
+    find_visible_schedule(&mut conn, &auth, &schedule_id).await?;
+    if !PayoutSchedule::delete(&mut conn, &schedule_id).await? {
+        return Err(ApiError::NotFound("Payout schedule not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/payout-schedules/{schedule_id}/runs",
+    params(ListPayoutRunsQuery),
+    responses(
+        (status = 200, description = "Most recent runs of the schedule, newest first", body = Vec<PayoutRunResponse>),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 404, description = "Payout schedule not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_list_payout_runs(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(schedule_id): Path<String>,
+    Query(query): Query<ListPayoutRunsQuery>,
+) -> Result<Json<Vec<PayoutRunResponse>>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    let limit = pagination::page_limit(query.limit)?;
+    let mut conn = db_pool.acquire().await?;
+
+    find_visible_schedule(&mut conn, &auth, &schedule_id).await?;
+    let runs = PayoutRun::find_by_schedule_id(&mut conn, &schedule_id, limit).await?;
+
+    Ok(Json(runs.into_iter().map(PayoutRunResponse::from).collect()))
+}
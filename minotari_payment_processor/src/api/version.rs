@@ -18,6 +18,7 @@ impl ServiceVersion {
 #[utoipa::path(
     get,
     path = "/health/version",
+    security(()),
     responses(
         (status = 200, description = "Service version", body = ServiceVersion),
     )
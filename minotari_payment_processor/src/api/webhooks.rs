@@ -0,0 +1,151 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use url::Url;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    api::{auth::Authenticated, error::ApiError},
+    db::{api_key::ApiScope, webhook::WebhookSubscription},
+};
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct WebhookSubscriptionRequest {
+    pub account_name: String,
+    /// Receives a signed POST whenever a payment of the account reaches CONFIRMED, FAILED or
+    /// CANCELLED.
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WebhookSubscriptionResponse {
+    pub subscription_id: String,
+    pub account_name: String,
+    pub url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<WebhookSubscription> for WebhookSubscriptionResponse {
+    fn from(subscription: WebhookSubscription) -> Self {
+        WebhookSubscriptionResponse {
+            subscription_id: subscription.id,
+            account_name: subscription.account_name,
+            url: subscription.url,
+            created_at: subscription.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListWebhooksQuery {
+    pub account_name: String,
+}
+
+/// Checks that a webhook URL is an absolute http(s) URL. Plain http is accepted so a local
+/// receiver can be used for testing.
+pub(crate) fn validate_webhook_url(url: &str) -> Result<(), ApiError> {
+    let parsed = Url::parse(url).map_err(|e| ApiError::BadRequest(format!("Invalid webhook URL: {}", e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+        return Err(ApiError::BadRequest(
+            "Webhook URL must be an http or https URL".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/webhooks",
+    request_body = WebhookSubscriptionRequest,
+    responses(
+        (status = 201, description = "Webhook subscription created", body = WebhookSubscriptionResponse),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_create_webhook(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Json(request): Json<WebhookSubscriptionRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    auth.require_account(&request.account_name)?;
+    validate_webhook_url(&request.url)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let subscription = WebhookSubscription::create(&mut conn, &request.account_name, &request.url).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(WebhookSubscriptionResponse::from(subscription)),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/webhooks",
+    params(ListWebhooksQuery),
+    responses(
+        (status = 200, description = "Webhook subscriptions retrieved successfully", body = Vec<WebhookSubscriptionResponse>),
+        (status = 400, description = "Bad request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_list_webhooks(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Query(query): Query<ListWebhooksQuery>,
+) -> Result<Json<Vec<WebhookSubscriptionResponse>>, ApiError> {
+    auth.require_scope(ApiScope::PaymentsRead)?;
+    auth.require_account(&query.account_name)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let subscriptions = WebhookSubscription::find_by_account_name(&mut conn, &query.account_name).await?;
+
+    Ok(Json(
+        subscriptions
+            .into_iter()
+            .map(WebhookSubscriptionResponse::from)
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/webhooks/{subscription_id}",
+    responses(
+        (status = 204, description = "Webhook subscription deleted"),
+        (status = 404, description = "Webhook subscription not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "API key lacks the required scope or account", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    )
+)]
+pub async fn api_delete_webhook(
+    State(db_pool): State<SqlitePool>,
+    auth: Authenticated,
+    Path(subscription_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    auth.require_scope(ApiScope::PaymentsWrite)?;
+    let mut conn = db_pool.acquire().await?;
+
+    let subscription = WebhookSubscription::find_by_id(&mut conn, &subscription_id).await?;
+    if !subscription.is_some_and(|subscription| auth.can_see(&subscription.account_name))
+        || !WebhookSubscription::delete(&mut conn, &subscription_id).await?
+    {
+        return Err(ApiError::NotFound("Webhook subscription not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
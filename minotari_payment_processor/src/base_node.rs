@@ -0,0 +1,191 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use minotari_node_wallet_client::{BaseNodeWalletClient, http::Client};
+use tari_transaction_components::offline_signing::models::SignedOneSidedTransactionResult;
+use tari_transaction_components::rpc::models::{TxLocation, TxSubmissionRejectionReason};
+use tari_utilities::byte_array::ByteArray;
+
+use crate::db::{payment::FailureCode, payment_batch::MinedBlock};
+
+/// Where the base node places a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionLocation {
+    Mined(MinedBlock),
+    InMempool,
+    /// Neither mined nor in the mempool.
+    Unknown,
+    /// The node has pruned the blocks the transaction could be in, so it cannot tell.
+    NotStored,
+}
+
+/// The base node's answer to a submitted transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Submission {
+    Accepted,
+    /// The transaction is already mined, e.g. by an earlier submission whose outcome was lost.
+    AlreadyMined,
+    /// The transaction was rejected for `reason`, which maps onto `code`.
+    Rejected {
+        code: FailureCode,
+        reason: String,
+    },
+}
+
+/// The chain queries the workers make about batches' transactions. Implemented by the base node
+/// client, and by a mock in tests.
+#[async_trait]
+pub trait BaseNode: Send + Sync {
+    /// The height of the tip of the best chain.
+    async fn best_block_height(&self) -> Result<u64, anyhow::Error>;
+
+    /// Looks up a signed transaction, as stored on a batch, by its kernel.
+    async fn transaction_location(&self, signed_tx_json: &str) -> Result<TransactionLocation, anyhow::Error>;
+
+    /// Submits a signed transaction, as stored on a batch, to the mempool.
+    async fn submit(&self, signed_tx_json: &str) -> Result<Submission, anyhow::Error>;
+}
+
+#[async_trait]
+impl BaseNode for Client {
+    async fn best_block_height(&self) -> Result<u64, anyhow::Error> {
+        let tip_info = self.get_tip_info().await?;
+        Ok(tip_info
+            .metadata
+            .ok_or_else(|| anyhow!("Tip info has no metadata"))?
+            .best_block_height())
+    }
+
+    async fn transaction_location(&self, signed_tx_json: &str) -> Result<TransactionLocation, anyhow::Error> {
+        let signed_tx = SignedOneSidedTransactionResult::from_json(signed_tx_json)?;
+        let sig = &signed_tx
+            .signed_transaction
+            .transaction
+            .body
+            .kernels()
+            .first()
+            .ok_or_else(|| anyhow!("Transaction has no kernel"))?
+            .excess_sig;
+        let response = self
+            .transaction_query(sig.get_compressed_public_nonce().to_vec(), sig.get_signature().to_vec())
+            .await?;
+
+        Ok(match response.location {
+            TxLocation::Mined => TransactionLocation::Mined(MinedBlock {
+                height: response
+                    .mined_height
+                    .ok_or_else(|| anyhow!("Mined transaction has no mined_height"))?,
+                header_hash: response
+                    .mined_header_hash
+                    .ok_or_else(|| anyhow!("Mined transaction has no mined_header_hash"))?,
+                timestamp: response
+                    .mined_timestamp
+                    .ok_or_else(|| anyhow!("Mined transaction has no mined_timestamp"))?,
+            }),
+            TxLocation::InMempool => TransactionLocation::InMempool,
+            TxLocation::None => TransactionLocation::Unknown,
+            TxLocation::NotStored => TransactionLocation::NotStored,
+        })
+    }
+
+    async fn submit(&self, signed_tx_json: &str) -> Result<Submission, anyhow::Error> {
+        let signed_tx = SignedOneSidedTransactionResult::from_json(signed_tx_json)?;
+        let response = self
+            .submit_transaction(signed_tx.signed_transaction.transaction)
+            .await?;
+        Ok(if response.accepted {
+            Submission::Accepted
+        } else if matches!(response.rejection_reason, TxSubmissionRejectionReason::AlreadyMined) {
+            Submission::AlreadyMined
+        } else {
+            Submission::Rejected {
+                code: rejection_failure_code(&response.rejection_reason),
+                reason: response.rejection_reason.to_string(),
+            }
+        })
+    }
+}
+
+/// Maps a base node's reason for rejecting a transaction onto a [`FailureCode`]. Spent inputs and invalid
+/// transactions are permanent; a fee or time lock problem may clear up in a later block.
+fn rejection_failure_code(reason: &TxSubmissionRejectionReason) -> FailureCode {
+    match reason {
+        TxSubmissionRejectionReason::DoubleSpend => FailureCode::InputsSpent,
+        // An orphan's inputs are not in the node's UTXO set. They may have been spent, but a node that is behind
+        // or on a fork does not know them yet either, so this proves nothing.
+        TxSubmissionRejectionReason::Orphan => FailureCode::InputsUnknown,
+        TxSubmissionRejectionReason::ValidationFailed => FailureCode::InvalidTransaction,
+        TxSubmissionRejectionReason::FeeTooLow => FailureCode::FeeTooLow,
+        TxSubmissionRejectionReason::TimeLocked => FailureCode::TimeLocked,
+        _ => FailureCode::BroadcastRejected,
+    }
+}
+
+/// A base node whose chain tests set up by hand. Transactions it has not been told about are unknown,
+/// and submissions it has not been told about are accepted.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct MockBaseNode {
+    best_block_height: u64,
+    locations: std::collections::HashMap<String, TransactionLocation>,
+    submissions: std::collections::HashMap<String, Submission>,
+}
+
+#[cfg(test)]
+impl MockBaseNode {
+    pub(crate) fn new(best_block_height: u64) -> Self {
+        MockBaseNode {
+            best_block_height,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn with_transaction(mut self, signed_tx_json: &str, location: TransactionLocation) -> Self {
+        self.locations.insert(signed_tx_json.to_string(), location);
+        self
+    }
+
+    pub(crate) fn with_submission(mut self, signed_tx_json: &str, submission: Submission) -> Self {
+        self.submissions.insert(signed_tx_json.to_string(), submission);
+        self
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl BaseNode for MockBaseNode {
+    async fn best_block_height(&self) -> Result<u64, anyhow::Error> {
+        Ok(self.best_block_height)
+    }
+
+    async fn transaction_location(&self, signed_tx_json: &str) -> Result<TransactionLocation, anyhow::Error> {
+        Ok(self
+            .locations
+            .get(signed_tx_json)
+            .cloned()
+            .unwrap_or(TransactionLocation::Unknown))
+    }
+
+    async fn submit(&self, signed_tx_json: &str) -> Result<Submission, anyhow::Error> {
+        Ok(self
+            .submissions
+            .get(signed_tx_json)
+            .cloned()
+            .unwrap_or(Submission::Accepted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_double_spends_mean_inputs_spent() {
+        assert_eq!(
+            rejection_failure_code(&TxSubmissionRejectionReason::DoubleSpend),
+            FailureCode::InputsSpent
+        );
+        let orphan = rejection_failure_code(&TxSubmissionRejectionReason::Orphan);
+        assert_eq!(orphan, FailureCode::InputsUnknown);
+        assert!(orphan.is_retryable());
+    }
+}
@@ -0,0 +1,217 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqliteConnection;
+use std::{fmt, str::FromStr};
+use uuid::Uuid;
+
+/// Grants access to every account when present in a key's account names.
+pub const ALL_ACCOUNTS: &str = "*";
+
+const KEY_PREFIX: &str = "mpp_";
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiScope {
+    #[serde(rename = "payments:read")]
+    PaymentsRead,
+    #[serde(rename = "payments:write")]
+    PaymentsWrite,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "payments:read" => Ok(ApiScope::PaymentsRead),
+            "payments:write" => Ok(ApiScope::PaymentsWrite),
+            "admin" => Ok(ApiScope::Admin),
+            _ => Err(format!(
+                "Unknown scope '{}', expected payments:read, payments:write or admin",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiScope::PaymentsRead => write!(f, "payments:read"),
+            ApiScope::PaymentsWrite => write!(f, "payments:write"),
+            ApiScope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub key_prefix: String,
+    pub account_names: Vec<String>,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    /// Issues a new key. Returns the key record and the key itself, which is not stored and
+    /// cannot be retrieved again.
+    pub async fn create(
+        pool: &mut SqliteConnection,
+        name: &str,
+        account_names: &[String],
+        scopes: &[ApiScope],
+    ) -> Result<(Self, String), sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        // Two random UUIDs give 244 bits of entropy from the OS random number generator.
+        let secret = format!("{}{}{}", KEY_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let key_hash = hash_key(&secret);
+        let key_prefix = secret[..DISPLAY_PREFIX_LEN].to_string();
+        let account_names_json = serde_json::to_string(account_names).unwrap();
+        let scopes_json = serde_json::to_string(scopes).unwrap();
+
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO api_keys (id, name, key_hash, key_prefix, account_names, scopes)
+            VALUES (?, ?, ?, ?, ?, ?)
+            RETURNING
+                created_at as "created_at: DateTime<Utc>"
+            "#,
+            id,
+            name,
+            key_hash,
+            key_prefix,
+            account_names_json,
+            scopes_json,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        let api_key = ApiKey {
+            id,
+            name: name.to_string(),
+            key_prefix,
+            account_names: account_names.to_vec(),
+            scopes: scopes.to_vec(),
+            created_at: row.created_at,
+            revoked_at: None,
+        };
+        Ok((api_key, secret))
+    }
+
+    /// Retrieves the unrevoked key matching the given secret.
+    pub async fn find_active_by_secret(pool: &mut SqliteConnection, secret: &str) -> Result<Option<Self>, sqlx::Error> {
+        let key_hash = hash_key(secret);
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                name,
+                key_prefix,
+                account_names,
+                scopes,
+                created_at as "created_at: DateTime<Utc>",
+                revoked_at as "revoked_at: DateTime<Utc>"
+            FROM api_keys
+            WHERE key_hash = ? AND revoked_at IS NULL
+            "#,
+            key_hash
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        row.map(|row| {
+            Ok(ApiKey {
+                id: row.id,
+                name: row.name,
+                key_prefix: row.key_prefix,
+                account_names: decode_json_column(&row.account_names)?,
+                scopes: decode_json_column(&row.scopes)?,
+                created_at: row.created_at,
+                revoked_at: row.revoked_at,
+            })
+        })
+        .transpose()
+    }
+
+    /// Retrieves all keys, including revoked ones, oldest first.
+    pub async fn list(pool: &mut SqliteConnection) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                name,
+                key_prefix,
+                account_names,
+                scopes,
+                created_at as "created_at: DateTime<Utc>",
+                revoked_at as "revoked_at: DateTime<Utc>"
+            FROM api_keys
+            ORDER BY created_at, id
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(ApiKey {
+                    id: row.id,
+                    name: row.name,
+                    key_prefix: row.key_prefix,
+                    account_names: decode_json_column(&row.account_names)?,
+                    scopes: decode_json_column(&row.scopes)?,
+                    created_at: row.created_at,
+                    revoked_at: row.revoked_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Revokes a key. Returns `false` if no unrevoked key with this ID exists.
+    pub async fn revoke(pool: &mut SqliteConnection, id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE api_keys
+            SET revoked_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND revoked_at IS NULL
+            "#,
+            id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub fn can_access_account(&self, account_name: &str) -> bool {
+        self.account_names
+            .iter()
+            .any(|name| name == ALL_ACCOUNTS || name == account_name)
+    }
+
+    /// The accounts this key is restricted to, or `None` if it may access every account.
+    pub fn account_restriction(&self) -> Option<&[String]> {
+        if self.account_names.iter().any(|name| name == ALL_ACCOUNTS) {
+            None
+        } else {
+            Some(&self.account_names)
+        }
+    }
+}
+
+fn hash_key(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn decode_json_column<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, sqlx::Error> {
+    serde_json::from_str(value).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
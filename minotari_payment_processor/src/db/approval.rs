@@ -0,0 +1,234 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use utoipa::ToSchema;
+
+/// The most approvals any policy may require.
+pub const MAX_REQUIRED_APPROVALS: i64 = 10;
+
+/// Which of an account's batches need operator approval before they are signed, and how many.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ApprovalPolicy {
+    /// How many distinct operators must approve a batch.
+    pub required_approvals: i64,
+    /// Batches paying out at least this much in total, in µT, need approval.
+    pub min_total_amount: Option<i64>,
+    /// Batches with at least this many payments need approval.
+    pub min_recipients: Option<i64>,
+}
+
+impl ApprovalPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_REQUIRED_APPROVALS).contains(&self.required_approvals) {
+            return Err(format!(
+                "required_approvals must be between 1 and {}",
+                MAX_REQUIRED_APPROVALS
+            ));
+        }
+        if self.min_total_amount.is_some_and(|amount| amount <= 0) {
+            return Err("min_total_amount must be positive".to_string());
+        }
+        if self.min_recipients.is_some_and(|recipients| recipients <= 0) {
+            return Err("min_recipients must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether a batch of `recipients` payments totalling `total_amount` needs approval. Without
+    /// thresholds every batch does.
+    pub fn applies_to(&self, total_amount: i64, recipients: i64) -> bool {
+        match (self.min_total_amount, self.min_recipients) {
+            (None, None) => true,
+            (min_total_amount, min_recipients) => {
+                min_total_amount.is_some_and(|min| total_amount >= min)
+                    || min_recipients.is_some_and(|min| recipients >= min)
+            },
+        }
+    }
+}
+
+/// The approval policy of one account.
+#[derive(Debug, Clone)]
+pub struct AccountApprovalPolicy {
+    pub account_name: String,
+    pub policy: ApprovalPolicy,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl AccountApprovalPolicy {
+    /// Finds the approval policy of an account.
+    pub async fn find_by_account_name(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                account_name,
+                required_approvals,
+                min_total_amount,
+                min_recipients,
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM approval_policies
+            WHERE account_name = ?
+            "#,
+            account_name
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|row| AccountApprovalPolicy {
+            account_name: row.account_name,
+            policy: ApprovalPolicy {
+                required_approvals: row.required_approvals,
+                min_total_amount: row.min_total_amount,
+                min_recipients: row.min_recipients,
+            },
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }))
+    }
+
+    /// Retrieves all approval policies, ordered by account name.
+    pub async fn list(pool: &mut SqliteConnection) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                account_name,
+                required_approvals,
+                min_total_amount,
+                min_recipients,
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM approval_policies
+            ORDER BY account_name
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AccountApprovalPolicy {
+                account_name: row.account_name,
+                policy: ApprovalPolicy {
+                    required_approvals: row.required_approvals,
+                    min_total_amount: row.min_total_amount,
+                    min_recipients: row.min_recipients,
+                },
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect())
+    }
+
+    /// Sets the approval policy of an account, replacing any existing one. Batches already awaiting
+    /// approval keep the number of approvals they were created with.
+    pub async fn upsert(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+        policy: &ApprovalPolicy,
+    ) -> Result<Self, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO approval_policies (account_name, required_approvals, min_total_amount, min_recipients)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (account_name) DO UPDATE SET
+                required_approvals = excluded.required_approvals,
+                min_total_amount = excluded.min_total_amount,
+                min_recipients = excluded.min_recipients,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            "#,
+            account_name,
+            policy.required_approvals,
+            policy.min_total_amount,
+            policy.min_recipients,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(AccountApprovalPolicy {
+            account_name: account_name.to_string(),
+            policy: policy.clone(),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+
+    /// Removes the approval policy of an account, so its new batches no longer need approval.
+    /// Returns `false` if the account had no policy.
+    pub async fn delete(pool: &mut SqliteConnection, account_name: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM approval_policies WHERE account_name = ?", account_name)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// An operator's approval of a batch's unsigned transaction.
+#[derive(Debug, Clone)]
+pub struct BatchApproval {
+    pub payment_batch_id: String,
+    pub pr_idempotency_key: String,
+    pub approver: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl BatchApproval {
+    /// Records an approval of the unsigned transaction built under `pr_idempotency_key`.
+    /// Returns `false` if the approver already approved it.
+    pub async fn record(
+        pool: &mut SqliteConnection,
+        payment_batch_id: &str,
+        pr_idempotency_key: &str,
+        approver: &str,
+        reason: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO batch_approvals (payment_batch_id, pr_idempotency_key, approver, reason)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (payment_batch_id, pr_idempotency_key, approver) DO NOTHING
+            "#,
+            payment_batch_id,
+            pr_idempotency_key,
+            approver,
+            reason,
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the approvals of the unsigned transaction built under `pr_idempotency_key`, oldest first.
+    pub async fn find_by_batch(
+        pool: &mut SqliteConnection,
+        payment_batch_id: &str,
+        pr_idempotency_key: &str,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            BatchApproval,
+            r#"
+            SELECT
+                payment_batch_id,
+                pr_idempotency_key,
+                approver,
+                reason,
+                created_at as "created_at: DateTime<Utc>"
+            FROM batch_approvals
+            WHERE payment_batch_id = ? AND pr_idempotency_key = ?
+            ORDER BY created_at, approver
+            "#,
+            payment_batch_id,
+            pr_idempotency_key,
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
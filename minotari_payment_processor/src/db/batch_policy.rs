@@ -0,0 +1,193 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use utoipa::ToSchema;
+
+/// The most payments any policy may put in one batch.
+pub const MAX_RECIPIENTS_LIMIT: i64 = 1_000;
+
+/// Controls how an account's queued payments are grouped into batches. Payments are always
+/// batched oldest first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BatchPolicy {
+    /// The most payments a single batch may contain.
+    pub max_recipients: i64,
+    /// The most a single batch may pay out in total, in µT. A payment larger than this on its
+    /// own is batched alone.
+    pub max_total_amount: Option<i64>,
+    /// How many payments must be queued before a batch is created, unless the oldest has waited
+    /// `max_age_secs`.
+    pub min_recipients: i64,
+    /// How long the oldest queued payment may wait for `min_recipients` before it is batched
+    /// anyway. Required when `min_recipients` is above 1.
+    pub max_age_secs: Option<i64>,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        BatchPolicy {
+            max_recipients: 100,
+            max_total_amount: None,
+            min_recipients: 1,
+            max_age_secs: None,
+        }
+    }
+}
+
+impl BatchPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_RECIPIENTS_LIMIT).contains(&self.max_recipients) {
+            return Err(format!("max_recipients must be between 1 and {}", MAX_RECIPIENTS_LIMIT));
+        }
+        if !(1..=self.max_recipients).contains(&self.min_recipients) {
+            return Err("min_recipients must be between 1 and max_recipients".to_string());
+        }
+        if self.max_total_amount.is_some_and(|amount| amount <= 0) {
+            return Err("max_total_amount must be positive".to_string());
+        }
+        if self.max_age_secs.is_some_and(|secs| secs <= 0) {
+            return Err("max_age_secs must be positive".to_string());
+        }
+        if self.min_recipients > 1 && self.max_age_secs.is_none() {
+            return Err("max_age_secs is required when min_recipients is above 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether a batch should be created for a queue of `queued` payments whose oldest was
+    /// queued at `oldest_queued_at`.
+    pub fn is_due(&self, queued: i64, oldest_queued_at: DateTime<Utc>) -> bool {
+        queued >= self.min_recipients
+            || self
+                .max_age_secs
+                .is_some_and(|secs| (Utc::now() - oldest_queued_at).num_seconds() >= secs)
+    }
+}
+
+/// A batching policy that overrides the default for one account.
+#[derive(Debug, Clone)]
+pub struct AccountBatchPolicy {
+    pub account_name: String,
+    pub policy: BatchPolicy,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl AccountBatchPolicy {
+    /// Finds the policy override of an account.
+    pub async fn find_by_account_name(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                account_name,
+                max_recipients,
+                max_total_amount,
+                min_recipients,
+                max_age_secs,
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM batch_policies
+            WHERE account_name = ?
+            "#,
+            account_name
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|row| AccountBatchPolicy {
+            account_name: row.account_name,
+            policy: BatchPolicy {
+                max_recipients: row.max_recipients,
+                max_total_amount: row.max_total_amount,
+                min_recipients: row.min_recipients,
+                max_age_secs: row.max_age_secs,
+            },
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }))
+    }
+
+    /// Retrieves all policy overrides, ordered by account name.
+    pub async fn list(pool: &mut SqliteConnection) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                account_name,
+                max_recipients,
+                max_total_amount,
+                min_recipients,
+                max_age_secs,
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM batch_policies
+            ORDER BY account_name
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AccountBatchPolicy {
+                account_name: row.account_name,
+                policy: BatchPolicy {
+                    max_recipients: row.max_recipients,
+                    max_total_amount: row.max_total_amount,
+                    min_recipients: row.min_recipients,
+                    max_age_secs: row.max_age_secs,
+                },
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect())
+    }
+
+    /// Sets the policy override of an account, replacing any existing one.
+    pub async fn upsert(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+        policy: &BatchPolicy,
+    ) -> Result<Self, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO batch_policies (account_name, max_recipients, max_total_amount, min_recipients, max_age_secs)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (account_name) DO UPDATE SET
+                max_recipients = excluded.max_recipients,
+                max_total_amount = excluded.max_total_amount,
+                min_recipients = excluded.min_recipients,
+                max_age_secs = excluded.max_age_secs,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            "#,
+            account_name,
+            policy.max_recipients,
+            policy.max_total_amount,
+            policy.min_recipients,
+            policy.max_age_secs,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(AccountBatchPolicy {
+            account_name: account_name.to_string(),
+            policy: policy.clone(),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+
+    /// Removes the policy override of an account, so it uses the default policy again.
+    /// Returns `false` if the account had no override.
+    pub async fn delete(pool: &mut SqliteConnection, account_name: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM batch_policies WHERE account_name = ?", account_name)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,256 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Timelike, Utc};
+use std::{fmt, str::FromStr};
+
+/// How far ahead to look for the next occurrence. Covers 29 February, which recurs every four years.
+const MAX_SEARCH_DAYS: usize = 366 * 5;
+
+/// A cron-like cadence of five space-separated fields, evaluated in UTC: minute (0-59), hour (0-23),
+/// day of month (1-31), month (1-12) and day of week (0-7, both 0 and 7 being Sunday). Each field is
+/// `*`, a value or a range `a-b`, optionally with a step `/n`, or a comma-separated list of these.
+/// `@hourly`, `@daily`, `@weekly` and `@monthly` are accepted as shorthands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cadence {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // As in cron, when both day fields are restricted a day matching either of them is an occurrence.
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+impl FromStr for Cadence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = s.trim();
+        let expanded = match expression {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(format!(
+                "Cadence '{}' must have 5 fields: minute, hour, day of month, month and day of week",
+                expression
+            ));
+        };
+
+        let mut days_of_week = parse_field(day_of_week, "day of week", 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(Cadence {
+            expression: expression.to_string(),
+            minutes: parse_field(minute, "minute", 0, 59)?,
+            hours: parse_field(hour, "hour", 0, 23)?,
+            days_of_month: parse_field(day_of_month, "day of month", 1, 31)?,
+            months: parse_field(month, "month", 1, 12)?,
+            days_of_week,
+            days_of_month_restricted: !day_of_month.starts_with('*'),
+            days_of_week_restricted: !day_of_week.starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for Cadence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl Cadence {
+    /// The first occurrence strictly after `after`, or `None` if there is none within five years,
+    /// e.g. for 30 February.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let first_day = start.date_naive();
+
+        for day in first_day.iter_days().take(MAX_SEARCH_DAYS) {
+            if !self.matches_day(day) {
+                continue;
+            }
+            let (from_hour, from_minute) = if day == first_day {
+                (start.hour(), start.minute())
+            } else {
+                (0, 0)
+            };
+            for hour in (from_hour..24).filter(|hour| is_set(self.hours, *hour)) {
+                let first_minute = if hour == from_hour { from_minute } else { 0 };
+                if let Some(minute) = (first_minute..60).find(|minute| is_set(self.minutes, *minute)) {
+                    return Some(Utc.from_utc_datetime(&day.and_hms_opt(hour, minute, 0)?));
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, day: NaiveDate) -> bool {
+        if !is_set(self.months, day.month()) {
+            return false;
+        }
+        let day_of_month = is_set(self.days_of_month, day.day());
+        let day_of_week = is_set(self.days_of_week, day.weekday().num_days_from_sunday());
+        match (self.days_of_month_restricted, self.days_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            (true, false) => day_of_month,
+            (false, true) => day_of_week,
+            (false, false) => true,
+        }
+    }
+}
+
+fn is_set(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// Parses one field into a bit set of the values it matches.
+fn parse_field(field: &str, name: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("Invalid step '{}' in {} field", step, name))?;
+                (range, Some(step))
+            },
+            None => (part, None),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, name, min, max)?, parse_value(end, name, min, max)?)
+        } else {
+            let value = parse_value(range, name, min, max)?;
+            // As in cron, `5/15` starts at 5 and runs to the end of the field.
+            (value, if step.is_some() { max } else { value })
+        };
+        if start > end {
+            return Err(format!("Invalid range '{}' in {} field", range, name));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1)) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(value: &str, name: &str, min: u32, max: u32) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or_else(|| format!("Invalid {} '{}', expected {}-{}", name, value, min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(bits: u64) -> Vec<u32> {
+        (0..64).filter(|value| is_set(bits, *value)).collect()
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn parse_field_cases() {
+        let cases: &[(&str, u32, u32, Option<Vec<u32>>)] = &[
+            ("*", 1, 12, Some((1..=12).collect())),
+            ("5", 0, 59, Some(vec![5])),
+            ("1-3", 0, 59, Some(vec![1, 2, 3])),
+            ("*/15", 0, 59, Some(vec![0, 15, 30, 45])),
+            ("10-20/5", 0, 59, Some(vec![10, 15, 20])),
+            ("5/20", 0, 59, Some(vec![5, 25, 45])),
+            ("1,3,5-6", 0, 59, Some(vec![1, 3, 5, 6])),
+            ("1,1,1", 0, 59, Some(vec![1])),
+            ("0-23/12,7", 0, 23, Some(vec![0, 7, 12])),
+            ("60", 0, 59, None),
+            ("0", 1, 31, None),
+            ("a", 0, 59, None),
+            ("", 0, 59, None),
+            ("5-3", 0, 59, None),
+            ("1-", 0, 59, None),
+            ("-1", 0, 59, None),
+            ("*/0", 0, 59, None),
+            ("*/x", 0, 59, None),
+            ("1,,2", 0, 59, None),
+        ];
+        for (field, min, max, expected) in cases {
+            let parsed = parse_field(field, "test", *min, *max).ok().map(values);
+            assert_eq!(parsed, *expected, "field '{}'", field);
+        }
+    }
+
+    #[test]
+    fn invalid_cadences_are_rejected() {
+        for expression in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * 32 * *",
+            "* * * 0 *",
+            "* * * 13 *",
+            "* * * * 8",
+            "@yearly",
+        ] {
+            assert!(expression.parse::<Cadence>().is_err(), "cadence '{}'", expression);
+        }
+    }
+
+    #[test]
+    fn sunday_is_both_0_and_7() {
+        let zero: Cadence = "0 0 * * 0".parse().unwrap();
+        let seven: Cadence = "0 0 * * 7".parse().unwrap();
+        assert_eq!(zero.days_of_week, seven.days_of_week);
+        assert_eq!(values(seven.days_of_week), vec![0]);
+    }
+
+    #[test]
+    fn next_after_cases() {
+        // 1 January 2025 is a Wednesday.
+        let cases: &[(&str, &str, Option<&str>)] = &[
+            ("*/15 * * * *", "2025-01-01T10:07:30Z", Some("2025-01-01T10:15:00Z")),
+            // Strictly after, even on an occurrence.
+            ("0 10 * * *", "2025-01-01T10:00:00Z", Some("2025-01-02T10:00:00Z")),
+            ("@hourly", "2025-01-01T23:59:59Z", Some("2025-01-02T00:00:00Z")),
+            ("@daily", "2025-01-01T00:00:00Z", Some("2025-01-02T00:00:00Z")),
+            ("@weekly", "2025-01-01T00:00:00Z", Some("2025-01-05T00:00:00Z")),
+            ("@monthly", "2025-01-15T00:00:00Z", Some("2025-02-01T00:00:00Z")),
+            ("0 9 * * 1", "2025-01-01T10:00:00Z", Some("2025-01-06T09:00:00Z")),
+            ("0 0 * * 7", "2025-01-01T00:00:00Z", Some("2025-01-05T00:00:00Z")),
+            ("0 8-10/2 * * 1-5", "2025-01-03T10:30:00Z", Some("2025-01-06T08:00:00Z")),
+            ("0 12 1,15 * *", "2025-01-02T00:00:00Z", Some("2025-01-15T12:00:00Z")),
+            // With both day fields restricted, either one matching is an occurrence: Friday the 3rd
+            // comes before the 15th.
+            ("30 12 15 * 5", "2025-01-01T00:00:00Z", Some("2025-01-03T12:30:00Z")),
+            ("0 0 31 * *", "2025-02-01T00:00:00Z", Some("2025-03-31T00:00:00Z")),
+            ("59 23 31 12 *", "2025-06-01T00:00:00Z", Some("2025-12-31T23:59:00Z")),
+            ("0 0 29 2 *", "2025-01-01T00:00:00Z", Some("2028-02-29T00:00:00Z")),
+            ("0 0 30 2 *", "2025-01-01T00:00:00Z", None),
+        ];
+        for (expression, after, expected) in cases {
+            let cadence: Cadence = expression.parse().unwrap();
+            assert_eq!(
+                cadence.next_after(at(after)),
+                expected.map(at),
+                "cadence '{}' after {}",
+                expression,
+                after
+            );
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use std::fmt;
+use utoipa::ToSchema;
+
+use crate::db::{payment::PaymentStatus, payment_batch::PaymentBatchStatus};
+
+/// Who caused a state transition, as recorded in the event history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Actor {
+    Api,
+    BatchCreator,
+    UnsignedTxCreator,
+    TransactionSigner,
+    Broadcaster,
+    ConfirmationChecker,
+    LeaseReaper,
+    PayoutScheduler,
+    ReorgWatcher,
+    /// Data fixes applied when the service starts.
+    Startup,
+    Operator(String),
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Actor::Api => write!(f, "api"),
+            Actor::BatchCreator => write!(f, "batch_creator"),
+            Actor::UnsignedTxCreator => write!(f, "unsigned_tx_creator"),
+            Actor::TransactionSigner => write!(f, "transaction_signer"),
+            Actor::Broadcaster => write!(f, "broadcaster"),
+            Actor::ConfirmationChecker => write!(f, "confirmation_checker"),
+            Actor::LeaseReaper => write!(f, "lease_reaper"),
+            Actor::PayoutScheduler => write!(f, "payout_scheduler"),
+            Actor::ReorgWatcher => write!(f, "reorg_watcher"),
+            Actor::Startup => write!(f, "startup"),
+            Actor::Operator(name) => write!(f, "operator:{}", name),
+        }
+    }
+}
+
+/// A recorded change to a payment's status.
+#[derive(Debug, Clone)]
+pub struct PaymentEvent {
+    pub id: i64,
+    pub payment_id: String,
+    pub from_status: Option<PaymentStatus>,
+    pub to_status: PaymentStatus,
+    pub actor: String,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PaymentEvent {
+    /// Records a status change for a single payment whose previous status is known.
+    pub async fn record(
+        pool: &mut SqliteConnection,
+        payment_id: &str,
+        from_status: Option<&PaymentStatus>,
+        to_status: &PaymentStatus,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let from_status = from_status.map(|s| s.to_string());
+        let to_status = to_status.to_string();
+        let actor = actor.to_string();
+        sqlx::query!(
+            r#"
+            INSERT INTO payment_events (payment_id, from_status, to_status, actor, detail)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+            payment_id,
+            from_status,
+            to_status,
+            actor,
+            detail,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Records a status change for each of the given payments, taking their current status as the
+    /// previous one. Must run before the payments are updated, in the same transaction.
+    pub async fn record_for_payments(
+        pool: &mut SqliteConnection,
+        payment_ids: &[String],
+        to_status: &PaymentStatus,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let json = serde_json::to_string(payment_ids).unwrap();
+        let to_status = to_status.to_string();
+        let actor = actor.to_string();
+        sqlx::query!(
+            r#"
+            INSERT INTO payment_events (payment_id, from_status, to_status, actor, detail)
+            SELECT id, status, ?, ?, ?
+            FROM payments
+            WHERE id IN (SELECT value FROM json_each(?))
+            "#,
+            to_status,
+            actor,
+            detail,
+            json,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Records a status change for every payment in a batch, optionally only those currently in
+    /// `from_status`. Must run before the payments are updated, in the same transaction.
+    pub async fn record_for_batch(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        from_status: Option<&PaymentStatus>,
+        to_status: &PaymentStatus,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let from_status = from_status.map(|s| s.to_string());
+        let to_status = to_status.to_string();
+        let actor = actor.to_string();
+        sqlx::query!(
+            r#"
+            INSERT INTO payment_events (payment_id, from_status, to_status, actor, detail)
+            SELECT id, status, ?, ?, ?
+            FROM payments
+            WHERE payment_batch_id = ? AND (? IS NULL OR status = ?)
+            "#,
+            to_status,
+            actor,
+            detail,
+            batch_id,
+            from_status,
+            from_status,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Retrieves the full history of a payment, oldest first.
+    pub async fn find_by_payment_id(pool: &mut SqliteConnection, payment_id: &str) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                payment_id,
+                from_status,
+                to_status,
+                actor,
+                detail,
+                created_at as "created_at: DateTime<Utc>"
+            FROM payment_events
+            WHERE payment_id = ?
+            ORDER BY id
+            "#,
+            payment_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PaymentEvent {
+                id: row.id,
+                payment_id: row.payment_id,
+                from_status: row.from_status.map(PaymentStatus::from),
+                to_status: row.to_status.into(),
+                actor: row.actor,
+                detail: row.detail,
+                created_at: row.created_at,
+            })
+            .collect())
+    }
+}
+
+/// A recorded change to a payment batch's status, or a retry within the same status.
+#[derive(Debug, Clone)]
+pub struct BatchEvent {
+    pub id: i64,
+    pub payment_batch_id: String,
+    pub from_status: Option<PaymentBatchStatus>,
+    pub to_status: PaymentBatchStatus,
+    pub actor: String,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl BatchEvent {
+    /// Records a status change for a batch. Should run in the same transaction as the change.
+    pub async fn record(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        from_status: Option<&PaymentBatchStatus>,
+        to_status: &PaymentBatchStatus,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let from_status = from_status.map(|s| s.to_string());
+        let to_status = to_status.to_string();
+        let actor = actor.to_string();
+        sqlx::query!(
+            r#"
+            INSERT INTO batch_events (payment_batch_id, from_status, to_status, actor, detail)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+            batch_id,
+            from_status,
+            to_status,
+            actor,
+            detail,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Retrieves the full history of a batch, oldest first.
+    pub async fn find_by_batch_id(pool: &mut SqliteConnection, batch_id: &str) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                payment_batch_id,
+                from_status,
+                to_status,
+                actor,
+                detail,
+                created_at as "created_at: DateTime<Utc>"
+            FROM batch_events
+            WHERE payment_batch_id = ?
+            ORDER BY id
+            "#,
+            batch_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| BatchEvent {
+                id: row.id,
+                payment_batch_id: row.payment_batch_id,
+                from_status: row.from_status.map(PaymentBatchStatus::from),
+                to_status: row.to_status.into(),
+                actor: row.actor,
+                detail: row.detail,
+                created_at: row.created_at,
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlertKind {
+    /// The account's balance does not cover its queued payments.
+    InsufficientFunds,
+    /// Occurrences of a payout schedule passed without being paid.
+    MissedPayoutRuns,
+    /// A batch's signed transaction did not match its payments and was not broadcast.
+    VerificationFailed,
+    /// A batch's block left the best chain and its transaction is no longer known to the base node.
+    BatchReorged,
+}
+
+impl From<String> for AlertKind {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "INSUFFICIENT_FUNDS" => AlertKind::InsufficientFunds,
+            "MISSED_PAYOUT_RUNS" => AlertKind::MissedPayoutRuns,
+            "VERIFICATION_FAILED" => AlertKind::VerificationFailed,
+            "BATCH_REORGED" => AlertKind::BatchReorged,
+            _ => panic!("Unknown AlertKind: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlertKind::InsufficientFunds => write!(f, "INSUFFICIENT_FUNDS"),
+            AlertKind::MissedPayoutRuns => write!(f, "MISSED_PAYOUT_RUNS"),
+            AlertKind::VerificationFailed => write!(f, "VERIFICATION_FAILED"),
+            AlertKind::BatchReorged => write!(f, "BATCH_REORGED"),
+        }
+    }
+}
+
+/// A problem with an account that needs an operator's attention.
+#[derive(Debug, Clone)]
+pub struct AccountAlert {
+    pub id: i64,
+    pub account_name: String,
+    pub kind: AlertKind,
+    pub detail: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AccountAlert {
+    /// Records an alert for an account.
+    pub async fn record(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+        kind: AlertKind,
+        detail: &str,
+    ) -> Result<(), sqlx::Error> {
+        let kind = kind.to_string();
+        sqlx::query!(
+            r#"
+            INSERT INTO account_alerts (account_name, kind, detail)
+            VALUES (?, ?, ?)
+            "#,
+            account_name,
+            kind,
+            detail,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Retrieves the most recent alerts of an account, newest first.
+    pub async fn find_by_account_name(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            AccountAlert,
+            r#"
+            SELECT
+                id,
+                account_name,
+                kind,
+                detail,
+                created_at as "created_at: DateTime<Utc>"
+            FROM account_alerts
+            WHERE account_name = ?
+            ORDER BY id DESC
+            LIMIT ?
+            "#,
+            account_name,
+            limit,
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -1,7 +1,16 @@
+pub mod api_key;
+pub mod approval;
+pub mod batch_policy;
+pub mod cadence;
+pub mod event;
+pub mod operator_action;
 pub mod payment;
 pub mod payment_batch;
+pub mod payout_schedule;
+pub mod webhook;
 
-use sqlx::{SqlitePool, sqlite::SqlitePoolOptions};
+use chrono::{DateTime, Utc};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool, sqlite::SqlitePoolOptions};
 
 pub async fn init_db(db_url: &str) -> Result<SqlitePool, anyhow::Error> {
     let pool = SqlitePoolOptions::new().max_connections(5).connect(db_url).await?;
@@ -10,3 +19,42 @@ pub async fn init_db(db_url: &str) -> Result<SqlitePool, anyhow::Error> {
     sqlx::migrate!("../migrations").run(&pool).await?;
     Ok(pool)
 }
+
+/// Formats a timestamp the way SQLite's `CURRENT_TIMESTAMP` stores it, so it can be compared
+/// against timestamp columns as text.
+pub(crate) fn sqlite_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Appends a condition restricting rows to the given accounts. An empty list matches nothing.
+pub(crate) fn push_account_names_condition<'a>(qb: &mut QueryBuilder<'a, Sqlite>, account_names: &'a [String]) {
+    if account_names.is_empty() {
+        qb.push(" AND 0");
+        return;
+    }
+    qb.push(" AND account_name IN (");
+    let mut separated = qb.separated(", ");
+    for account_name in account_names {
+        separated.push_bind(account_name.as_str());
+    }
+    separated.push_unseparated(")");
+}
+
+/// Keyset position in a listing ordered by `(created_at, id)`, newest first.
+/// Paging by position rather than offset keeps pages stable while new rows arrive.
+#[derive(Debug, Clone)]
+pub struct PageCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: String,
+}
+
+impl PageCursor {
+    /// Appends a condition selecting only rows strictly after this cursor.
+    pub(crate) fn push_condition<'a>(&'a self, qb: &mut QueryBuilder<'a, Sqlite>) {
+        let created_at = sqlite_timestamp(&self.created_at);
+        qb.push(" AND (created_at < ").push_bind(created_at.clone());
+        qb.push(" OR (created_at = ").push_bind(created_at);
+        qb.push(" AND id < ").push_bind(self.id.as_str());
+        qb.push("))");
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+use std::fmt;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db::payment_batch::PaymentBatchStatus;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OperatorActionKind {
+    Retry,
+    ReleasePayments,
+    ForceFail,
+    Approve,
+    Reject,
+}
+
+impl From<String> for OperatorActionKind {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "RETRY" => OperatorActionKind::Retry,
+            "RELEASE_PAYMENTS" => OperatorActionKind::ReleasePayments,
+            "FORCE_FAIL" => OperatorActionKind::ForceFail,
+            "APPROVE" => OperatorActionKind::Approve,
+            "REJECT" => OperatorActionKind::Reject,
+            _ => panic!("Unknown OperatorActionKind: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for OperatorActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OperatorActionKind::Retry => write!(f, "RETRY"),
+            OperatorActionKind::ReleasePayments => write!(f, "RELEASE_PAYMENTS"),
+            OperatorActionKind::ForceFail => write!(f, "FORCE_FAIL"),
+            OperatorActionKind::Approve => write!(f, "APPROVE"),
+            OperatorActionKind::Reject => write!(f, "REJECT"),
+        }
+    }
+}
+
+/// A manual intervention on a payment batch, recorded for audit.
+#[derive(Debug, Clone, FromRow)]
+pub struct OperatorAction {
+    pub id: String,
+    pub payment_batch_id: String,
+    pub action: OperatorActionKind,
+    pub details: Option<String>,
+    pub operator: String,
+    pub reason: String,
+    pub from_status: PaymentBatchStatus,
+    pub to_status: PaymentBatchStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OperatorAction {
+    /// Records an operator action. Should be called in the same transaction as the change it describes.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create(
+        pool: &mut SqliteConnection,
+        payment_batch_id: &str,
+        action: OperatorActionKind,
+        details: Option<&str>,
+        operator: &str,
+        reason: &str,
+        from_status: &PaymentBatchStatus,
+        to_status: &PaymentBatchStatus,
+    ) -> Result<Self, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let action = action.to_string();
+        let from_status = from_status.to_string();
+        let to_status = to_status.to_string();
+
+        sqlx::query_as!(
+            OperatorAction,
+            r#"
+            INSERT INTO operator_actions (id, payment_batch_id, action, details, operator, reason, from_status, to_status)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING
+                id,
+                payment_batch_id,
+                action,
+                details,
+                operator,
+                reason,
+                from_status,
+                to_status,
+                created_at as "created_at: DateTime<Utc>"
+            "#,
+            id,
+            payment_batch_id,
+            action,
+            details,
+            operator,
+            reason,
+            from_status,
+            to_status,
+        )
+        .fetch_one(pool)
+        .await
+    }
+}
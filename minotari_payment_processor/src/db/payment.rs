@@ -1,28 +1,38 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, SqliteConnection};
+use sqlx::{Connection, FromRow, SqliteConnection};
 use std::fmt;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::db::payment_batch::PaymentBatch;
+use crate::db::{
+    PageCursor,
+    event::{Actor, PaymentEvent},
+    payment_batch::PaymentBatch,
+    push_account_names_condition, sqlite_timestamp,
+    webhook::WebhookDelivery,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum PaymentStatus {
+    Scheduled,
     Received,
     Batched,
     Confirmed,
     Failed,
+    Cancelled,
 }
 
 impl From<String> for PaymentStatus {
     fn from(s: String) -> Self {
         match s.as_str() {
+            "SCHEDULED" => PaymentStatus::Scheduled,
             "RECEIVED" => PaymentStatus::Received,
             "BATCHED" => PaymentStatus::Batched,
             "CONFIRMED" => PaymentStatus::Confirmed,
             "FAILED" => PaymentStatus::Failed,
+            "CANCELLED" => PaymentStatus::Cancelled,
             _ => panic!("Unknown PaymentStatus: {}", s),
         }
     }
@@ -31,10 +41,141 @@ impl From<String> for PaymentStatus {
 impl fmt::Display for PaymentStatus {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            PaymentStatus::Scheduled => write!(f, "SCHEDULED"),
             PaymentStatus::Received => write!(f, "RECEIVED"),
             PaymentStatus::Batched => write!(f, "BATCHED"),
             PaymentStatus::Confirmed => write!(f, "CONFIRMED"),
             PaymentStatus::Failed => write!(f, "FAILED"),
+            PaymentStatus::Cancelled => write!(f, "CANCELLED"),
+        }
+    }
+}
+
+/// Why a 'RECEIVED' payment is not being batched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldReason {
+    /// The account's balance does not cover the payment.
+    InsufficientFunds,
+}
+
+impl From<String> for HoldReason {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "insufficient_funds" => HoldReason::InsufficientFunds,
+            _ => panic!("Unknown HoldReason: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for HoldReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HoldReason::InsufficientFunds => write!(f, "insufficient_funds"),
+        }
+    }
+}
+
+/// Why a payment failed, so clients can act on it without parsing `failure_reason`. Codes are
+/// grouped by the pipeline stage that produces them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCode {
+    /// PR does not know the account.
+    AccountNotFound,
+    /// PR refused to build the transaction, e.g. for an invalid recipient address.
+    InvalidTransactionRequest,
+    /// PR could not build the transaction this time, e.g. while the account's funds are locked.
+    UnsignedTxFailed,
+    /// PR could not be reached or failed internally.
+    PaymentReceiverUnavailable,
+    /// The signer refused to sign the transaction.
+    SigningRejected,
+    /// The signer could not be reached, was busy or timed out.
+    SignerUnavailable,
+    /// The transaction's inputs have been spent by another transaction.
+    InputsSpent,
+    /// The base node does not know the transaction's inputs, e.g. while it is behind or on a fork.
+    InputsUnknown,
+    /// The base node found the transaction invalid.
+    InvalidTransaction,
+    /// The transaction's fee is too low for the mempool.
+    FeeTooLow,
+    /// The transaction is time-locked and not valid yet.
+    TimeLocked,
+    /// The base node rejected the transaction for another reason.
+    BroadcastRejected,
+    /// The base node could not be reached.
+    BaseNodeUnavailable,
+    /// The transaction disappeared from the mempool and the base node would not take it back.
+    TransactionDropped,
+    /// An operator rejected the batch instead of approving it.
+    ApprovalRejected,
+    /// An operator failed the batch.
+    OperatorFailed,
+}
+
+impl FailureCode {
+    /// Whether another attempt at the stage may succeed. A stage failing with any other code fails
+    /// the batch at once instead of using up its retries.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            FailureCode::UnsignedTxFailed
+                | FailureCode::PaymentReceiverUnavailable
+                | FailureCode::SignerUnavailable
+                | FailureCode::InputsUnknown
+                | FailureCode::FeeTooLow
+                | FailureCode::TimeLocked
+                | FailureCode::BroadcastRejected
+                | FailureCode::BaseNodeUnavailable
+        )
+    }
+}
+
+impl From<String> for FailureCode {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "account_not_found" => FailureCode::AccountNotFound,
+            "invalid_transaction_request" => FailureCode::InvalidTransactionRequest,
+            "unsigned_tx_failed" => FailureCode::UnsignedTxFailed,
+            "payment_receiver_unavailable" => FailureCode::PaymentReceiverUnavailable,
+            "signing_rejected" => FailureCode::SigningRejected,
+            "signer_unavailable" => FailureCode::SignerUnavailable,
+            "inputs_spent" => FailureCode::InputsSpent,
+            "inputs_unknown" => FailureCode::InputsUnknown,
+            "invalid_transaction" => FailureCode::InvalidTransaction,
+            "fee_too_low" => FailureCode::FeeTooLow,
+            "time_locked" => FailureCode::TimeLocked,
+            "broadcast_rejected" => FailureCode::BroadcastRejected,
+            "base_node_unavailable" => FailureCode::BaseNodeUnavailable,
+            "transaction_dropped" => FailureCode::TransactionDropped,
+            "approval_rejected" => FailureCode::ApprovalRejected,
+            "operator_failed" => FailureCode::OperatorFailed,
+            _ => panic!("Unknown FailureCode: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for FailureCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FailureCode::AccountNotFound => write!(f, "account_not_found"),
+            FailureCode::InvalidTransactionRequest => write!(f, "invalid_transaction_request"),
+            FailureCode::UnsignedTxFailed => write!(f, "unsigned_tx_failed"),
+            FailureCode::PaymentReceiverUnavailable => write!(f, "payment_receiver_unavailable"),
+            FailureCode::SigningRejected => write!(f, "signing_rejected"),
+            FailureCode::SignerUnavailable => write!(f, "signer_unavailable"),
+            FailureCode::InputsSpent => write!(f, "inputs_spent"),
+            FailureCode::InputsUnknown => write!(f, "inputs_unknown"),
+            FailureCode::InvalidTransaction => write!(f, "invalid_transaction"),
+            FailureCode::FeeTooLow => write!(f, "fee_too_low"),
+            FailureCode::TimeLocked => write!(f, "time_locked"),
+            FailureCode::BroadcastRejected => write!(f, "broadcast_rejected"),
+            FailureCode::BaseNodeUnavailable => write!(f, "base_node_unavailable"),
+            FailureCode::TransactionDropped => write!(f, "transaction_dropped"),
+            FailureCode::ApprovalRejected => write!(f, "approval_rejected"),
+            FailureCode::OperatorFailed => write!(f, "operator_failed"),
         }
     }
 }
@@ -44,34 +185,88 @@ pub struct Payment {
     pub id: String,
     pub client_id: String,
     pub account_name: String,
+    #[sqlx(try_from = "String")]
     pub status: PaymentStatus,
     pub payment_batch_id: Option<String>,
     pub recipient_address: String,
     pub amount: i64,
     pub payment_id: Option<String>,
     pub failure_reason: Option<String>,
+    /// Why a 'FAILED' payment failed, see [`FailureCode`].
+    pub failure_code: Option<String>,
+    pub callback_url: Option<String>,
+    pub request_fingerprint: Option<String>,
+    /// Why a 'RECEIVED' payment is held back from batching, see [`HoldReason`].
+    pub hold_reason: Option<String>,
+    /// The earliest time the payment may be batched. Until then it stays 'SCHEDULED'.
+    pub execute_after: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Optional filters for listing payments. All set filters must match.
+#[derive(Debug, Default)]
+pub struct PaymentFilter<'a> {
+    pub account_name: Option<&'a str>,
+    /// Restricts the listing to these accounts, e.g. those an API key may access.
+    pub account_names: Option<&'a [String]>,
+    pub status: Option<PaymentStatus>,
+    pub recipient_address: Option<&'a str>,
+    pub client_id_prefix: Option<&'a str>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+/// The fields of a payment supplied when it is created.
+#[derive(Debug)]
+pub struct NewPayment<'a> {
+    pub client_id: &'a str,
+    pub account_name: &'a str,
+    pub recipient_address: &'a str,
+    pub amount: i64,
+    pub payment_id: Option<&'a str>,
+    pub callback_url: Option<&'a str>,
+    pub request_fingerprint: Option<&'a str>,
+    pub execute_after: Option<DateTime<Utc>>,
+}
+
+/// The fields of a 'SCHEDULED' payment that can be changed before it becomes due. Unset fields
+/// are left unchanged.
+#[derive(Debug, Default)]
+pub struct ScheduledPaymentUpdate<'a> {
+    pub recipient_address: Option<&'a str>,
+    pub amount: Option<i64>,
+    pub payment_id: Option<&'a str>,
+    pub callback_url: Option<&'a str>,
+    pub execute_after: Option<DateTime<Utc>>,
+}
+
 impl Payment {
-    /// Creates a new payment record in the database.
+    /// Creates a new payment record in the database. Payments to be executed in the future are
+    /// created 'SCHEDULED', all others 'RECEIVED'.
     pub async fn create(
         pool: &mut SqliteConnection,
-        client_id: &str,
-        account_name: &str,
-        recipient_address: &str,
-        amount: i64,
-        payment_id: Option<String>,
+        new_payment: &NewPayment<'_>,
+        actor: &Actor,
     ) -> Result<Self, sqlx::Error> {
+        let mut tx = pool.begin().await?;
         let id = Uuid::new_v4().to_string();
-        let status = PaymentStatus::Received.to_string();
+        let status = if new_payment.execute_after.is_some_and(|at| at > Utc::now()) {
+            PaymentStatus::Scheduled
+        } else {
+            PaymentStatus::Received
+        }
+        .to_string();
+        let execute_after = new_payment.execute_after.as_ref().map(sqlite_timestamp);
 
-        sqlx::query_as!(
+        let payment = sqlx::query_as!(
             Payment,
             r#"
-            INSERT INTO payments (id, client_id, account_name, status, recipient_address, amount, payment_id)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO payments (
+                id, client_id, account_name, status, recipient_address, amount, payment_id, callback_url,
+                request_fingerprint, execute_after
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             RETURNING
                 id,
                 client_id,
@@ -82,19 +277,31 @@ impl Payment {
                 amount,
                 payment_id,
                 failure_reason,
+                failure_code,
+                callback_url,
+                request_fingerprint,
+                hold_reason,
+                execute_after as "execute_after: DateTime<Utc>",
                 created_at as "created_at: DateTime<Utc>",
                 updated_at as "updated_at: DateTime<Utc>"
             "#,
             id,
-            client_id,
-            account_name,
+            new_payment.client_id,
+            new_payment.account_name,
             status,
-            recipient_address,
-            amount,
-            payment_id,
+            new_payment.recipient_address,
+            new_payment.amount,
+            new_payment.payment_id,
+            new_payment.callback_url,
+            new_payment.request_fingerprint,
+            execute_after,
         )
-        .fetch_one(pool)
-        .await
+        .fetch_one(&mut *tx)
+        .await?;
+        PaymentEvent::record(&mut tx, &payment.id, None, &payment.status, actor, None).await?;
+
+        tx.commit().await?;
+        Ok(payment)
     }
 
     /// Retrieves a payment by its ID.
@@ -112,6 +319,11 @@ impl Payment {
                 amount,
                 payment_id,
                 failure_reason,
+                failure_code,
+                callback_url,
+                request_fingerprint,
+                hold_reason,
+                execute_after as "execute_after: DateTime<Utc>",
                 created_at as "created_at: DateTime<Utc>",
                 updated_at as "updated_at: DateTime<Utc>"
             FROM payments
@@ -142,6 +354,11 @@ impl Payment {
                 amount,
                 payment_id,
                 failure_reason,
+                failure_code,
+                callback_url,
+                request_fingerprint,
+                hold_reason,
+                execute_after as "execute_after: DateTime<Utc>",
                 created_at as "created_at: DateTime<Utc>",
                 updated_at as "updated_at: DateTime<Utc>"
             FROM payments
@@ -154,8 +371,31 @@ impl Payment {
         .await
     }
 
-    /// Finds payments with status 'RECEIVED' for batching.
-    pub async fn find_receivable_payments(pool: &mut SqliteConnection, limit: i64) -> Result<Vec<Self>, sqlx::Error> {
+    /// Lists the accounts that have payments with status 'RECEIVED', the account whose oldest such
+    /// payment has waited longest first.
+    pub async fn find_accounts_with_receivable_payments(
+        pool: &mut SqliteConnection,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT account_name
+            FROM payments
+            WHERE status = 'RECEIVED'
+            GROUP BY account_name
+            ORDER BY MIN(COALESCE(execute_after, created_at)), account_name
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Finds an account's due payments with status 'RECEIVED' for batching, in the order they
+    /// became due.
+    pub async fn find_receivable_payments(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
         sqlx::query_as!(
             Payment,
             r#"
@@ -169,27 +409,244 @@ impl Payment {
                 amount,
                 payment_id,
                 failure_reason,
+                failure_code,
+                callback_url,
+                request_fingerprint,
+                hold_reason,
+                execute_after as "execute_after: DateTime<Utc>",
                 created_at as "created_at: DateTime<Utc>",
                 updated_at as "updated_at: DateTime<Utc>"
             FROM payments
-            WHERE status = 'RECEIVED'
+            WHERE account_name = ? AND status = 'RECEIVED'
+                AND (execute_after IS NULL OR execute_after <= CURRENT_TIMESTAMP)
+            ORDER BY COALESCE(execute_after, created_at), created_at, id
             LIMIT ?
             "#,
+            account_name,
             limit
         )
         .fetch_all(pool)
         .await
     }
 
-    /// Generic function to update payment status and optional fields.
+    /// Moves 'SCHEDULED' payments whose execute_after has passed to 'RECEIVED'.
+    /// Returns the IDs of the released payments.
+    pub async fn release_due_scheduled_payments(
+        pool: &mut SqliteConnection,
+        actor: &Actor,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let payment_ids = sqlx::query_scalar!(
+            r#"
+            SELECT id
+            FROM payments
+            WHERE status = 'SCHEDULED' AND execute_after <= CURRENT_TIMESTAMP
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        if payment_ids.is_empty() {
+            return Ok(payment_ids);
+        }
+        PaymentEvent::record_for_payments(&mut tx, &payment_ids, &PaymentStatus::Received, actor, None).await?;
+
+        let json = serde_json::to_string(&payment_ids).unwrap();
+        let status_received = PaymentStatus::Received.to_string();
+        sqlx::query!(
+            r#"
+            UPDATE payments
+            SET status = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id IN (SELECT value FROM json_each(?)) AND status = 'SCHEDULED'
+            "#,
+            status_received,
+            json,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(payment_ids)
+    }
+
+    /// Edits a 'SCHEDULED' payment that is not yet due. The request fingerprint is cleared, so
+    /// replays of the original request are compared field by field against the edited payment.
+    /// Returns `false` if the payment is no longer scheduled or has become due.
+    pub async fn update_scheduled(
+        pool: &mut SqliteConnection,
+        payment_id: &str,
+        update: &ScheduledPaymentUpdate<'_>,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let execute_after = update.execute_after.as_ref().map(sqlite_timestamp);
+        let result = sqlx::query!(
+            r#"
+            UPDATE payments
+            SET recipient_address = COALESCE(?, recipient_address),
+                amount = COALESCE(?, amount),
+                payment_id = COALESCE(?, payment_id),
+                callback_url = COALESCE(?, callback_url),
+                execute_after = COALESCE(?, execute_after),
+                request_fingerprint = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = 'SCHEDULED' AND execute_after > CURRENT_TIMESTAMP
+            "#,
+            update.recipient_address,
+            update.amount,
+            update.payment_id,
+            update.callback_url,
+            execute_after,
+            payment_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        PaymentEvent::record(
+            &mut tx,
+            payment_id,
+            Some(&PaymentStatus::Scheduled),
+            &PaymentStatus::Scheduled,
+            actor,
+            detail,
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Returns the number and total amount of an account's payments in the given status.
+    pub async fn total_by_status(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+        status: PaymentStatus,
+    ) -> Result<(i64, i64), sqlx::Error> {
+        let status = status.to_string();
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) as "count!: i64",
+                COALESCE(SUM(amount), 0) as "amount!: i64"
+            FROM payments
+            WHERE account_name = ? AND status = ?
+            "#,
+            account_name,
+            status
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok((row.count, row.amount))
+    }
+
+    /// Sets or clears the hold on all of an account's 'RECEIVED' payments.
+    /// Returns the number of payments whose hold changed.
+    pub async fn set_hold_reason(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+        hold_reason: Option<HoldReason>,
+    ) -> Result<u64, sqlx::Error> {
+        let hold_reason = hold_reason.map(|reason| reason.to_string());
+        let result = sqlx::query!(
+            r#"
+            UPDATE payments
+            SET hold_reason = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE account_name = ? AND status = 'RECEIVED' AND hold_reason IS NOT ?
+            "#,
+            hold_reason,
+            account_name,
+            hold_reason,
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Lists payments matching the filter, newest first, starting after the given cursor.
+    /// Paging by `(created_at, id)` keeps pages stable while new payments arrive.
+    pub async fn list(
+        pool: &mut SqliteConnection,
+        filter: &PaymentFilter<'_>,
+        cursor: Option<&PageCursor>,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let mut qb = sqlx::QueryBuilder::new(
+            r#"
+            SELECT
+                id,
+                client_id,
+                account_name,
+                status,
+                payment_batch_id,
+                recipient_address,
+                amount,
+                payment_id,
+                failure_reason,
+                failure_code,
+                callback_url,
+                request_fingerprint,
+                hold_reason,
+                execute_after,
+                created_at,
+                updated_at
+            FROM payments
+            WHERE 1 = 1"#,
+        );
+
+        if let Some(account_name) = filter.account_name {
+            qb.push(" AND account_name = ").push_bind(account_name);
+        }
+        if let Some(account_names) = filter.account_names {
+            push_account_names_condition(&mut qb, account_names);
+        }
+        if let Some(status) = &filter.status {
+            qb.push(" AND status = ").push_bind(status.to_string());
+        }
+        if let Some(recipient_address) = filter.recipient_address {
+            qb.push(" AND recipient_address = ").push_bind(recipient_address);
+        }
+        if let Some(prefix) = filter.client_id_prefix {
+            qb.push(" AND client_id GLOB ")
+                .push_bind(format!("{}*", glob_escape(prefix)));
+        }
+        if let Some(created_after) = &filter.created_after {
+            qb.push(" AND created_at >= ")
+                .push_bind(sqlite_timestamp(created_after));
+        }
+        if let Some(created_before) = &filter.created_before {
+            qb.push(" AND created_at < ")
+                .push_bind(sqlite_timestamp(created_before));
+        }
+        if let Some(cursor) = cursor {
+            cursor.push_condition(&mut qb);
+        }
+
+        qb.push(" ORDER BY created_at DESC, id DESC LIMIT ").push_bind(limit);
+        qb.build_query_as::<Payment>().fetch_all(pool).await
+    }
+
+    /// Generic function to update payment status and optional fields. Queues webhook deliveries
+    /// when the payments reach a final status.
     async fn update_payment_status(
         pool: &mut SqliteConnection,
         payment_ids: &[String],
         status: PaymentStatus,
         payment_batch_id: Option<&str>,
         failure_reason: Option<&str>,
+        actor: &Actor,
     ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        PaymentEvent::record_for_payments(&mut tx, payment_ids, &status, actor, failure_reason).await?;
+
         let json = serde_json::to_string(payment_ids).unwrap();
+        let is_final = matches!(
+            status,
+            PaymentStatus::Confirmed | PaymentStatus::Failed | PaymentStatus::Cancelled
+        );
         let status = status.to_string();
         sqlx::query!(
             r#"
@@ -202,8 +659,13 @@ impl Payment {
             failure_reason,
             json,
         )
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
+        if is_final {
+            WebhookDelivery::enqueue_for_payments(&mut tx, payment_ids).await?;
+        }
+
+        tx.commit().await?;
         Ok(())
     }
 
@@ -212,16 +674,21 @@ impl Payment {
         pool: &mut SqliteConnection,
         payment_ids: &[String],
         batch_id: &str,
+        actor: &Actor,
     ) -> Result<(), sqlx::Error> {
-        Self::update_payment_status(pool, payment_ids, PaymentStatus::Batched, Some(batch_id), None).await
+        Self::update_payment_status(pool, payment_ids, PaymentStatus::Batched, Some(batch_id), None, actor).await
     }
 
-    /// Updates the status of a list of payments to 'CONFIRMED'.
+    /// Updates the status of a list of payments of batch `batch_id` to 'CONFIRMED'. Confirmed payments stay
+    /// attached to their batch: their webhook payload and API response report the block the batch was mined
+    /// in, and the reorg watcher finds them through it if that block is reorged out.
     pub async fn update_payments_to_confirmed(
         pool: &mut SqliteConnection,
         payment_ids: &[String],
+        batch_id: &str,
+        actor: &Actor,
     ) -> Result<(), sqlx::Error> {
-        Self::update_payment_status(pool, payment_ids, PaymentStatus::Confirmed, None, None).await
+        Self::update_payment_status(pool, payment_ids, PaymentStatus::Confirmed, Some(batch_id), None, actor).await
     }
 
     /// Updates the status of a list of payments to 'FAILED' with a reason.
@@ -229,32 +696,255 @@ impl Payment {
         pool: &mut SqliteConnection,
         payment_ids: &[String],
         reason: &str,
+        actor: &Actor,
     ) -> Result<(), sqlx::Error> {
-        Self::update_payment_status(pool, payment_ids, PaymentStatus::Failed, None, Some(reason)).await
+        Self::update_payment_status(pool, payment_ids, PaymentStatus::Failed, None, Some(reason), actor).await
+    }
+
+    /// Cancels a payment that is still in status `from`, detaching it from its batch.
+    /// Returns `false` if the payment moved on in the meantime.
+    pub async fn cancel(
+        pool: &mut SqliteConnection,
+        payment_id: &str,
+        from: PaymentStatus,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let status_cancelled = PaymentStatus::Cancelled.to_string();
+        let from_status = from.to_string();
+        let result = sqlx::query!(
+            r#"
+            UPDATE payments
+            SET status = ?, payment_batch_id = NULL, hold_reason = NULL, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = ?
+            "#,
+            status_cancelled,
+            payment_id,
+            from_status,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        PaymentEvent::record(
+            &mut tx,
+            payment_id,
+            Some(&from),
+            &PaymentStatus::Cancelled,
+            actor,
+            detail,
+        )
+        .await?;
+        WebhookDelivery::enqueue_for_payments(&mut tx, &[payment_id.to_string()]).await?;
+
+        tx.commit().await?;
+        Ok(true)
     }
 
-    /// Updates the status of all payments in a batch to 'FAILED' with a reason.
+    /// Updates the status of all payments in a batch to 'FAILED' with a code and reason.
     pub async fn fail_payments_in_batch(
         pool: &mut SqliteConnection,
         batch_id: &str,
+        code: FailureCode,
         reason: &str,
+        actor: &Actor,
     ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        PaymentEvent::record_for_batch(&mut tx, batch_id, None, &PaymentStatus::Failed, actor, Some(reason)).await?;
+
         let status_failed = PaymentStatus::Failed.to_string();
-        sqlx::query!(
+        let code = code.to_string();
+        let payment_ids = sqlx::query_scalar!(
             r#"
             UPDATE payments
-            SET status = ?, failure_reason = ?, updated_at = CURRENT_TIMESTAMP
+            SET status = ?, failure_reason = ?, failure_code = ?, updated_at = CURRENT_TIMESTAMP
             WHERE payment_batch_id = ?
+            RETURNING id
             "#,
             status_failed,
             reason,
+            code,
             batch_id,
         )
-        .execute(pool)
+        .fetch_all(&mut *tx)
         .await?;
+        WebhookDelivery::enqueue_for_payments(&mut tx, &payment_ids).await?;
+
+        tx.commit().await?;
         Ok(())
     }
 
+    /// Moves the confirmed payments of a batch back to 'BATCHED' after its transaction left the best chain,
+    /// and notifies their webhooks with a `payment.reorged` event.
+    pub async fn unconfirm_payments_in_batch(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        detail: &str,
+        actor: &Actor,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let payment_ids = sqlx::query_scalar!(
+            "SELECT id FROM payments WHERE payment_batch_id = ? AND status = 'CONFIRMED'",
+            batch_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        PaymentEvent::record_for_batch(
+            &mut tx,
+            batch_id,
+            Some(&PaymentStatus::Confirmed),
+            &PaymentStatus::Batched,
+            actor,
+            Some(detail),
+        )
+        .await?;
+
+        let status_batched = PaymentStatus::Batched.to_string();
+        sqlx::query!(
+            r#"
+            UPDATE payments
+            SET status = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE payment_batch_id = ? AND status = 'CONFIRMED'
+            "#,
+            status_batched,
+            batch_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        WebhookDelivery::enqueue_reorged_for_payments(&mut tx, &payment_ids).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Moves the failed payments of a batch back to 'BATCHED' so the batch can be retried.
+    pub async fn requeue_failed_payments_in_batch(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        PaymentEvent::record_for_batch(
+            &mut tx,
+            batch_id,
+            Some(&PaymentStatus::Failed),
+            &PaymentStatus::Batched,
+            actor,
+            detail,
+        )
+        .await?;
+
+        let status_batched = PaymentStatus::Batched.to_string();
+        sqlx::query!(
+            r#"
+            UPDATE payments
+            SET status = ?, failure_reason = NULL, failure_code = NULL, updated_at = CURRENT_TIMESTAMP
+            WHERE payment_batch_id = ? AND status = 'FAILED'
+            "#,
+            status_batched,
+            batch_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Detaches all payments from a batch and returns them to 'RECEIVED' so they are batched again.
+    pub async fn release_payments_in_batch(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        PaymentEvent::record_for_batch(&mut tx, batch_id, None, &PaymentStatus::Received, actor, detail).await?;
+
+        let status_received = PaymentStatus::Received.to_string();
+        sqlx::query!(
+            r#"
+            UPDATE payments
+            SET
+                status = ?,
+                payment_batch_id = NULL,
+                failure_reason = NULL,
+                failure_code = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE payment_batch_id = ?
+            "#,
+            status_received,
+            batch_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Lists the distinct recipient addresses of payments that are not yet 'CONFIRMED', 'FAILED' or 'CANCELLED'.
+    pub async fn find_distinct_open_recipient_addresses(
+        pool: &mut SqliteConnection,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT recipient_address
+            FROM payments
+            WHERE status NOT IN ('CONFIRMED', 'FAILED', 'CANCELLED')
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Rewrites the recipient address `from` to `to` on every payment that is not yet 'CONFIRMED', 'FAILED'
+    /// or 'CANCELLED', recording an event for each, and returns how many payments changed. Finished payments
+    /// keep the address they were made to. Both must be encodings of the same address.
+    pub async fn replace_recipient_address(
+        pool: &mut SqliteConnection,
+        from: &str,
+        to: &str,
+        actor: &Actor,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let actor = actor.to_string();
+        let detail = format!("Recipient address {} normalized to {}", from, to);
+        // The status does not change, so the event records it as both the previous and the new one.
+        sqlx::query!(
+            r#"
+            INSERT INTO payment_events (payment_id, from_status, to_status, actor, detail)
+            SELECT id, status, status, ?, ?
+            FROM payments
+            WHERE recipient_address = ? AND status NOT IN ('CONFIRMED', 'FAILED', 'CANCELLED')
+            "#,
+            actor,
+            detail,
+            from,
+        )
+        .execute(&mut *tx)
+        .await?;
+        let result = sqlx::query!(
+            r#"
+            UPDATE payments
+            SET recipient_address = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE recipient_address = ? AND status NOT IN ('CONFIRMED', 'FAILED', 'CANCELLED')
+            "#,
+            to,
+            from,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
     /// Finds payments associated with a specific payment batch ID.
     pub async fn find_by_batch_id(pool: &mut SqliteConnection, batch_id: &str) -> Result<Vec<Self>, sqlx::Error> {
         sqlx::query_as!(
@@ -270,6 +960,11 @@ impl Payment {
                 amount,
                 payment_id,
                 failure_reason,
+                failure_code,
+                callback_url,
+                request_fingerprint,
+                hold_reason,
+                execute_after as "execute_after: DateTime<Utc>",
                 created_at as "created_at: DateTime<Utc>",
                 updated_at as "updated_at: DateTime<Utc>"
             FROM payments
@@ -299,6 +994,11 @@ impl Payment {
                 p.amount,
                 p.payment_id,
                 p.failure_reason,
+                p.failure_code,
+                p.callback_url,
+                p.request_fingerprint,
+                p.hold_reason,
+                p.execute_after as "execute_after: DateTime<Utc>",
                 p.created_at as "created_at: DateTime<Utc>",
                 p.updated_at as "updated_at: DateTime<Utc>",
                 pb.id as batch_id,
@@ -312,6 +1012,10 @@ impl Payment {
                 pb.mined_height as batch_mined_height,
                 pb.mined_header_hash as batch_mined_header_hash,
                 pb.mined_timestamp as batch_mined_timestamp,
+                pb.lease_expires_at as "batch_lease_expires_at: DateTime<Utc>",
+                pb.approvals_required as batch_approvals_required,
+                pb.next_retry_at as "batch_next_retry_at: DateTime<Utc>",
+                pb.parent_batch_id as batch_parent_batch_id,
                 pb.created_at as "batch_created_at: DateTime<Utc>",
                 pb.updated_at as "batch_updated_at: DateTime<Utc>"
             FROM payments p
@@ -334,6 +1038,11 @@ impl Payment {
                     amount: row.amount,
                     payment_id: row.payment_id,
                     failure_reason: row.failure_reason,
+                    failure_code: row.failure_code,
+                    callback_url: row.callback_url,
+                    request_fingerprint: row.request_fingerprint,
+                    hold_reason: row.hold_reason,
+                    execute_after: row.execute_after,
                     created_at: row.created_at,
                     updated_at: row.updated_at,
                 };
@@ -350,6 +1059,10 @@ impl Payment {
                     mined_height: row.batch_mined_height,
                     mined_header_hash: row.batch_mined_header_hash,
                     mined_timestamp: row.batch_mined_timestamp,
+                    lease_expires_at: row.batch_lease_expires_at,
+                    approvals_required: row.batch_approvals_required,
+                    next_retry_at: row.batch_next_retry_at,
+                    parent_batch_id: row.batch_parent_batch_id,
                     created_at: row.batch_created_at.unwrap(),
                     updated_at: row.batch_updated_at.unwrap(),
                 });
@@ -371,6 +1084,11 @@ struct PaymentWithBatch {
     amount: i64,
     payment_id: Option<String>,
     failure_reason: Option<String>,
+    failure_code: Option<String>,
+    callback_url: Option<String>,
+    request_fingerprint: Option<String>,
+    hold_reason: Option<String>,
+    execute_after: Option<DateTime<Utc>>,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
     batch_id: Option<String>,
@@ -384,6 +1102,26 @@ struct PaymentWithBatch {
     batch_mined_height: Option<i64>,
     batch_mined_header_hash: Option<String>,
     batch_mined_timestamp: Option<i64>,
+    batch_lease_expires_at: Option<DateTime<Utc>>,
+    batch_approvals_required: Option<i64>,
+    batch_next_retry_at: Option<DateTime<Utc>>,
+    batch_parent_batch_id: Option<String>,
     batch_created_at: Option<DateTime<Utc>>,
     batch_updated_at: Option<DateTime<Utc>>,
 }
+
+/// Escapes GLOB metacharacters so the value only matches literally.
+fn glob_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '*' | '?' | '[' => {
+                escaped.push('[');
+                escaped.push(c);
+                escaped.push(']');
+            },
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -5,14 +5,23 @@ use std::fmt;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::db::payment::{Payment, PaymentStatus};
+use crate::db::{
+    PageCursor,
+    event::{Actor, BatchEvent, PaymentEvent},
+    payment::{FailureCode, Payment, PaymentStatus},
+    push_account_names_condition, sqlite_timestamp,
+};
 
+/// Failed attempts at a pipeline stage before the batch is failed. Each stage has its own budget.
 const MAX_RETRIES: i64 = 10;
+/// How long a worker may hold a batch in 'SIGNING_IN_PROGRESS' or 'BROADCASTING' before it is reclaimed.
+pub const LEASE_DURATION_SECS: u64 = 5 * 60;
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum PaymentBatchStatus {
     PendingBatching,
+    AwaitingApproval,
     AwaitingSignature,
     SigningInProgress,
     AwaitingBroadcast,
@@ -20,12 +29,24 @@ pub enum PaymentBatchStatus {
     AwaitingConfirmation,
     Confirmed,
     Failed,
+    /// The signed transaction does not match the batch's payments. Held for an operator.
+    VerificationFailed,
+    /// The mined transaction was reorged out of the chain and is no longer known to the base node.
+    Reorged,
+    /// The transaction disappeared from the mempool and the base node would not take it back.
+    Dropped,
+    /// PR rejected the batch in a way a single payment could cause. Its payments moved to two smaller
+    /// batches that name it as their parent.
+    Split,
+    /// Every payment of the batch was cancelled before its transaction was signed.
+    Cancelled,
 }
 
 impl From<String> for PaymentBatchStatus {
     fn from(s: String) -> Self {
         match s.as_str() {
             "PENDING_BATCHING" => PaymentBatchStatus::PendingBatching,
+            "AWAITING_APPROVAL" => PaymentBatchStatus::AwaitingApproval,
             "AWAITING_SIGNATURE" => PaymentBatchStatus::AwaitingSignature,
             "SIGNING_IN_PROGRESS" => PaymentBatchStatus::SigningInProgress,
             "AWAITING_BROADCAST" => PaymentBatchStatus::AwaitingBroadcast,
@@ -33,6 +54,11 @@ impl From<String> for PaymentBatchStatus {
             "AWAITING_CONFIRMATION" => PaymentBatchStatus::AwaitingConfirmation,
             "CONFIRMED" => PaymentBatchStatus::Confirmed,
             "FAILED" => PaymentBatchStatus::Failed,
+            "VERIFICATION_FAILED" => PaymentBatchStatus::VerificationFailed,
+            "REORGED" => PaymentBatchStatus::Reorged,
+            "DROPPED" => PaymentBatchStatus::Dropped,
+            "SPLIT" => PaymentBatchStatus::Split,
+            "CANCELLED" => PaymentBatchStatus::Cancelled,
             _ => panic!("Unknown PaymentBatchStatus: {}", s),
         }
     }
@@ -42,6 +68,7 @@ impl fmt::Display for PaymentBatchStatus {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             PaymentBatchStatus::PendingBatching => write!(f, "PENDING_BATCHING"),
+            PaymentBatchStatus::AwaitingApproval => write!(f, "AWAITING_APPROVAL"),
             PaymentBatchStatus::AwaitingSignature => write!(f, "AWAITING_SIGNATURE"),
             PaymentBatchStatus::SigningInProgress => write!(f, "SIGNING_IN_PROGRESS"),
             PaymentBatchStatus::AwaitingBroadcast => write!(f, "AWAITING_BROADCAST"),
@@ -49,14 +76,29 @@ impl fmt::Display for PaymentBatchStatus {
             PaymentBatchStatus::AwaitingConfirmation => write!(f, "AWAITING_CONFIRMATION"),
             PaymentBatchStatus::Confirmed => write!(f, "CONFIRMED"),
             PaymentBatchStatus::Failed => write!(f, "FAILED"),
+            PaymentBatchStatus::VerificationFailed => write!(f, "VERIFICATION_FAILED"),
+            PaymentBatchStatus::Reorged => write!(f, "REORGED"),
+            PaymentBatchStatus::Dropped => write!(f, "DROPPED"),
+            PaymentBatchStatus::Split => write!(f, "SPLIT"),
+            PaymentBatchStatus::Cancelled => write!(f, "CANCELLED"),
         }
     }
 }
 
+/// A worker's claim on a batch it moved to 'SIGNING_IN_PROGRESS' or 'BROADCASTING'. Updates made under a lease
+/// only apply while the batch is still in `status` with the same expiry, so a worker whose lease was reclaimed
+/// cannot overwrite the work of the worker that claimed the batch next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    pub status: PaymentBatchStatus,
+    pub expires_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, FromRow)]
 pub struct PaymentBatch {
     pub id: String,
     pub account_name: String,
+    #[sqlx(try_from = "String")]
     pub status: PaymentBatchStatus,
     pub pr_idempotency_key: String,
     pub unsigned_tx_json: Option<String>,
@@ -66,10 +108,104 @@ pub struct PaymentBatch {
     pub mined_height: Option<i64>,
     pub mined_header_hash: Option<String>,
     pub mined_timestamp: Option<i64>,
+    pub lease_expires_at: Option<DateTime<Utc>>,
+    /// How many operators must approve the batch before it is signed. Set when the account's
+    /// approval policy applied to the batch.
+    pub approvals_required: Option<i64>,
+    /// When a failed stage is attempted again. Workers skip the batch until then.
+    pub next_retry_at: Option<DateTime<Utc>>,
+    /// The batch this one was split from to isolate a failing payment.
+    pub parent_batch_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// The pipeline stage an operator restarts a failed batch from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RetryStage {
+    /// Request a new unsigned transaction from PR under a fresh idempotency key.
+    CreateUnsignedTx,
+    /// Sign the stored unsigned transaction again.
+    Sign,
+    /// Submit the stored signed transaction again.
+    Broadcast,
+}
+
+impl RetryStage {
+    /// The stage a batch in the given status is waiting for or working on, if it is retried.
+    pub fn for_status(status: &PaymentBatchStatus) -> Option<Self> {
+        match status {
+            PaymentBatchStatus::PendingBatching => Some(RetryStage::CreateUnsignedTx),
+            PaymentBatchStatus::AwaitingSignature | PaymentBatchStatus::SigningInProgress => Some(RetryStage::Sign),
+            PaymentBatchStatus::AwaitingBroadcast | PaymentBatchStatus::Broadcasting => Some(RetryStage::Broadcast),
+            _ => None,
+        }
+    }
+
+    /// The backoff used when `*_RETRY_BASE_SECS` and `*_RETRY_MAX_SECS` are not set for the stage. PR
+    /// outages get the most room, since nothing has been built yet.
+    pub fn default_backoff(&self) -> RetryBackoff {
+        match self {
+            RetryStage::CreateUnsignedTx => RetryBackoff {
+                base_secs: 30,
+                max_secs: 60 * 60,
+            },
+            RetryStage::Sign | RetryStage::Broadcast => RetryBackoff {
+                base_secs: 15,
+                max_secs: 15 * 60,
+            },
+        }
+    }
+
+    /// The status a batch is put in to restart it from this stage.
+    pub fn status(&self) -> PaymentBatchStatus {
+        match self {
+            RetryStage::CreateUnsignedTx => PaymentBatchStatus::PendingBatching,
+            RetryStage::Sign => PaymentBatchStatus::AwaitingSignature,
+            RetryStage::Broadcast => PaymentBatchStatus::AwaitingBroadcast,
+        }
+    }
+}
+
+impl fmt::Display for RetryStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RetryStage::CreateUnsignedTx => write!(f, "CREATE_UNSIGNED_TX"),
+            RetryStage::Sign => write!(f, "SIGN"),
+            RetryStage::Broadcast => write!(f, "BROADCAST"),
+        }
+    }
+}
+
+/// How long a batch waits before a failed stage is attempted again: `base_secs`, doubling with every
+/// retry of the stage up to `max_secs`, plus up to a quarter of that as jitter so batches that failed
+/// together are not retried together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryBackoff {
+    pub base_secs: u64,
+    pub max_secs: u64,
+}
+
+impl RetryBackoff {
+    /// The delay before the given retry of a stage, counting from 1.
+    pub fn delay_secs(&self, retries: i64) -> u64 {
+        let exponent = (retries - 1).clamp(0, 20) as u32;
+        let delay = self.base_secs.saturating_mul(1 << exponent).min(self.max_secs);
+        // A random UUID is the only randomness at hand, and plenty for spreading retries.
+        let jitter = (Uuid::new_v4().as_u128() % (delay as u128 / 4 + 1)) as u64;
+        delay + jitter
+    }
+}
+
+/// The block a batch's transaction was mined in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinedBlock {
+    pub height: u64,
+    pub header_hash: Vec<u8>,
+    pub timestamp: u64,
+}
+
 #[derive(Debug, Default)]
 pub struct PaymentBatchUpdate<'a> {
     pub status: Option<PaymentBatchStatus>,
@@ -79,6 +215,17 @@ pub struct PaymentBatchUpdate<'a> {
     pub mined_height: Option<i64>,
     pub mined_header_hash: Option<&'a str>,
     pub mined_timestamp: Option<i64>,
+    /// Delays the next attempt by this many seconds. Without it, a status change makes the batch due at once.
+    pub next_retry_in_secs: Option<u64>,
+}
+
+/// Optional filters for listing payment batches. All set filters must match.
+#[derive(Debug, Default)]
+pub struct PaymentBatchFilter<'a> {
+    pub account_name: Option<&'a str>,
+    /// Restricts the listing to these accounts, e.g. those an API key may access.
+    pub account_names: Option<&'a [String]>,
+    pub status: Option<PaymentBatchStatus>,
 }
 
 impl PaymentBatch {
@@ -99,6 +246,10 @@ impl PaymentBatch {
                 mined_height,
                 mined_header_hash,
                 mined_timestamp,
+                lease_expires_at as "lease_expires_at: DateTime<Utc>",
+                approvals_required,
+                next_retry_at as "next_retry_at: DateTime<Utc>",
+                parent_batch_id,
                 created_at as "created_at: DateTime<Utc>",
                 updated_at as "updated_at: DateTime<Utc>"
             FROM payment_batches
@@ -110,22 +261,97 @@ impl PaymentBatch {
         .await
     }
 
+    /// Lists payment batches matching the filter, newest first, starting after the given cursor.
+    pub async fn list(
+        pool: &mut SqliteConnection,
+        filter: &PaymentBatchFilter<'_>,
+        cursor: Option<&PageCursor>,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let mut qb = sqlx::QueryBuilder::new(
+            r#"
+            SELECT
+                id,
+                account_name,
+                status,
+                pr_idempotency_key,
+                unsigned_tx_json,
+                signed_tx_json,
+                error_message,
+                retry_count,
+                mined_height,
+                mined_header_hash,
+                mined_timestamp,
+                lease_expires_at,
+                approvals_required,
+                next_retry_at,
+                parent_batch_id,
+                created_at,
+                updated_at
+            FROM payment_batches
+            WHERE 1 = 1"#,
+        );
+
+        if let Some(account_name) = filter.account_name {
+            qb.push(" AND account_name = ").push_bind(account_name);
+        }
+        if let Some(account_names) = filter.account_names {
+            push_account_names_condition(&mut qb, account_names);
+        }
+        if let Some(status) = &filter.status {
+            qb.push(" AND status = ").push_bind(status.to_string());
+        }
+        if let Some(cursor) = cursor {
+            cursor.push_condition(&mut qb);
+        }
+
+        qb.push(" ORDER BY created_at DESC, id DESC LIMIT ").push_bind(limit);
+        qb.build_query_as::<PaymentBatch>().fetch_all(pool).await
+    }
+
     /// Creates a new payment batch and updates the associated payments.
     pub async fn create_with_payments(
         pool: &mut SqliteConnection,
         account_name: &str,
         pr_idempotency_key: &str,
         payment_ids: &[String],
+        actor: &Actor,
     ) -> Result<Self, sqlx::Error> {
         let mut tx = pool.begin().await?;
+        let batch = Self::insert_with_payments(
+            &mut tx,
+            account_name,
+            pr_idempotency_key,
+            None,
+            payment_ids,
+            actor,
+            None,
+        )
+        .await?;
+        PaymentEvent::record_for_payments(&mut tx, payment_ids, &PaymentStatus::Batched, actor, None).await?;
+
+        tx.commit().await?;
+        Ok(batch)
+    }
+
+    /// Inserts a 'PENDING_BATCHING' batch and moves the payments into it as 'BATCHED'.
+    async fn insert_with_payments(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+        pr_idempotency_key: &str,
+        parent_batch_id: Option<&str>,
+        payment_ids: &[String],
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<Self, sqlx::Error> {
         let batch_id = Uuid::new_v4().to_string();
         let status = PaymentBatchStatus::PendingBatching.to_string();
 
         let batch = sqlx::query_as!(
             PaymentBatch,
             r#"
-            INSERT INTO payment_batches (id, account_name, pr_idempotency_key, status)
-            VALUES (?, ?, ?, ?)
+            INSERT INTO payment_batches (id, account_name, pr_idempotency_key, status, parent_batch_id)
+            VALUES (?, ?, ?, ?, ?)
             RETURNING
                 id,
                 account_name,
@@ -138,37 +364,42 @@ impl PaymentBatch {
                 mined_height,
                 mined_header_hash,
                 mined_timestamp,
+                lease_expires_at as "lease_expires_at: DateTime<Utc>",
+                approvals_required,
+                next_retry_at as "next_retry_at: DateTime<Utc>",
+                parent_batch_id,
                 created_at as "created_at: DateTime<Utc>",
                 updated_at as "updated_at: DateTime<Utc>"
             "#,
             batch_id,
             account_name,
             pr_idempotency_key,
-            status
+            status,
+            parent_batch_id
         )
-        .fetch_one(&mut *tx)
+        .fetch_one(&mut *pool)
         .await?;
+        BatchEvent::record(pool, &batch.id, None, &batch.status, actor, detail).await?;
 
         let json = serde_json::to_string(payment_ids).unwrap();
         let status_batched = PaymentStatus::Batched.to_string();
         sqlx::query!(
             r#"
             UPDATE payments
-            SET status = ?, payment_batch_id = ?, updated_at = CURRENT_TIMESTAMP
+            SET status = ?, payment_batch_id = ?, hold_reason = NULL, updated_at = CURRENT_TIMESTAMP
             WHERE id IN (SELECT value FROM json_each(?))
             "#,
             status_batched,
             batch_id,
             json,
         )
-        .execute(&mut *tx)
+        .execute(&mut *pool)
         .await?;
 
-        tx.commit().await?;
         Ok(batch)
     }
 
-    /// Finds payment batches by their status.
+    /// Finds payment batches by their status, leaving out those whose next retry is not yet due.
     pub async fn find_by_status(
         pool: &mut SqliteConnection,
         status: PaymentBatchStatus,
@@ -189,10 +420,14 @@ impl PaymentBatch {
                 mined_height,
                 mined_header_hash,
                 mined_timestamp,
+                lease_expires_at as "lease_expires_at: DateTime<Utc>",
+                approvals_required,
+                next_retry_at as "next_retry_at: DateTime<Utc>",
+                parent_batch_id,
                 created_at as "created_at: DateTime<Utc>",
                 updated_at as "updated_at: DateTime<Utc>"
             FROM payment_batches
-            WHERE status = ?
+            WHERE status = ? AND (next_retry_at IS NULL OR next_retry_at <= CURRENT_TIMESTAMP)
             "#,
             status
         )
@@ -200,12 +435,49 @@ impl PaymentBatch {
         .await
     }
 
-    async fn update_payment_batch_status(
+    /// Finds the batches whose transaction was seen mined and could still be reorged out: those awaiting
+    /// confirmation, and those confirmed at or above `min_confirmed_height`.
+    pub async fn find_reorg_candidates(
         pool: &mut SqliteConnection,
-        batch_id: &str,
-        update: &PaymentBatchUpdate<'_>,
+        min_confirmed_height: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            PaymentBatch,
+            r#"
+            SELECT
+                id,
+                account_name,
+                status,
+                pr_idempotency_key,
+                unsigned_tx_json,
+                signed_tx_json,
+                error_message,
+                retry_count,
+                mined_height,
+                mined_header_hash,
+                mined_timestamp,
+                lease_expires_at as "lease_expires_at: DateTime<Utc>",
+                approvals_required,
+                next_retry_at as "next_retry_at: DateTime<Utc>",
+                parent_batch_id,
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM payment_batches
+            WHERE mined_header_hash IS NOT NULL
+                AND (status = 'AWAITING_CONFIRMATION' OR (status = 'CONFIRMED' AND mined_height >= ?))
+            ORDER BY mined_height
+            "#,
+            min_confirmed_height
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Builds an `UPDATE payment_batches SET ...` query for the given update, without a WHERE clause.
+    fn build_update_query<'a>(
+        update: &PaymentBatchUpdate<'a>,
         increment_retry_count: bool,
-    ) -> Result<(), sqlx::Error> {
+    ) -> sqlx::QueryBuilder<'a, sqlx::Sqlite> {
         let mut qb = sqlx::QueryBuilder::new("UPDATE payment_batches SET");
         let mut needs_comma = false;
 
@@ -225,6 +497,9 @@ impl PaymentBatch {
         if let Some(status) = &update.status {
             separator(&mut qb);
             qb.push("status = ").push_bind(status.to_string());
+            // Any status change ends a worker's lease on the batch.
+            separator(&mut qb);
+            qb.push("lease_expires_at = NULL");
         }
         if let Some(json) = update.unsigned_tx_json {
             separator(&mut qb);
@@ -250,141 +525,1520 @@ impl PaymentBatch {
             separator(&mut qb);
             qb.push("mined_timestamp = ").push_bind(timestamp);
         }
+        if let Some(secs) = update.next_retry_in_secs {
+            separator(&mut qb);
+            qb.push("next_retry_at = datetime('now', ")
+                .push_bind(format!("+{} seconds", secs))
+                .push(")");
+        } else if update.status.is_some() {
+            separator(&mut qb);
+            qb.push("next_retry_at = NULL");
+        }
         if increment_retry_count {
             separator(&mut qb);
             qb.push("retry_count = retry_count + 1");
+            // A failed attempt ends the lease it was made under.
+            if update.status.is_none() {
+                separator(&mut qb);
+                qb.push("lease_expires_at = NULL");
+            }
         }
 
-        qb.push(" WHERE id = ").push_bind(batch_id);
-        qb.build().execute(pool).await?;
+        qb
+    }
 
-        Ok(())
+    /// Reads the current status of a batch.
+    async fn find_status(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+    ) -> Result<Option<PaymentBatchStatus>, sqlx::Error> {
+        let status = sqlx::query_scalar!("SELECT status FROM payment_batches WHERE id = ?", batch_id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(status.map(PaymentBatchStatus::from))
     }
 
-    /// Updates a payment batch to 'AWAITING_SIGNATURE' status with unsigned transaction details.
-    pub async fn update_to_awaiting_signature(
+    /// Applies the update and records it in the batch history when it changes the status or
+    /// counts a retry.
+    async fn update_payment_batch_status(
         pool: &mut SqliteConnection,
         batch_id: &str,
-        unsigned_tx_json: &str,
+        update: &PaymentBatchUpdate<'_>,
+        increment_retry_count: bool,
+        actor: &Actor,
     ) -> Result<(), sqlx::Error> {
-        let update = PaymentBatchUpdate {
-            status: Some(PaymentBatchStatus::AwaitingSignature),
-            unsigned_tx_json: Some(unsigned_tx_json),
-            ..Default::default()
-        };
-        Self::update_payment_batch_status(pool, batch_id, &update, false).await
-    }
+        let mut tx = pool.begin().await?;
+        let from_status = Self::find_status(&mut tx, batch_id).await?;
 
-    /// Updates a payment batch to 'SIGNING_IN_PROGRESS' status.
-    pub async fn update_to_signing_in_progress(pool: &mut SqliteConnection, batch_id: &str) -> Result<(), sqlx::Error> {
-        let update = PaymentBatchUpdate {
-            status: Some(PaymentBatchStatus::SigningInProgress),
-            ..Default::default()
-        };
-        Self::update_payment_batch_status(pool, batch_id, &update, false).await
+        let mut qb = Self::build_update_query(update, increment_retry_count);
+        qb.push(" WHERE id = ").push_bind(batch_id);
+        qb.build().execute(&mut *tx).await?;
+
+        if let Some(from_status) = from_status
+            && (update.status.is_some() || increment_retry_count)
+        {
+            let to_status = update.status.as_ref().unwrap_or(&from_status);
+            BatchEvent::record(
+                &mut tx,
+                batch_id,
+                Some(&from_status),
+                to_status,
+                actor,
+                update.error_message,
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
     }
 
-    /// Updates a payment batch to 'AWAITING_BROADCAST' status with signed transaction details.
-    pub async fn update_to_awaiting_broadcast(
+    /// Applies the update only if the batch is still in status `from`, recording it in the batch history.
+    /// Returns `false` if the batch moved on in the meantime, e.g. because the reaper reclaimed its lease.
+    async fn update_payment_batch_status_from(
         pool: &mut SqliteConnection,
         batch_id: &str,
-        signed_tx_json: &str,
-    ) -> Result<(), sqlx::Error> {
-        let update = PaymentBatchUpdate {
-            status: Some(PaymentBatchStatus::AwaitingBroadcast),
-            signed_tx_json: Some(signed_tx_json),
-            ..Default::default()
-        };
-        Self::update_payment_batch_status(pool, batch_id, &update, false).await
+        from: PaymentBatchStatus,
+        update: &PaymentBatchUpdate<'_>,
+        increment_retry_count: bool,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        Self::update_payment_batch_status_where(pool, batch_id, from, None, update, increment_retry_count, actor).await
     }
 
-    /// Updates a payment batch to 'BROADCASTING' status.
-    pub async fn update_to_broadcasting(pool: &mut SqliteConnection, batch_id: &str) -> Result<(), sqlx::Error> {
-        let update = PaymentBatchUpdate {
-            status: Some(PaymentBatchStatus::Broadcasting),
-            ..Default::default()
-        };
-        Self::update_payment_batch_status(pool, batch_id, &update, false).await
+    /// Applies the update only while the batch is still held under `lease`, recording it in the batch history.
+    /// Returns `false` if the lease was reclaimed in the meantime, even if the batch was claimed again since.
+    async fn update_payment_batch_status_leased(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        lease: &Lease,
+        update: &PaymentBatchUpdate<'_>,
+        increment_retry_count: bool,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        Self::update_payment_batch_status_where(
+            pool,
+            batch_id,
+            lease.status.clone(),
+            Some(&lease.expires_at),
+            update,
+            increment_retry_count,
+            actor,
+        )
+        .await
     }
 
-    /// Updates a payment batch to 'AWAITING_CONFIRMATION' status with the on-chain transaction hash.
-    pub async fn update_to_awaiting_confirmation(
+    async fn update_payment_batch_status_where(
         pool: &mut SqliteConnection,
         batch_id: &str,
-    ) -> Result<(), sqlx::Error> {
-        let update = PaymentBatchUpdate {
-            status: Some(PaymentBatchStatus::AwaitingConfirmation),
-            ..Default::default()
-        };
-        Self::update_payment_batch_status(pool, batch_id, &update, false).await
+        from: PaymentBatchStatus,
+        lease_expires_at: Option<&DateTime<Utc>>,
+        update: &PaymentBatchUpdate<'_>,
+        increment_retry_count: bool,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let mut qb = Self::build_update_query(update, increment_retry_count);
+        qb.push(" WHERE id = ").push_bind(batch_id);
+        qb.push(" AND status = ").push_bind(from.to_string());
+        if let Some(lease_expires_at) = lease_expires_at {
+            qb.push(" AND lease_expires_at = ")
+                .push_bind(sqlite_timestamp(lease_expires_at));
+        }
+        let result = qb.build().execute(&mut *tx).await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        let to_status = update.status.as_ref().unwrap_or(&from);
+        BatchEvent::record(&mut tx, batch_id, Some(&from), to_status, actor, update.error_message).await?;
+
+        tx.commit().await?;
+        Ok(true)
     }
 
-    /// Updates a payment batch to 'CONFIRMED' status.
-    pub async fn update_to_confirmed(
+    /// Moves a batch from `from` to the in-flight status `to` and grants the caller a lease on it.
+    /// Returns `None` if the batch was no longer in `from`, so another worker already owns it.
+    async fn acquire_lease(
         pool: &mut SqliteConnection,
         batch_id: &str,
-        mined_height: u64,
-        mined_header_hash: Vec<u8>,
-        mined_timestamp: u64,
-    ) -> Result<(), sqlx::Error> {
-        let update = PaymentBatchUpdate {
-            status: Some(PaymentBatchStatus::Confirmed),
-            mined_height: Some(mined_height as i64),
-            mined_header_hash: Some(&hex::encode(mined_header_hash)),
-            mined_timestamp: Some(mined_timestamp as i64),
-            ..Default::default()
+        from: PaymentBatchStatus,
+        to: PaymentBatchStatus,
+        actor: &Actor,
+    ) -> Result<Option<Lease>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let from_status = from.to_string();
+        let to_status = to.to_string();
+        let lease_modifier = format!("+{} seconds", LEASE_DURATION_SECS);
+        let expires_at = sqlx::query_scalar!(
+            r#"
+            UPDATE payment_batches
+            SET
+                status = ?,
+                lease_expires_at = datetime('now', ?),
+                next_retry_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = ?
+            RETURNING lease_expires_at as "lease_expires_at!: DateTime<Utc>"
+            "#,
+            to_status,
+            lease_modifier,
+            batch_id,
+            from_status,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(expires_at) = expires_at else {
+            return Ok(None);
         };
-        Self::update_payment_batch_status(pool, batch_id, &update, false).await
+        BatchEvent::record(&mut tx, batch_id, Some(&from), &to, actor, None).await?;
+
+        tx.commit().await?;
+        Ok(Some(Lease { status: to, expires_at }))
     }
 
-    /// Updates a payment batch to 'FAILED' status with an error message.
-    pub async fn update_to_failed(
+    /// The status a leased batch returns to when its lease is released or expires.
+    /// A batch that already holds a signed transaction is never sent back for signing.
+    fn lease_release_status(&self) -> Option<PaymentBatchStatus> {
+        match self.status {
+            PaymentBatchStatus::SigningInProgress if self.signed_tx_json.is_some() => {
+                Some(PaymentBatchStatus::AwaitingBroadcast)
+            },
+            PaymentBatchStatus::SigningInProgress => Some(PaymentBatchStatus::AwaitingSignature),
+            PaymentBatchStatus::Broadcasting => Some(PaymentBatchStatus::AwaitingBroadcast),
+            _ => None,
+        }
+    }
+
+    /// Stores the unsigned transaction PR created under `pr_idempotency_key` on a 'PENDING_BATCHING' batch and
+    /// moves it to 'AWAITING_SIGNATURE', or to 'AWAITING_APPROVAL' when `approvals_required` is set. Returns
+    /// `false` if the batch was rebuilt under a new key in the meantime, in which case the unsigned transaction
+    /// is discarded.
+    pub async fn update_to_awaiting_signature(
         pool: &mut SqliteConnection,
         batch_id: &str,
-        error_message: &str,
-    ) -> Result<(), sqlx::Error> {
+        pr_idempotency_key: &str,
+        unsigned_tx_json: &str,
+        approvals_required: Option<i64>,
+        utxo_lock_secs: u64,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
         let mut tx = pool.begin().await?;
 
-        let update = PaymentBatchUpdate {
-            status: Some(PaymentBatchStatus::Failed),
-            error_message: Some(error_message),
-            ..Default::default()
+        let to_status = if approvals_required.is_some() {
+            PaymentBatchStatus::AwaitingApproval
+        } else {
+            PaymentBatchStatus::AwaitingSignature
         };
-        Self::update_payment_batch_status(&mut tx, batch_id, &update, false).await?;
-        Payment::fail_payments_in_batch(&mut tx, batch_id, error_message).await?;
+        let status = to_status.to_string();
+        let utxo_lock = format!("+{} seconds", utxo_lock_secs);
+        let result = sqlx::query!(
+            r#"
+            UPDATE payment_batches
+            SET
+                status = ?,
+                unsigned_tx_json = ?,
+                approvals_required = ?,
+                utxos_locked_until = datetime('now', ?),
+                lease_expires_at = NULL,
+                next_retry_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = 'PENDING_BATCHING' AND pr_idempotency_key = ?
+            "#,
+            status,
+            unsigned_tx_json,
+            approvals_required,
+            utxo_lock,
+            batch_id,
+            pr_idempotency_key,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        let detail = approvals_required.map(|required| format!("{} approvals required", required));
+        BatchEvent::record(
+            &mut tx,
+            batch_id,
+            Some(&PaymentBatchStatus::PendingBatching),
+            &to_status,
+            actor,
+            detail.as_deref(),
+        )
+        .await?;
 
         tx.commit().await?;
-        Ok(())
+        Ok(true)
     }
 
-    /// Increments the retry count for a payment batch, or sets to FAILED if max retries reached.
-    pub async fn increment_retry_count(
+    /// Releases an 'AWAITING_APPROVAL' batch for signing once its unsigned transaction, the one built under
+    /// `pr_idempotency_key`, has been approved. Returns `false` if the batch moved on or was rebuilt.
+    pub async fn update_to_approved(
         pool: &mut SqliteConnection,
         batch_id: &str,
-        error_message: &str,
-    ) -> Result<(), sqlx::Error> {
+        pr_idempotency_key: &str,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
         let mut tx = pool.begin().await?;
 
-        let batch = Self::find_by_id(&mut tx, batch_id)
-            .await?
-            .ok_or_else(|| sqlx::Error::RowNotFound)?;
-
-        if batch.retry_count + 1 >= MAX_RETRIES {
-            let status_failed = PaymentBatchStatus::Failed;
-            let update = PaymentBatchUpdate {
-                status: Some(status_failed),
-                error_message: Some(error_message),
-                ..Default::default()
-            };
-            Self::update_payment_batch_status(&mut tx, batch_id, &update, false).await?;
-            Payment::fail_payments_in_batch(&mut tx, batch_id, error_message).await?;
-        } else {
-            // No fields to update other than incrementing retry_count.
-            let update = PaymentBatchUpdate::default();
-            Self::update_payment_batch_status(&mut tx, batch_id, &update, true).await?;
+        let status = PaymentBatchStatus::AwaitingSignature.to_string();
+        let result = sqlx::query!(
+            r#"
+            UPDATE payment_batches
+            SET status = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = 'AWAITING_APPROVAL' AND pr_idempotency_key = ?
+            "#,
+            status,
+            batch_id,
+            pr_idempotency_key,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
         }
+        BatchEvent::record(
+            &mut tx,
+            batch_id,
+            Some(&PaymentBatchStatus::AwaitingApproval),
+            &PaymentBatchStatus::AwaitingSignature,
+            actor,
+            detail,
+        )
+        .await?;
 
         tx.commit().await?;
-        Ok(())
+        Ok(true)
+    }
+
+    /// Claims an 'AWAITING_SIGNATURE' batch for signing by moving it to 'SIGNING_IN_PROGRESS' under a lease.
+    /// Returns `None` if the batch was already claimed.
+    pub async fn update_to_signing_in_progress(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        actor: &Actor,
+    ) -> Result<Option<Lease>, sqlx::Error> {
+        Self::acquire_lease(
+            pool,
+            batch_id,
+            PaymentBatchStatus::AwaitingSignature,
+            PaymentBatchStatus::SigningInProgress,
+            actor,
+        )
+        .await
+    }
+
+    /// Updates a payment batch to 'AWAITING_BROADCAST' status with signed transaction details.
+    /// Returns `false` if the signing lease was lost, in which case the signed transaction is discarded.
+    pub async fn update_to_awaiting_broadcast(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        lease: &Lease,
+        signed_tx_json: &str,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let update = PaymentBatchUpdate {
+            status: Some(PaymentBatchStatus::AwaitingBroadcast),
+            signed_tx_json: Some(signed_tx_json),
+            ..Default::default()
+        };
+        Self::update_payment_batch_status_leased(pool, batch_id, lease, &update, false, actor).await
+    }
+
+    /// Moves an 'AWAITING_SIGNATURE' batch straight to 'AWAITING_BROADCAST' with a transaction signed
+    /// outside the processor. Returns `false` if the batch left 'AWAITING_SIGNATURE' or its unsigned
+    /// transaction is no longer `unsigned_tx_json`, the one the upload was checked against.
+    pub async fn submit_signed_tx(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        unsigned_tx_json: &str,
+        signed_tx_json: &str,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let status = PaymentBatchStatus::AwaitingBroadcast.to_string();
+        let result = sqlx::query!(
+            r#"
+            UPDATE payment_batches
+            SET
+                status = ?,
+                signed_tx_json = ?,
+                lease_expires_at = NULL,
+                next_retry_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = 'AWAITING_SIGNATURE' AND unsigned_tx_json = ?
+            "#,
+            status,
+            signed_tx_json,
+            batch_id,
+            unsigned_tx_json,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        BatchEvent::record(
+            &mut tx,
+            batch_id,
+            Some(&PaymentBatchStatus::AwaitingSignature),
+            &PaymentBatchStatus::AwaitingBroadcast,
+            actor,
+            Some("Signed transaction uploaded"),
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Claims an 'AWAITING_BROADCAST' batch for broadcasting by moving it to 'BROADCASTING' under a lease.
+    /// Returns `None` if the batch was already claimed.
+    pub async fn update_to_broadcasting(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        actor: &Actor,
+    ) -> Result<Option<Lease>, sqlx::Error> {
+        Self::acquire_lease(
+            pool,
+            batch_id,
+            PaymentBatchStatus::AwaitingBroadcast,
+            PaymentBatchStatus::Broadcasting,
+            actor,
+        )
+        .await
+    }
+
+    /// Holds a 'BROADCASTING' batch whose signed transaction failed verification in 'VERIFICATION_FAILED'.
+    /// Its payments stay 'BATCHED' until an operator retries, releases or fails the batch. Returns `false`
+    /// if the broadcast lease was lost.
+    pub async fn update_to_verification_failed(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        lease: &Lease,
+        error_message: &str,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let update = PaymentBatchUpdate {
+            status: Some(PaymentBatchStatus::VerificationFailed),
+            error_message: Some(error_message),
+            ..Default::default()
+        };
+        Self::update_payment_batch_status_leased(pool, batch_id, lease, &update, false, actor).await
+    }
+
+    /// Moves a 'BROADCASTING' batch whose transaction the base node accepted to 'AWAITING_CONFIRMATION'.
+    /// Returns `false` if the broadcast lease was lost.
+    pub async fn update_to_awaiting_confirmation(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        lease: &Lease,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let update = PaymentBatchUpdate {
+            status: Some(PaymentBatchStatus::AwaitingConfirmation),
+            ..Default::default()
+        };
+        Self::update_payment_batch_status_leased(pool, batch_id, lease, &update, false, actor).await
+    }
+
+    /// Notes that a batch's transaction is about to be submitted to the base node, which keeps operators from
+    /// failing the batch from then on. Only the first submission is recorded.
+    pub async fn record_submission(pool: &mut SqliteConnection, batch_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE payment_batches
+            SET submitted_at = COALESCE(submitted_at, CURRENT_TIMESTAMP), updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+            batch_id,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Whether a batch's transaction was ever submitted to the base node, see [`Self::record_submission`].
+    pub async fn was_submitted(pool: &mut SqliteConnection, batch_id: &str) -> Result<bool, sqlx::Error> {
+        let submitted = sqlx::query_scalar!(
+            r#"SELECT submitted_at IS NOT NULL as "submitted!: bool" FROM payment_batches WHERE id = ?"#,
+            batch_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(submitted.unwrap_or(false))
+    }
+
+    /// Notes the block an 'AWAITING_CONFIRMATION' batch's transaction was mined in, so a reorg can be detected
+    /// before the batch is confirmed.
+    pub async fn record_mined_block(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        block: &MinedBlock,
+    ) -> Result<(), sqlx::Error> {
+        let mined_height = block.height as i64;
+        let mined_header_hash = hex::encode(&block.header_hash);
+        let mined_timestamp = block.timestamp as i64;
+        sqlx::query!(
+            r#"
+            UPDATE payment_batches
+            SET mined_height = ?, mined_header_hash = ?, mined_timestamp = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = 'AWAITING_CONFIRMATION'
+            "#,
+            mined_height,
+            mined_header_hash,
+            mined_timestamp,
+            batch_id,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Notes that the base node does not know the transaction of an 'AWAITING_CONFIRMATION' batch, counting
+    /// a resubmission if one was made. Returns when the transaction was first found missing, or `None` if the
+    /// batch moved on in the meantime.
+    pub async fn record_missing(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        resubmitted: bool,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let resubmissions = i64::from(resubmitted);
+        sqlx::query_scalar!(
+            r#"
+            UPDATE payment_batches
+            SET
+                missing_since = COALESCE(missing_since, CURRENT_TIMESTAMP),
+                resubmission_count = resubmission_count + ?,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = 'AWAITING_CONFIRMATION'
+            RETURNING missing_since as "missing_since!: DateTime<Utc>"
+            "#,
+            resubmissions,
+            batch_id,
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Clears the missing mark of a batch whose transaction the base node knows again.
+    pub async fn clear_missing(pool: &mut SqliteConnection, batch_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE payment_batches
+            SET missing_since = NULL, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND missing_since IS NOT NULL
+            "#,
+            batch_id,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Declares the transaction of an 'AWAITING_CONFIRMATION' batch dropped. If its inputs have been spent
+    /// the transaction can never be mined, so the payments return to 'RECEIVED' to be batched again with
+    /// fresh inputs; otherwise they are failed. Returns `false` if the batch moved on in the meantime.
+    pub async fn update_to_dropped(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        inputs_spent: bool,
+        error_message: &str,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let update = PaymentBatchUpdate {
+            status: Some(PaymentBatchStatus::Dropped),
+            error_message: Some(error_message),
+            ..Default::default()
+        };
+        if !Self::update_payment_batch_status_from(
+            &mut tx,
+            batch_id,
+            PaymentBatchStatus::AwaitingConfirmation,
+            &update,
+            false,
+            actor,
+        )
+        .await?
+        {
+            return Ok(false);
+        }
+        if inputs_spent {
+            Payment::release_payments_in_batch(&mut tx, batch_id, actor, Some(error_message)).await?;
+        } else {
+            Payment::fail_payments_in_batch(&mut tx, batch_id, FailureCode::TransactionDropped, error_message, actor)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Handles a batch whose transaction is no longer in the block `mined_header_hash` it was recorded in.
+    /// The batch, still in status `from`, moves to `to`: 'AWAITING_CONFIRMATION' with the block it has been
+    /// mined in since, if any, or 'REORGED' when the base node no longer knows the transaction. The payments
+    /// of a 'CONFIRMED' batch go back to 'BATCHED'. Returns `false` if the batch changed in the meantime.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_after_reorg(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        from: PaymentBatchStatus,
+        mined_header_hash: &str,
+        to: PaymentBatchStatus,
+        block: Option<&MinedBlock>,
+        detail: &str,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let from_status = from.to_string();
+        let to_status = to.to_string();
+        let new_height = block.map(|block| block.height as i64);
+        let new_header_hash = block.map(|block| hex::encode(&block.header_hash));
+        let new_timestamp = block.map(|block| block.timestamp as i64);
+        let error_message = (to == PaymentBatchStatus::Reorged).then_some(detail);
+        let result = sqlx::query!(
+            r#"
+            UPDATE payment_batches
+            SET
+                status = ?,
+                mined_height = ?,
+                mined_header_hash = ?,
+                mined_timestamp = ?,
+                error_message = COALESCE(?, error_message),
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = ? AND mined_header_hash = ?
+            "#,
+            to_status,
+            new_height,
+            new_header_hash,
+            new_timestamp,
+            error_message,
+            batch_id,
+            from_status,
+            mined_header_hash,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        BatchEvent::record(&mut tx, batch_id, Some(&from), &to, actor, Some(detail)).await?;
+        if from == PaymentBatchStatus::Confirmed {
+            Payment::unconfirm_payments_in_batch(&mut tx, batch_id, detail, actor).await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Updates a payment batch to 'CONFIRMED' status.
+    pub async fn update_to_confirmed(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        mined_height: u64,
+        mined_header_hash: Vec<u8>,
+        mined_timestamp: u64,
+        actor: &Actor,
+    ) -> Result<(), sqlx::Error> {
+        let update = PaymentBatchUpdate {
+            status: Some(PaymentBatchStatus::Confirmed),
+            mined_height: Some(mined_height as i64),
+            mined_header_hash: Some(&hex::encode(mined_header_hash)),
+            mined_timestamp: Some(mined_timestamp as i64),
+            ..Default::default()
+        };
+        Self::update_payment_batch_status(pool, batch_id, &update, false, actor).await
+    }
+
+    /// Fails a batch held under `lease`, along with its payments. Returns `false` if the lease was reclaimed in
+    /// the meantime, in which case the batch is left to the worker that holds it now.
+    pub async fn update_to_failed_under_lease(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        lease: &Lease,
+        code: FailureCode,
+        error_message: &str,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let update = PaymentBatchUpdate {
+            status: Some(PaymentBatchStatus::Failed),
+            error_message: Some(error_message),
+            ..Default::default()
+        };
+        if !Self::update_payment_batch_status_leased(&mut tx, batch_id, lease, &update, false, actor).await? {
+            return Ok(false);
+        }
+        Payment::fail_payments_in_batch(&mut tx, batch_id, code, error_message, actor).await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Whether one more failed attempt at the stage the batch is waiting for uses up the stage's retries.
+    pub async fn is_last_retry(pool: &mut SqliteConnection, batch_id: &str) -> Result<bool, sqlx::Error> {
+        let batch = Self::find_by_id(&mut *pool, batch_id)
+            .await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)?;
+        let Some(stage) = RetryStage::for_status(&batch.status) else {
+            return Ok(false);
+        };
+        let stage = stage.to_string();
+        let retries = sqlx::query_scalar!(
+            "SELECT retries FROM payment_batch_stage_retries WHERE payment_batch_id = ? AND stage = ?",
+            batch_id,
+            stage,
+        )
+        .fetch_optional(&mut *pool)
+        .await?
+        .unwrap_or(0);
+        Ok(retries + 1 >= MAX_RETRIES)
+    }
+
+    /// Counts a failed attempt at the stage a 'PENDING_BATCHING' batch held under `lease` is waiting for and
+    /// schedules the next one after `backoff`, or sets the batch to FAILED with the attempt's `code` once the stage
+    /// has used up its retries. Returns `false` without counting the attempt if the batch is no longer held under
+    /// `lease`, e.g. because it was rebuilt or its lease was reclaimed.
+    pub async fn increment_retry_count(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        lease: &Lease,
+        code: FailureCode,
+        error_message: &str,
+        backoff: &RetryBackoff,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let Some(stage) = RetryStage::for_status(&lease.status) else {
+            return Ok(false);
+        };
+        let retries = Self::count_stage_retry(&mut tx, batch_id, stage).await?;
+
+        if retries >= MAX_RETRIES {
+            let status_failed = PaymentBatchStatus::Failed;
+            let update = PaymentBatchUpdate {
+                status: Some(status_failed),
+                error_message: Some(error_message),
+                ..Default::default()
+            };
+            if !Self::update_payment_batch_status_leased(&mut tx, batch_id, lease, &update, false, actor).await? {
+                return Ok(false);
+            }
+            Payment::fail_payments_in_batch(&mut tx, batch_id, code, error_message, actor).await?;
+        } else {
+            // Only the retry count changes; the error is kept in the batch history.
+            let update = PaymentBatchUpdate {
+                error_message: Some(error_message),
+                next_retry_in_secs: Some(backoff.delay_secs(retries)),
+                ..Default::default()
+            };
+            if !Self::update_payment_batch_status_leased(&mut tx, batch_id, lease, &update, true, actor).await? {
+                return Ok(false);
+            }
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Releases a worker's lease after a failed attempt, returning the batch to the state it was claimed from
+    /// after `backoff` and counting the attempt against the retry budget of its stage. A batch that has used up its
+    /// retries fails with the attempt's `code`. Does nothing if the batch is no longer held under `lease`.
+    pub async fn release_lease(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        lease: &Lease,
+        code: FailureCode,
+        error_message: &str,
+        backoff: &RetryBackoff,
+        actor: &Actor,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let batch = Self::find_by_id(&mut tx, batch_id)
+            .await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)?;
+        let Some(release_status) = batch.lease_release_status() else {
+            return Ok(());
+        };
+        let Some(stage) = RetryStage::for_status(&batch.status) else {
+            return Ok(());
+        };
+        let retries = Self::count_stage_retry(&mut tx, batch_id, stage).await?;
+
+        if retries >= MAX_RETRIES {
+            let update = PaymentBatchUpdate {
+                status: Some(PaymentBatchStatus::Failed),
+                error_message: Some(error_message),
+                ..Default::default()
+            };
+            if !Self::update_payment_batch_status_leased(&mut tx, batch_id, lease, &update, false, actor).await? {
+                return Ok(());
+            }
+            Payment::fail_payments_in_batch(&mut tx, batch_id, code, error_message, actor).await?;
+        } else {
+            let update = PaymentBatchUpdate {
+                status: Some(release_status),
+                error_message: Some(error_message),
+                next_retry_in_secs: Some(backoff.delay_secs(retries)),
+                ..Default::default()
+            };
+            if !Self::update_payment_batch_status_leased(&mut tx, batch_id, lease, &update, true, actor).await? {
+                return Ok(());
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Counts a retry of `stage` for the batch and returns how many the stage has had.
+    async fn count_stage_retry(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        stage: RetryStage,
+    ) -> Result<i64, sqlx::Error> {
+        let stage = stage.to_string();
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO payment_batch_stage_retries (payment_batch_id, stage, retries)
+            VALUES (?, ?, 1)
+            ON CONFLICT (payment_batch_id, stage) DO UPDATE SET retries = retries + 1
+            RETURNING retries
+            "#,
+            batch_id,
+            stage,
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Returns batches stuck in 'SIGNING_IN_PROGRESS' or 'BROADCASTING' to the state they were claimed from.
+    /// Only expired leases are reclaimed unless `include_active` is set, which is only safe before any
+    /// worker has started. Returns the recovered batches as they were before recovery.
+    pub async fn recover_leases(
+        pool: &mut SqliteConnection,
+        include_active: bool,
+        actor: &Actor,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let batches = sqlx::query_as!(
+            PaymentBatch,
+            r#"
+            SELECT
+                id,
+                account_name,
+                status,
+                pr_idempotency_key,
+                unsigned_tx_json,
+                signed_tx_json,
+                error_message,
+                retry_count,
+                mined_height,
+                mined_header_hash,
+                mined_timestamp,
+                lease_expires_at as "lease_expires_at: DateTime<Utc>",
+                approvals_required,
+                next_retry_at as "next_retry_at: DateTime<Utc>",
+                parent_batch_id,
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM payment_batches
+            WHERE status IN ('SIGNING_IN_PROGRESS', 'BROADCASTING')
+                AND (? OR lease_expires_at IS NULL OR lease_expires_at <= CURRENT_TIMESTAMP)
+            "#,
+            include_active
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for batch in &batches {
+            let error_message = format!("Lease on {} batch expired, batch recovered", batch.status);
+            let update = PaymentBatchUpdate {
+                status: batch.lease_release_status(),
+                error_message: Some(&error_message),
+                ..Default::default()
+            };
+            Self::update_payment_batch_status_from(&mut tx, &batch.id, batch.status.clone(), &update, false, actor)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(batches)
+    }
+
+    /// Restarts a 'FAILED', 'VERIFICATION_FAILED' or 'REORGED' batch, still in status `from`, from the given stage with
+    /// fresh retry budgets for that stage and later ones, discarding any transaction produced by that stage or later ones, and moves its
+    /// failed payments back to 'BATCHED'. A batch restarted from signing whose transaction still lacks
+    /// approvals goes back to 'AWAITING_APPROVAL' instead. Returns `false` if the batch moved on.
+    pub async fn retry_from_stage(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        from: PaymentBatchStatus,
+        stage: RetryStage,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let status = stage.status().to_string();
+        let pr_idempotency_key = (stage == RetryStage::CreateUnsignedTx).then(|| Uuid::new_v4().to_string());
+        let clear_unsigned_tx = stage == RetryStage::CreateUnsignedTx;
+        let clear_signed_tx = stage != RetryStage::Broadcast;
+        let needs_approval = stage == RetryStage::Sign;
+        let from_status = from.to_string();
+        let to_status = sqlx::query_scalar!(
+            r#"
+            UPDATE payment_batches
+            SET
+                status = CASE
+                    WHEN ? AND approvals_required > (
+                        SELECT COUNT(*) FROM batch_approvals ba
+                        WHERE ba.payment_batch_id = payment_batches.id
+                            AND ba.pr_idempotency_key = payment_batches.pr_idempotency_key
+                    ) THEN 'AWAITING_APPROVAL'
+                    ELSE ?
+                END,
+                pr_idempotency_key = COALESCE(?, pr_idempotency_key),
+                unsigned_tx_json = CASE WHEN ? THEN NULL ELSE unsigned_tx_json END,
+                signed_tx_json = CASE WHEN ? THEN NULL ELSE signed_tx_json END,
+                approvals_required = CASE WHEN ? THEN NULL ELSE approvals_required END,
+                error_message = NULL,
+                retry_count = 0,
+                lease_expires_at = NULL,
+                next_retry_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = ? AND status IN ('FAILED', 'VERIFICATION_FAILED', 'REORGED')
+            RETURNING status
+            "#,
+            needs_approval,
+            status,
+            pr_idempotency_key,
+            clear_unsigned_tx,
+            clear_signed_tx,
+            clear_unsigned_tx,
+            batch_id,
+            from_status,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(to_status) = to_status else {
+            return Ok(false);
+        };
+        let reset_stages: Vec<String> = [RetryStage::CreateUnsignedTx, RetryStage::Sign, RetryStage::Broadcast]
+            .iter()
+            .skip_while(|earlier| **earlier != stage)
+            .map(|stage| stage.to_string())
+            .collect();
+        let reset_stages = serde_json::to_string(&reset_stages).unwrap();
+        sqlx::query!(
+            r#"
+            DELETE FROM payment_batch_stage_retries
+            WHERE payment_batch_id = ? AND stage IN (SELECT value FROM json_each(?))
+            "#,
+            batch_id,
+            reset_stages,
+        )
+        .execute(&mut *tx)
+        .await?;
+        BatchEvent::record(
+            &mut tx,
+            batch_id,
+            Some(&from),
+            &PaymentBatchStatus::from(to_status),
+            actor,
+            detail,
+        )
+        .await?;
+
+        Payment::requeue_failed_payments_in_batch(&mut tx, batch_id, actor, detail).await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Fails a batch that is still in status `from` and returns its payments to 'RECEIVED' so they are
+    /// batched again. Returns `false` if the batch moved on in the meantime.
+    pub async fn release_payments(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        from: PaymentBatchStatus,
+        error_message: &str,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let update = PaymentBatchUpdate {
+            status: Some(PaymentBatchStatus::Failed),
+            error_message: Some(error_message),
+            ..Default::default()
+        };
+        if !Self::update_payment_batch_status_from(&mut tx, batch_id, from, &update, false, actor).await? {
+            return Ok(false);
+        }
+        Payment::release_payments_in_batch(&mut tx, batch_id, actor, Some(error_message)).await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Cancels one payment of a batch that is still in status `from` and has not been signed. The batch
+    /// goes back to 'PENDING_BATCHING' under a new PR idempotency key, so its transaction is rebuilt
+    /// without the payment, or moves to 'CANCELLED' if no payments remain. Returns `false` if the batch
+    /// or the payment moved on in the meantime, or if PR is building the batch's transaction right now,
+    /// see [`Self::claim_for_unsigned_tx`].
+    ///
+    /// PR has no call to release the UTXOs it reserved for an old transaction, so they stay locked until
+    /// `utxos_locked_until`. The batch is rebuilt at once all the same; only if PR cannot fund it without
+    /// them does it wait, see [`Self::wait_for_utxo_lock`].
+    pub async fn cancel_payment(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        from: PaymentBatchStatus,
+        payment_id: &str,
+        actor: &Actor,
+        detail: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let remaining = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM payments WHERE payment_batch_id = ? AND id != ?",
+            batch_id,
+            payment_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let (to, batch_detail) = if remaining == 0 {
+            (
+                PaymentBatchStatus::Cancelled,
+                "All payments of the batch were cancelled".to_string(),
+            )
+        } else {
+            (
+                PaymentBatchStatus::PendingBatching,
+                format!("Rebuilding without cancelled payment {}", payment_id),
+            )
+        };
+        let to_status = to.to_string();
+        let error_message = (to == PaymentBatchStatus::Cancelled).then_some(batch_detail.as_str());
+        let pr_idempotency_key = Uuid::new_v4().to_string();
+        let from_status = from.to_string();
+        // The lease guard keeps a cancel from racing a create_unsigned_transaction call in flight.
+        let result = sqlx::query!(
+            r#"
+            UPDATE payment_batches
+            SET
+                status = ?,
+                pr_idempotency_key = ?,
+                error_message = COALESCE(?, error_message),
+                unsigned_tx_json = NULL,
+                approvals_required = NULL,
+                lease_expires_at = NULL,
+                next_retry_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = ?
+                AND (lease_expires_at IS NULL OR lease_expires_at <= CURRENT_TIMESTAMP)
+            "#,
+            to_status,
+            pr_idempotency_key,
+            error_message,
+            batch_id,
+            from_status,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        BatchEvent::record(&mut tx, batch_id, Some(&from), &to, actor, Some(&batch_detail)).await?;
+        if !Payment::cancel(&mut tx, payment_id, PaymentStatus::Batched, actor, detail).await? {
+            return Ok(false);
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Grants the unsigned transaction creator a lease on a 'PENDING_BATCHING' batch for the duration of its
+    /// create_unsigned_transaction call under `pr_idempotency_key`. The batch keeps its status, but it cannot
+    /// be cancelled from until the lease ends. Returns `None` if the batch was rebuilt or is already leased.
+    pub async fn claim_for_unsigned_tx(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        pr_idempotency_key: &str,
+    ) -> Result<Option<Lease>, sqlx::Error> {
+        let lease_modifier = format!("+{} seconds", LEASE_DURATION_SECS);
+        let expires_at = sqlx::query_scalar!(
+            r#"
+            UPDATE payment_batches
+            SET lease_expires_at = datetime('now', ?), updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = 'PENDING_BATCHING' AND pr_idempotency_key = ?
+                AND (lease_expires_at IS NULL OR lease_expires_at <= CURRENT_TIMESTAMP)
+            RETURNING lease_expires_at as "lease_expires_at!: DateTime<Utc>"
+            "#,
+            lease_modifier,
+            batch_id,
+            pr_idempotency_key,
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(expires_at.map(|expires_at| Lease {
+            status: PaymentBatchStatus::PendingBatching,
+            expires_at,
+        }))
+    }
+
+    /// Until when PR keeps the UTXOs of the batch's last unsigned transaction reserved, if that is still
+    /// in the future.
+    pub async fn utxos_locked_until(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let locked_until = sqlx::query_scalar!(
+            r#"
+            SELECT utxos_locked_until as "utxos_locked_until: DateTime<Utc>"
+            FROM payment_batches
+            WHERE id = ? AND utxos_locked_until > CURRENT_TIMESTAMP
+            "#,
+            batch_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(locked_until.flatten())
+    }
+
+    /// Holds back a 'PENDING_BATCHING' batch held under `lease` that PR could not build while the UTXOs of its
+    /// previous, discarded transaction are still reserved, until they are free again. The attempt does not count
+    /// against the stage's retries. Returns when the batch waits until, or `None` if nothing is reserved or the
+    /// batch is no longer held under `lease`.
+    pub async fn wait_for_utxo_lock(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        lease: &Lease,
+        error_message: &str,
+        actor: &Actor,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let lease_expires_at = sqlite_timestamp(&lease.expires_at);
+        let waits_until = sqlx::query_scalar!(
+            r#"
+            UPDATE payment_batches
+            SET
+                next_retry_at = utxos_locked_until,
+                lease_expires_at = NULL,
+                error_message = ?,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND status = 'PENDING_BATCHING' AND lease_expires_at = ?
+                AND utxos_locked_until > CURRENT_TIMESTAMP
+            RETURNING next_retry_at as "next_retry_at!: DateTime<Utc>"
+            "#,
+            error_message,
+            batch_id,
+            lease_expires_at,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(waits_until) = waits_until else {
+            return Ok(None);
+        };
+        let detail = format!(
+            "Waiting until {} for PR to release the UTXOs of the discarded transaction: {}",
+            waits_until, error_message
+        );
+        let status = PaymentBatchStatus::PendingBatching;
+        BatchEvent::record(&mut tx, batch_id, Some(&status), &status, actor, Some(&detail)).await?;
+
+        tx.commit().await?;
+        Ok(Some(waits_until))
+    }
+
+    /// Splits a batch held under `lease` into two new 'PENDING_BATCHING' batches with half of its payments each,
+    /// so a payment that makes PR reject the whole batch ends up failing on its own while the others proceed. The
+    /// batch moves to 'SPLIT'. Returns the new batches, or `None` if the batch is no longer held under `lease` or
+    /// has fewer than two payments.
+    pub async fn split(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        lease: &Lease,
+        error_message: &str,
+        actor: &Actor,
+    ) -> Result<Option<Vec<Self>>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let Some(batch) = Self::find_by_id(&mut tx, batch_id).await? else {
+            return Ok(None);
+        };
+        let payment_ids = sqlx::query_scalar!(
+            "SELECT id FROM payments WHERE payment_batch_id = ? ORDER BY created_at, id",
+            batch_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        if payment_ids.len() < 2 {
+            return Ok(None);
+        }
+
+        let update = PaymentBatchUpdate {
+            status: Some(PaymentBatchStatus::Split),
+            error_message: Some(error_message),
+            ..Default::default()
+        };
+        if !Self::update_payment_batch_status_leased(&mut tx, batch_id, lease, &update, false, actor).await? {
+            return Ok(None);
+        }
+
+        let (first_half, second_half) = payment_ids.split_at(payment_ids.len() / 2);
+        let detail = format!("Split from batch {}", batch_id);
+        let mut halves = Vec::with_capacity(2);
+        for payment_ids in [first_half, second_half] {
+            let half = Self::insert_with_payments(
+                &mut tx,
+                &batch.account_name,
+                &Uuid::new_v4().to_string(),
+                Some(batch_id),
+                payment_ids,
+                actor,
+                Some(&detail),
+            )
+            .await?;
+            let payment_detail = format!("Moved to batch {} after batch {} was split", half.id, batch_id);
+            PaymentEvent::record_for_payments(
+                &mut tx,
+                payment_ids,
+                &PaymentStatus::Batched,
+                actor,
+                Some(&payment_detail),
+            )
+            .await?;
+            halves.push(half);
+        }
+
+        tx.commit().await?;
+        Ok(Some(halves))
+    }
+
+    /// Fails a batch that is still in status `from`, along with its payments.
+    /// Returns `false` if the batch moved on in the meantime.
+    pub async fn update_to_failed_from(
+        pool: &mut SqliteConnection,
+        batch_id: &str,
+        from: PaymentBatchStatus,
+        code: FailureCode,
+        error_message: &str,
+        actor: &Actor,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let update = PaymentBatchUpdate {
+            status: Some(PaymentBatchStatus::Failed),
+            error_message: Some(error_message),
+            ..Default::default()
+        };
+        if !Self::update_payment_batch_status_from(&mut tx, batch_id, from, &update, false, actor).await? {
+            return Ok(false);
+        }
+        Payment::fail_payments_in_batch(&mut tx, batch_id, code, error_message, actor).await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::test_support;
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn reclaimed_lease_cannot_update_batch(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 1, 1_000).await;
+        test_support::set_batch_status(&mut conn, &batch.id, PaymentBatchStatus::AwaitingSignature).await;
+        let stale_lease = PaymentBatch::update_to_signing_in_progress(&mut conn, &batch.id, &Actor::TransactionSigner)
+            .await
+            .unwrap()
+            .unwrap();
+        // The lease expired and was reclaimed, and another worker now holds the batch under a new lease.
+        sqlx::query("UPDATE payment_batches SET lease_expires_at = datetime('now', '+600 seconds') WHERE id = ?")
+            .bind(&batch.id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        let failed = PaymentBatch::update_to_failed_under_lease(
+            &mut conn,
+            &batch.id,
+            &stale_lease,
+            FailureCode::SigningRejected,
+            "rejected",
+            &Actor::TransactionSigner,
+        )
+        .await
+        .unwrap();
+        let signed = PaymentBatch::update_to_awaiting_broadcast(
+            &mut conn,
+            &batch.id,
+            &stale_lease,
+            "{}",
+            &Actor::TransactionSigner,
+        )
+        .await
+        .unwrap();
+
+        assert!(!failed);
+        assert!(!signed);
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::SigningInProgress);
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+        assert!(payments.iter().all(|payment| payment.status == PaymentStatus::Batched));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn rebuilt_batch_waits_for_utxo_lock_only_if_pr_fails(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 2, 1_000).await;
+        assert!(
+            PaymentBatch::update_to_awaiting_signature(
+                &mut conn,
+                &batch.id,
+                &batch.pr_idempotency_key,
+                "{}",
+                None,
+                3_600,
+                &Actor::UnsignedTxCreator,
+            )
+            .await
+            .unwrap()
+        );
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+
+        let cancelled = PaymentBatch::cancel_payment(
+            &mut conn,
+            &batch.id,
+            PaymentBatchStatus::AwaitingSignature,
+            &payments[0].id,
+            &Actor::Api,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(cancelled);
+        let rebuilt = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(rebuilt.status, PaymentBatchStatus::PendingBatching);
+        assert_ne!(rebuilt.pr_idempotency_key, batch.pr_idempotency_key);
+        assert_eq!(rebuilt.next_retry_at, None);
+
+        let lease = PaymentBatch::claim_for_unsigned_tx(&mut conn, &batch.id, &rebuilt.pr_idempotency_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let waits_until =
+            PaymentBatch::wait_for_utxo_lock(&mut conn, &batch.id, &lease, "funds locked", &Actor::UnsignedTxCreator)
+                .await
+                .unwrap()
+                .unwrap();
+
+        let wait = waits_until - Utc::now();
+        assert!(wait > chrono::Duration::seconds(3_500) && wait <= chrono::Duration::seconds(3_600));
+        let waiting = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(waiting.next_retry_at, Some(waits_until));
+        let retries: Option<i64> =
+            sqlx::query_scalar("SELECT retries FROM payment_batch_stage_retries WHERE payment_batch_id = ?")
+                .bind(&batch.id)
+                .fetch_optional(&mut *conn)
+                .await
+                .unwrap();
+        assert_eq!(retries, None);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn batch_being_built_cannot_be_cancelled(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 2, 1_000).await;
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+        PaymentBatch::claim_for_unsigned_tx(&mut conn, &batch.id, &batch.pr_idempotency_key)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let cancelled = PaymentBatch::cancel_payment(
+            &mut conn,
+            &batch.id,
+            PaymentBatchStatus::PendingBatching,
+            &payments[0].id,
+            &Actor::Api,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(!cancelled);
+        let unchanged = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(unchanged.pr_idempotency_key, batch.pr_idempotency_key);
+        let payment = Payment::get_by_id(&mut conn, &payments[0].id).await.unwrap().unwrap();
+        assert_eq!(payment.status, PaymentStatus::Batched);
+        // A second claim, e.g. by an overlapping worker run, is refused as well.
+        let claimed = PaymentBatch::claim_for_unsigned_tx(&mut conn, &batch.id, &batch.pr_idempotency_key)
+            .await
+            .unwrap();
+        assert!(claimed.is_none());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn batch_never_built_is_rebuilt_at_once(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 2, 1_000).await;
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+
+        let cancelled = PaymentBatch::cancel_payment(
+            &mut conn,
+            &batch.id,
+            PaymentBatchStatus::PendingBatching,
+            &payments[0].id,
+            &Actor::Api,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(cancelled);
+        let rebuilt = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(rebuilt.status, PaymentBatchStatus::PendingBatching);
+        assert_eq!(rebuilt.next_retry_at, None);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn batch_with_all_payments_cancelled_is_cancelled(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 1, 1_000).await;
+        test_support::set_batch_status(&mut conn, &batch.id, PaymentBatchStatus::AwaitingSignature).await;
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+
+        let cancelled = PaymentBatch::cancel_payment(
+            &mut conn,
+            &batch.id,
+            PaymentBatchStatus::AwaitingSignature,
+            &payments[0].id,
+            &Actor::Api,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(cancelled);
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Cancelled);
+        let payment = Payment::get_by_id(&mut conn, &payments[0].id).await.unwrap().unwrap();
+        assert_eq!(payment.status, PaymentStatus::Cancelled);
+    }
+
+    #[test]
+    fn retry_backoff_doubles_up_to_max_with_jitter() {
+        let backoff = RetryBackoff {
+            base_secs: 10,
+            max_secs: 100,
+        };
+        let cases = [
+            (0, 10),
+            (1, 10),
+            (2, 20),
+            (3, 40),
+            (4, 80),
+            (5, 100),
+            (6, 100),
+            (1_000, 100),
+        ];
+        for (retries, delay) in cases {
+            for _ in 0..200 {
+                let delay_secs = backoff.delay_secs(retries);
+                assert!(
+                    (delay..=delay + delay / 4).contains(&delay_secs),
+                    "retry {} waited {}s, expected {}s plus at most a quarter",
+                    retries,
+                    delay_secs,
+                    delay
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn retry_backoff_jitter_spreads_delays() {
+        let backoff = RetryBackoff {
+            base_secs: 1_000,
+            max_secs: 1_000,
+        };
+        let delays: std::collections::HashSet<u64> = (0..100).map(|_| backoff.delay_secs(1)).collect();
+        assert!(delays.len() > 1);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn retry_from_stage_resets_that_stage_and_later_ones(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 1, 1_000).await;
+        for (stage, retries) in [
+            (RetryStage::CreateUnsignedTx, 3),
+            (RetryStage::Sign, 5),
+            (RetryStage::Broadcast, 7),
+        ] {
+            sqlx::query("INSERT INTO payment_batch_stage_retries (payment_batch_id, stage, retries) VALUES (?, ?, ?)")
+                .bind(&batch.id)
+                .bind(stage.to_string())
+                .bind(retries)
+                .execute(&mut *conn)
+                .await
+                .unwrap();
+        }
+        sqlx::query("UPDATE payment_batches SET retry_count = 9, unsigned_tx_json = '{}' WHERE id = ?")
+            .bind(&batch.id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        test_support::set_batch_status(&mut conn, &batch.id, PaymentBatchStatus::Failed).await;
+
+        let retried = PaymentBatch::retry_from_stage(
+            &mut conn,
+            &batch.id,
+            PaymentBatchStatus::Failed,
+            RetryStage::Sign,
+            &Actor::Api,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(retried);
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::AwaitingSignature);
+        assert_eq!(batch.retry_count, 0);
+        let budgets: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT stage, retries FROM payment_batch_stage_retries WHERE payment_batch_id = ? ORDER BY stage",
+        )
+        .bind(&batch.id)
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(budgets, vec![(RetryStage::CreateUnsignedTx.to_string(), 3)]);
+        // The next signing failure starts the stage's budget and backoff afresh.
+        assert_eq!(
+            PaymentBatch::count_stage_retry(&mut conn, &batch.id, RetryStage::Sign)
+                .await
+                .unwrap(),
+            1
+        );
     }
 }
@@ -0,0 +1,342 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use std::fmt;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db::{cadence::Cadence, sqlite_timestamp};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PayoutRunStatus {
+    /// The occurrence's payment was created.
+    Created,
+    /// The occurrence passed while the processor was not running and was not paid.
+    Missed,
+}
+
+impl From<String> for PayoutRunStatus {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "CREATED" => PayoutRunStatus::Created,
+            "MISSED" => PayoutRunStatus::Missed,
+            _ => panic!("Unknown PayoutRunStatus: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for PayoutRunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PayoutRunStatus::Created => write!(f, "CREATED"),
+            PayoutRunStatus::Missed => write!(f, "MISSED"),
+        }
+    }
+}
+
+/// A payment that is made again on every occurrence of a cadence.
+#[derive(Debug, Clone)]
+pub struct PayoutSchedule {
+    pub id: String,
+    pub account_name: String,
+    pub recipient_address: String,
+    pub amount: i64,
+    pub payment_id: Option<String>,
+    pub callback_url: Option<String>,
+    /// A cron expression, see [`Cadence`].
+    pub cadence: String,
+    pub is_paused: bool,
+    /// The next occurrence that has not been run yet.
+    pub next_run_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The fields of a schedule supplied when it is created.
+#[derive(Debug)]
+pub struct NewPayoutSchedule<'a> {
+    pub account_name: &'a str,
+    pub recipient_address: &'a str,
+    pub amount: i64,
+    pub payment_id: Option<&'a str>,
+    pub callback_url: Option<&'a str>,
+    pub cadence: &'a Cadence,
+    pub is_paused: bool,
+    pub next_run_at: DateTime<Utc>,
+}
+
+/// Changes to a schedule. Unset fields are left unchanged.
+#[derive(Debug, Default)]
+pub struct PayoutScheduleUpdate<'a> {
+    pub recipient_address: Option<&'a str>,
+    pub amount: Option<i64>,
+    pub payment_id: Option<&'a str>,
+    pub callback_url: Option<&'a str>,
+    pub cadence: Option<&'a Cadence>,
+    pub is_paused: Option<bool>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+impl PayoutSchedule {
+    /// Creates a new schedule.
+    pub async fn create(
+        pool: &mut SqliteConnection,
+        new_schedule: &NewPayoutSchedule<'_>,
+    ) -> Result<Self, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let cadence = new_schedule.cadence.to_string();
+        let next_run_at = sqlite_timestamp(&new_schedule.next_run_at);
+        sqlx::query_as!(
+            PayoutSchedule,
+            r#"
+            INSERT INTO payout_schedules (
+                id, account_name, recipient_address, amount, payment_id, callback_url, cadence, is_paused,
+                next_run_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING
+                id,
+                account_name,
+                recipient_address,
+                amount,
+                payment_id,
+                callback_url,
+                cadence,
+                is_paused,
+                next_run_at as "next_run_at: DateTime<Utc>",
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            "#,
+            id,
+            new_schedule.account_name,
+            new_schedule.recipient_address,
+            new_schedule.amount,
+            new_schedule.payment_id,
+            new_schedule.callback_url,
+            cadence,
+            new_schedule.is_paused,
+            next_run_at,
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Finds a schedule by its ID.
+    pub async fn find_by_id(pool: &mut SqliteConnection, id: &str) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            PayoutSchedule,
+            r#"
+            SELECT
+                id,
+                account_name,
+                recipient_address,
+                amount,
+                payment_id,
+                callback_url,
+                cadence,
+                is_paused,
+                next_run_at as "next_run_at: DateTime<Utc>",
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM payout_schedules
+            WHERE id = ?
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Retrieves all schedules of an account, oldest first.
+    pub async fn find_by_account_name(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            PayoutSchedule,
+            r#"
+            SELECT
+                id,
+                account_name,
+                recipient_address,
+                amount,
+                payment_id,
+                callback_url,
+                cadence,
+                is_paused,
+                next_run_at as "next_run_at: DateTime<Utc>",
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM payout_schedules
+            WHERE account_name = ?
+            ORDER BY created_at, id
+            "#,
+            account_name
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Finds unpaused schedules with an occurrence due, the longest overdue first.
+    pub async fn find_due(pool: &mut SqliteConnection, limit: i64) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            PayoutSchedule,
+            r#"
+            SELECT
+                id,
+                account_name,
+                recipient_address,
+                amount,
+                payment_id,
+                callback_url,
+                cadence,
+                is_paused,
+                next_run_at as "next_run_at: DateTime<Utc>",
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM payout_schedules
+            WHERE is_paused = 0 AND next_run_at <= CURRENT_TIMESTAMP
+            ORDER BY next_run_at, id
+            LIMIT ?
+            "#,
+            limit
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Edits a schedule. Returns `false` if no such schedule exists.
+    pub async fn update(
+        pool: &mut SqliteConnection,
+        id: &str,
+        update: &PayoutScheduleUpdate<'_>,
+    ) -> Result<bool, sqlx::Error> {
+        let cadence = update.cadence.map(|cadence| cadence.to_string());
+        let next_run_at = update.next_run_at.as_ref().map(sqlite_timestamp);
+        let result = sqlx::query!(
+            r#"
+            UPDATE payout_schedules
+            SET recipient_address = COALESCE(?, recipient_address),
+                amount = COALESCE(?, amount),
+                payment_id = COALESCE(?, payment_id),
+                callback_url = COALESCE(?, callback_url),
+                cadence = COALESCE(?, cadence),
+                is_paused = COALESCE(?, is_paused),
+                next_run_at = COALESCE(?, next_run_at),
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+            update.recipient_address,
+            update.amount,
+            update.payment_id,
+            update.callback_url,
+            cadence,
+            update.is_paused,
+            next_run_at,
+            id,
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Moves a schedule on to its next occurrence, provided it is still at `from`.
+    /// Returns `false` if the schedule was edited or deleted in the meantime.
+    pub async fn advance(
+        pool: &mut SqliteConnection,
+        id: &str,
+        from: DateTime<Utc>,
+        next_run_at: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let from = sqlite_timestamp(&from);
+        let next_run_at = sqlite_timestamp(&next_run_at);
+        let result = sqlx::query!(
+            r#"
+            UPDATE payout_schedules
+            SET next_run_at = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND next_run_at = ? AND is_paused = 0
+            "#,
+            next_run_at,
+            id,
+            from,
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Deletes a schedule and its run history. Payments it already created are kept.
+    /// Returns `false` if no such schedule exists.
+    pub async fn delete(pool: &mut SqliteConnection, id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM payout_schedules WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// What happened to one occurrence of a schedule.
+#[derive(Debug, Clone)]
+pub struct PayoutRun {
+    pub schedule_id: String,
+    pub scheduled_for: DateTime<Utc>,
+    pub status: PayoutRunStatus,
+    pub payment_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PayoutRun {
+    /// Records the outcome of an occurrence. An occurrence that was already recorded is left as is.
+    pub async fn record(
+        pool: &mut SqliteConnection,
+        schedule_id: &str,
+        scheduled_for: DateTime<Utc>,
+        status: PayoutRunStatus,
+        payment_id: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let scheduled_for = sqlite_timestamp(&scheduled_for);
+        let status = status.to_string();
+        sqlx::query!(
+            r#"
+            INSERT INTO payout_schedule_runs (schedule_id, scheduled_for, status, payment_id)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (schedule_id, scheduled_for) DO NOTHING
+            "#,
+            schedule_id,
+            scheduled_for,
+            status,
+            payment_id,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Retrieves the most recent runs of a schedule, newest first.
+    pub async fn find_by_schedule_id(
+        pool: &mut SqliteConnection,
+        schedule_id: &str,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            PayoutRun,
+            r#"
+            SELECT
+                schedule_id,
+                scheduled_for as "scheduled_for: DateTime<Utc>",
+                status,
+                payment_id,
+                created_at as "created_at: DateTime<Utc>"
+            FROM payout_schedule_runs
+            WHERE schedule_id = ?
+            ORDER BY scheduled_for DESC
+            LIMIT ?
+            "#,
+            schedule_id,
+            limit,
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -0,0 +1,305 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use std::fmt;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    DeadLettered,
+}
+
+impl From<String> for WebhookDeliveryStatus {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "PENDING" => WebhookDeliveryStatus::Pending,
+            "DELIVERED" => WebhookDeliveryStatus::Delivered,
+            "DEAD_LETTERED" => WebhookDeliveryStatus::DeadLettered,
+            _ => panic!("Unknown WebhookDeliveryStatus: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for WebhookDeliveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WebhookDeliveryStatus::Pending => write!(f, "PENDING"),
+            WebhookDeliveryStatus::Delivered => write!(f, "DELIVERED"),
+            WebhookDeliveryStatus::DeadLettered => write!(f, "DEAD_LETTERED"),
+        }
+    }
+}
+
+/// A webhook that receives every terminal payment update of an account.
+#[derive(Debug, Clone)]
+pub struct WebhookSubscription {
+    pub id: String,
+    pub account_name: String,
+    pub url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookSubscription {
+    /// Subscribes `url` to the account's payment updates. Subscribing the same URL twice
+    /// returns the existing subscription.
+    pub async fn create(pool: &mut SqliteConnection, account_name: &str, url: &str) -> Result<Self, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        sqlx::query_as!(
+            WebhookSubscription,
+            r#"
+            INSERT INTO webhook_subscriptions (id, account_name, url)
+            VALUES (?, ?, ?)
+            ON CONFLICT (account_name, url) DO UPDATE SET url = excluded.url
+            RETURNING
+                id,
+                account_name,
+                url,
+                created_at as "created_at: DateTime<Utc>"
+            "#,
+            id,
+            account_name,
+            url,
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Finds a subscription by its ID.
+    pub async fn find_by_id(pool: &mut SqliteConnection, id: &str) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            WebhookSubscription,
+            r#"
+            SELECT
+                id,
+                account_name,
+                url,
+                created_at as "created_at: DateTime<Utc>"
+            FROM webhook_subscriptions
+            WHERE id = ?
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Retrieves all subscriptions of an account, oldest first.
+    pub async fn find_by_account_name(
+        pool: &mut SqliteConnection,
+        account_name: &str,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            WebhookSubscription,
+            r#"
+            SELECT
+                id,
+                account_name,
+                url,
+                created_at as "created_at: DateTime<Utc>"
+            FROM webhook_subscriptions
+            WHERE account_name = ?
+            ORDER BY created_at, id
+            "#,
+            account_name
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Deletes a subscription. Deliveries already in the outbox are still sent.
+    /// Returns `false` if no such subscription exists.
+    pub async fn delete(pool: &mut SqliteConnection, id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM webhook_subscriptions WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// A webhook delivery in the outbox.
+#[derive(Debug, Clone)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub payment_id: String,
+    pub event_type: String,
+    pub url: String,
+    pub payload: String,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WebhookDelivery {
+    /// Queues a delivery of each payment's current state to its `callback_url` and to every
+    /// webhook subscribed to its account. Must run after the status change it reports, in the
+    /// same transaction, so a delivery is queued if and only if the change is committed.
+    pub async fn enqueue_for_payments(pool: &mut SqliteConnection, payment_ids: &[String]) -> Result<(), sqlx::Error> {
+        Self::enqueue(pool, payment_ids, None).await
+    }
+
+    /// Queues a `payment.reorged` delivery for payments whose confirmed transaction left the best chain.
+    pub async fn enqueue_reorged_for_payments(
+        pool: &mut SqliteConnection,
+        payment_ids: &[String],
+    ) -> Result<(), sqlx::Error> {
+        Self::enqueue(pool, payment_ids, Some("payment.reorged")).await
+    }
+
+    /// Queues the deliveries as `event_type`, or as `payment.<status>` when not given.
+    async fn enqueue(
+        pool: &mut SqliteConnection,
+        payment_ids: &[String],
+        event_type: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let json = serde_json::to_string(payment_ids).unwrap();
+        let status_pending = WebhookDeliveryStatus::Pending.to_string();
+        sqlx::query!(
+            r#"
+            WITH targets (payment_id, url) AS (
+                SELECT id, callback_url
+                FROM payments
+                WHERE id IN (SELECT value FROM json_each(?)) AND callback_url IS NOT NULL
+                UNION
+                SELECT p.id, s.url
+                FROM payments p
+                JOIN webhook_subscriptions s ON s.account_name = p.account_name
+                WHERE p.id IN (SELECT value FROM json_each(?))
+            )
+            INSERT INTO webhook_deliveries (payment_id, event_type, url, payload, status)
+            SELECT
+                p.id,
+                COALESCE(?, 'payment.' || lower(p.status)),
+                t.url,
+                json_object(
+                    'event', COALESCE(?, 'payment.' || lower(p.status)),
+                    'created_at', strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+                    'payment', json_object(
+                        'payment_id', p.id,
+                        'client_id', p.client_id,
+                        'account_name', p.account_name,
+                        'status', p.status,
+                        'recipient_address', p.recipient_address,
+                        'amount', p.amount,
+                        'failure_reason', p.failure_reason,
+                        'failure_code', p.failure_code,
+                        'mined_height', pb.mined_height,
+                        'mined_header_hash', pb.mined_header_hash,
+                        'mined_timestamp', pb.mined_timestamp
+                    )
+                ),
+                ?
+            FROM targets t
+            JOIN payments p ON p.id = t.payment_id
+            LEFT JOIN payment_batches pb ON pb.id = p.payment_batch_id
+            ORDER BY p.id, t.url
+            "#,
+            json,
+            json,
+            event_type,
+            event_type,
+            status_pending,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Retrieves pending deliveries whose next attempt is due, oldest first.
+    pub async fn find_due(pool: &mut SqliteConnection, limit: i64) -> Result<Vec<Self>, sqlx::Error> {
+        let status_pending = WebhookDeliveryStatus::Pending.to_string();
+        sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT
+                id,
+                payment_id,
+                event_type,
+                url,
+                payload,
+                status,
+                attempts,
+                next_attempt_at as "next_attempt_at: DateTime<Utc>",
+                last_error,
+                delivered_at as "delivered_at: DateTime<Utc>",
+                created_at as "created_at: DateTime<Utc>",
+                updated_at as "updated_at: DateTime<Utc>"
+            FROM webhook_deliveries
+            WHERE status = ? AND next_attempt_at <= CURRENT_TIMESTAMP
+            ORDER BY id
+            LIMIT ?
+            "#,
+            status_pending,
+            limit,
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Marks a delivery as acknowledged by the receiver.
+    pub async fn mark_delivered(pool: &mut SqliteConnection, id: i64) -> Result<(), sqlx::Error> {
+        let status_delivered = WebhookDeliveryStatus::Delivered.to_string();
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?, attempts = attempts + 1, last_error = NULL,
+                delivered_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+            status_delivered,
+            id,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Records a failed attempt and schedules the next one `retry_in_secs` from now.
+    pub async fn schedule_retry(
+        pool: &mut SqliteConnection,
+        id: i64,
+        error: &str,
+        retry_in_secs: u64,
+    ) -> Result<(), sqlx::Error> {
+        let retry_modifier = format!("+{} seconds", retry_in_secs);
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET attempts = attempts + 1, last_error = ?,
+                next_attempt_at = datetime('now', ?), updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+            error,
+            retry_modifier,
+            id,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Records a failed final attempt. The delivery is kept as a dead letter and never retried.
+    pub async fn dead_letter(pool: &mut SqliteConnection, id: i64, error: &str) -> Result<(), sqlx::Error> {
+        let status_dead_lettered = WebhookDeliveryStatus::DeadLettered.to_string();
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?, attempts = attempts + 1, last_error = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+            status_dead_lettered,
+            error,
+            id,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -1,3 +1,8 @@
 pub mod api;
+pub mod base_node;
 pub mod db;
+pub mod signer;
 pub mod workers;
+
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -1,9 +1,21 @@
 use anyhow::anyhow;
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
 use minotari_client::apis::configuration::Configuration as MinotariConfiguration;
 use minotari_node_wallet_client::http::Client as BaseNodeClient;
-use minotari_payment_processor::{api, db, workers};
-use std::sync::Arc;
+use minotari_payment_processor::{
+    api,
+    db::{
+        self,
+        api_key::{ApiKey, ApiScope},
+        batch_policy::BatchPolicy,
+        payment_batch::{RetryBackoff, RetryStage},
+    },
+    signer::{SignerBackend, SignerConfig, Signers},
+    workers,
+};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+use tari_transaction_components::tari_common::configuration::Network;
 use tokio::{net::TcpListener, signal};
 use url::Url;
 
@@ -11,15 +23,28 @@ struct PaymentProcessorEnv {
     pub database_url: String,
     pub payment_receiver: String,
     pub base_node: String,
-    pub console_wallet_path: String,
-    pub console_wallet_password: String,
+    pub network: Network,
+    pub signer_config: SignerConfig,
     pub listen_ip: String,
     pub listen_port: u16,
+    pub webhook_signing_secret: Option<String>,
+    pub default_batch_policy: BatchPolicy,
+    pub batch_fee_margin_per_payment: Option<u64>,
+    pub max_fee_per_payment: Option<u64>,
+    pub unsigned_tx_retry_backoff: RetryBackoff,
+    pub sign_retry_backoff: RetryBackoff,
+    pub broadcast_retry_backoff: RetryBackoff,
+    pub utxo_lock_secs: Option<u64>,
     pub batch_creator_sleep_secs: Option<u64>,
     pub unsigned_tx_creator_sleep_secs: Option<u64>,
     pub transaction_signer_sleep_secs: Option<u64>,
     pub broadcaster_sleep_secs: Option<u64>,
     pub confirmation_checker_sleep_secs: Option<u64>,
+    pub dropped_tx_timeout_secs: Option<u64>,
+    pub lease_reaper_sleep_secs: Option<u64>,
+    pub webhook_dispatcher_sleep_secs: Option<u64>,
+    pub payout_scheduler_sleep_secs: Option<u64>,
+    pub reorg_watcher_sleep_secs: Option<u64>,
 }
 
 impl PaymentProcessorEnv {
@@ -29,14 +54,67 @@ impl PaymentProcessorEnv {
         let payment_receiver =
             std::env::var("PAYMENT_RECEIVER").map_err(|_| anyhow!("PAYMENT_RECEIVER environment variable not set"))?;
         let base_node = std::env::var("BASE_NODE").map_err(|_| anyhow!("BASE_NODE environment variable not set"))?;
-        let console_wallet_path = std::env::var("CONSOLE_WALLET_PATH")
-            .map_err(|_| anyhow!("CONSOLE_WALLET_PATH environment variable not set"))?;
-        let console_wallet_password = std::env::var("CONSOLE_WALLET_PASSWORD")
-            .map_err(|_| anyhow!("CONSOLE_WALLET_PASSWORD environment variable not set"))?;
+        // Required rather than defaulted, so a test deployment cannot end up paying on mainnet.
+        let network =
+            std::env::var("TARI_NETWORK").map_err(|_| anyhow!("TARI_NETWORK environment variable not set"))?;
+        let network = Network::from_str(&network).map_err(|e| anyhow!("Invalid TARI_NETWORK {}: {}", network, e))?;
+        let signer_config = SignerConfig {
+            default_backend: std::env::var("SIGNER_BACKEND")
+                .ok()
+                .map(|s| SignerBackend::from_str(&s))
+                .transpose()
+                .map_err(|e| anyhow!("Invalid SIGNER_BACKEND: {}", e))?
+                .unwrap_or(SignerBackend::ConsoleWallet),
+            account_backends: std::env::var("SIGNER_ACCOUNT_BACKENDS")
+                .ok()
+                .map(|s| parse_account_backends(&s))
+                .transpose()
+                .map_err(|e| anyhow!("Invalid SIGNER_ACCOUNT_BACKENDS: {}", e))?
+                .unwrap_or_default(),
+            network,
+            console_wallet_path: std::env::var("CONSOLE_WALLET_PATH").ok(),
+            console_wallet_password: std::env::var("CONSOLE_WALLET_PASSWORD").ok(),
+            key_file: std::env::var("SIGNER_KEY_FILE").ok(),
+            key_password: std::env::var("SIGNER_KEY_PASSWORD").ok(),
+            remote_url: std::env::var("REMOTE_SIGNER_URL").ok(),
+            remote_token: std::env::var("REMOTE_SIGNER_TOKEN").ok().filter(|s| !s.is_empty()),
+        };
         let listen_ip = std::env::var("LISTEN_IP").unwrap_or_else(|_| "0.0.0.0".to_string());
         let listen_port = std::env::var("LISTEN_PORT")
             .unwrap_or_else(|_| "9145".to_string())
             .parse::<u16>()?;
+        let webhook_signing_secret = std::env::var("WEBHOOK_SIGNING_SECRET").ok().filter(|s| !s.is_empty());
+
+        let defaults = BatchPolicy::default();
+        let default_batch_policy = BatchPolicy {
+            max_recipients: std::env::var("BATCH_MAX_RECIPIENTS")
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .unwrap_or(defaults.max_recipients),
+            max_total_amount: std::env::var("BATCH_MAX_TOTAL_AMOUNT")
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok()),
+            min_recipients: std::env::var("BATCH_MIN_RECIPIENTS")
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .unwrap_or(defaults.min_recipients),
+            max_age_secs: std::env::var("BATCH_MAX_AGE_SECS")
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok()),
+        };
+        default_batch_policy
+            .validate()
+            .map_err(|e| anyhow!("Invalid default batch policy: {}", e))?;
+        let batch_fee_margin_per_payment = std::env::var("BATCH_FEE_MARGIN_PER_PAYMENT")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok());
+        let max_fee_per_payment = std::env::var("MAX_FEE_PER_PAYMENT")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok());
+        let unsigned_tx_retry_backoff = retry_backoff_from_env("UNSIGNED_TX", RetryStage::CreateUnsignedTx);
+        let sign_retry_backoff = retry_backoff_from_env("SIGN", RetryStage::Sign);
+        let broadcast_retry_backoff = retry_backoff_from_env("BROADCAST", RetryStage::Broadcast);
+        let utxo_lock_secs = std::env::var("UTXO_LOCK_SECS").ok().and_then(|s| s.parse::<u64>().ok());
 
         let batch_creator_sleep_secs = std::env::var("BATCH_CREATOR_SLEEP_SECS")
             .ok()
@@ -53,33 +131,129 @@ impl PaymentProcessorEnv {
         let confirmation_checker_sleep_secs = std::env::var("CONFIRMATION_CHECKER_SLEEP_SECS")
             .ok()
             .and_then(|s| s.parse::<u64>().ok());
+        let dropped_tx_timeout_secs = std::env::var("DROPPED_TX_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok());
+        let lease_reaper_sleep_secs = std::env::var("LEASE_REAPER_SLEEP_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok());
+        let webhook_dispatcher_sleep_secs = std::env::var("WEBHOOK_DISPATCHER_SLEEP_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok());
+        let payout_scheduler_sleep_secs = std::env::var("PAYOUT_SCHEDULER_SLEEP_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok());
+        let reorg_watcher_sleep_secs = std::env::var("REORG_WATCHER_SLEEP_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok());
 
         Ok(Self {
             database_url,
             payment_receiver,
             base_node,
-            console_wallet_path,
-            console_wallet_password,
+            network,
+            signer_config,
             listen_ip,
             listen_port,
+            webhook_signing_secret,
+            default_batch_policy,
+            batch_fee_margin_per_payment,
+            max_fee_per_payment,
+            unsigned_tx_retry_backoff,
+            sign_retry_backoff,
+            broadcast_retry_backoff,
+            utxo_lock_secs,
             batch_creator_sleep_secs,
             unsigned_tx_creator_sleep_secs,
             transaction_signer_sleep_secs,
             broadcaster_sleep_secs,
             confirmation_checker_sleep_secs,
+            dropped_tx_timeout_secs,
+            lease_reaper_sleep_secs,
+            webhook_dispatcher_sleep_secs,
+            payout_scheduler_sleep_secs,
+            reorg_watcher_sleep_secs,
         })
     }
 }
 
+/// Reads `<PREFIX>_RETRY_BASE_SECS` and `<PREFIX>_RETRY_MAX_SECS`, falling back to the stage's default backoff.
+fn retry_backoff_from_env(prefix: &str, stage: RetryStage) -> RetryBackoff {
+    let defaults = stage.default_backoff();
+    let secs = |name: &str| {
+        std::env::var(format!("{}_{}", prefix, name))
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+    };
+    RetryBackoff {
+        base_secs: secs("RETRY_BASE_SECS").unwrap_or(defaults.base_secs),
+        max_secs: secs("RETRY_MAX_SECS").unwrap_or(defaults.max_secs),
+    }
+}
+
+/// Parses `account=backend` pairs separated by commas, e.g. `treasury=remote,payroll=local`.
+fn parse_account_backends(value: &str) -> Result<HashMap<String, SignerBackend>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (account_name, backend) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected account=backend, got '{}'", pair))?;
+            Ok((account_name.trim().to_string(), backend.trim().parse()?))
+        })
+        .collect()
+}
+
+#[derive(Parser)]
+#[command(version, about = "Minotari Payment Processor")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage the API keys used to authenticate HTTP API requests.
+    #[command(subcommand)]
+    ApiKey(ApiKeyCommand),
+}
+
+#[derive(Subcommand)]
+enum ApiKeyCommand {
+    /// Issues a new API key. The key is printed once and cannot be retrieved again.
+    Issue {
+        /// Who the key is for. Recorded as the operator for admin actions.
+        #[arg(long)]
+        name: String,
+        /// Account the key may act on. Repeat for several accounts, or use "*" for all of them.
+        #[arg(long = "account", required = true)]
+        accounts: Vec<String>,
+        /// Scope granted to the key: payments:read, payments:write or admin. Repeat for several scopes.
+        #[arg(long = "scope", required = true)]
+        scopes: Vec<ApiScope>,
+    },
+    /// Revokes an API key by its ID.
+    Revoke { id: String },
+    /// Lists all API keys, including revoked ones.
+    List,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv().ok();
+    let cli = Cli::parse();
+    if let Some(Command::ApiKey(command)) = cli.command {
+        return run_api_key_command(command).await;
+    }
     let env = PaymentProcessorEnv::from_env()?;
 
-    println!("Starting Minotari Payment Processor...");
+    println!("Starting Minotari Payment Processor on the {} network...", env.network);
 
     let db_pool = db::init_db(&env.database_url).await?;
     println!("Database initialized.");
+    api::normalize_stored_addresses(&db_pool, env.network).await?;
 
     let client_config = Arc::new(MinotariConfiguration {
         base_path: env.payment_receiver,
@@ -89,36 +263,69 @@ async fn main() -> anyhow::Result<()> {
     let base_node_url = Url::parse(&env.base_node)?;
     let base_node_client = BaseNodeClient::new(base_node_url.clone(), base_node_url.clone());
 
+    let signers = Arc::new(Signers::from_config(&env.signer_config).await?);
+
+    // Batches left in flight by a previous run must be recovered before any worker claims new ones.
+    workers::lease_reaper::recover_on_startup(&db_pool).await?;
+
     // Spawn workers
     tokio::spawn(workers::batch_creator::run(
         db_pool.clone(),
+        client_config.clone(),
+        env.default_batch_policy.clone(),
+        env.batch_fee_margin_per_payment,
         env.batch_creator_sleep_secs,
     ));
     tokio::spawn(workers::unsigned_tx_creator::run(
         db_pool.clone(),
         client_config.clone(),
+        env.unsigned_tx_retry_backoff,
+        env.utxo_lock_secs,
         env.unsigned_tx_creator_sleep_secs,
     ));
     tokio::spawn(workers::transaction_signer::run(
         db_pool.clone(),
-        env.console_wallet_path.clone(),
-        env.console_wallet_password.clone(),
+        signers.clone(),
+        env.sign_retry_backoff,
         env.transaction_signer_sleep_secs,
     ));
     tokio::spawn(workers::broadcaster::run(
         db_pool.clone(),
         base_node_client.clone(),
+        env.network,
+        env.max_fee_per_payment,
+        env.broadcast_retry_backoff,
         env.broadcaster_sleep_secs,
     ));
     tokio::spawn(workers::confirmation_checker::run(
         db_pool.clone(),
         base_node_client.clone(),
+        env.dropped_tx_timeout_secs,
         env.confirmation_checker_sleep_secs,
     ));
+    tokio::spawn(workers::reorg_watcher::run(
+        db_pool.clone(),
+        base_node_client.clone(),
+        env.reorg_watcher_sleep_secs,
+    ));
+    tokio::spawn(workers::lease_reaper::run(db_pool.clone(), env.lease_reaper_sleep_secs));
+    tokio::spawn(workers::payout_scheduler::run(
+        db_pool.clone(),
+        env.payout_scheduler_sleep_secs,
+    ));
+    if let Some(webhook_signing_secret) = env.webhook_signing_secret.clone() {
+        tokio::spawn(workers::webhook_dispatcher::run(
+            db_pool.clone(),
+            webhook_signing_secret,
+            env.webhook_dispatcher_sleep_secs,
+        ));
+    } else {
+        println!("WEBHOOK_SIGNING_SECRET not set, webhook deliveries will be queued but not sent.");
+    }
     println!("Minotari Payment Processor started. Press Ctrl+C to shut down.");
 
     // Create Axum API router
-    let app = api::create_router(db_pool.clone());
+    let app = api::create_router(db_pool.clone(), env.network, env.default_batch_policy, signers);
     let addr = format!("{}:{}", env.listen_ip, env.listen_port);
     let listener = TcpListener::bind(&addr).await?;
     println!("Axum API server listening on {}", addr);
@@ -131,3 +338,44 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+async fn run_api_key_command(command: ApiKeyCommand) -> anyhow::Result<()> {
+    let database_url =
+        std::env::var("DATABASE_URL").map_err(|_| anyhow!("DATABASE_URL environment variable not set"))?;
+    let db_pool = db::init_db(&database_url).await?;
+    let mut conn = db_pool.acquire().await?;
+
+    match command {
+        ApiKeyCommand::Issue { name, accounts, scopes } => {
+            let (api_key, secret) = ApiKey::create(&mut conn, &name, &accounts, &scopes).await?;
+            println!("Issued API key {} for {}.", api_key.id, api_key.name);
+            println!("Key: {}", secret);
+            println!("Store it now, it cannot be shown again.");
+        },
+        ApiKeyCommand::Revoke { id } => {
+            if !ApiKey::revoke(&mut conn, &id).await? {
+                return Err(anyhow!("No active API key with ID {}", id));
+            }
+            println!("Revoked API key {}.", id);
+        },
+        ApiKeyCommand::List => {
+            for api_key in ApiKey::list(&mut conn).await? {
+                let scopes: Vec<String> = api_key.scopes.iter().map(ApiScope::to_string).collect();
+                println!(
+                    "{}  {}  {}...  accounts: {}  scopes: {}{}",
+                    api_key.id,
+                    api_key.name,
+                    api_key.key_prefix,
+                    api_key.account_names.join(","),
+                    scopes.join(","),
+                    api_key
+                        .revoked_at
+                        .map(|revoked_at| format!("  revoked at {}", revoked_at))
+                        .unwrap_or_default()
+                );
+            }
+        },
+    }
+
+    Ok(())
+}
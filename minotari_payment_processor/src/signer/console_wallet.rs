@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use std::io::Write;
+use tempfile::NamedTempFile;
+use tokio::{fs, process::Command};
+
+use crate::signer::{SigningError, SigningRequest, TransactionSigner};
+
+/// Error output of the console wallet that means it could not sign right now rather than that it
+/// refused, e.g. while another process holds the wallet database. Matched case-insensitively.
+const TRANSIENT_ERRORS: &[&str] = &[
+    "database is locked",
+    "lock file",
+    "resource temporarily unavailable",
+    "connection refused",
+    "timed out",
+];
+
+/// Signs by running `minotari_console_wallet sign-one-sided-transaction` on temporary files.
+pub struct ConsoleWalletSigner {
+    console_wallet_path: String,
+    console_wallet_password: String,
+}
+
+impl ConsoleWalletSigner {
+    pub fn new(console_wallet_path: String, console_wallet_password: String) -> Self {
+        ConsoleWalletSigner {
+            console_wallet_path,
+            console_wallet_password,
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for ConsoleWalletSigner {
+    async fn sign(&self, request: &SigningRequest<'_>) -> Result<String, SigningError> {
+        let io_error = |e: std::io::Error| SigningError::Unavailable(format!("Temporary file error: {}", e));
+
+        // Create temporary input file
+        let mut input_file = NamedTempFile::with_prefix("unsigned-tx-").map_err(io_error)?;
+        input_file
+            .write_all(request.unsigned_tx_json.as_bytes())
+            .map_err(io_error)?;
+        let input_file_path = input_file.path().to_path_buf();
+
+        // Create temporary output file
+        let output_file = NamedTempFile::with_prefix("signed-tx-").map_err(io_error)?;
+        let output_file_path = output_file.path().to_path_buf();
+
+        // The child is killed if the caller gives up waiting and drops this future.
+        let output = Command::new(&self.console_wallet_path)
+            .env("MINOTARI_WALLET_PASSWORD", &self.console_wallet_password)
+            .arg("sign-one-sided-transaction")
+            .arg("--input-file")
+            .arg(&input_file_path)
+            .arg("--output-file")
+            .arg(&output_file_path)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| SigningError::Rejected(format!("CLI execution error: {:?}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            let lowercase_stderr = stderr.to_lowercase();
+            // Without an exit code the wallet was killed by a signal, which says nothing about the transaction.
+            if output.status.code().is_none() || TRANSIENT_ERRORS.iter().any(|e| lowercase_stderr.contains(e)) {
+                return Err(SigningError::Unavailable(stderr));
+            }
+            return Err(SigningError::Rejected(stderr));
+        }
+        fs::read_to_string(&output_file_path).await.map_err(io_error)
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tari_transaction_components::tari_common::configuration::Network;
+use tari_transaction_components::tari_common_types::seeds::cipher_seed::CipherSeed;
+use tari_transaction_components::{
+    key_manager::{MemoryDbKeyManager, create_memory_db_key_manager_from_seed},
+    offline_signing::{models::PrepareOneSidedTransactionForSigningResult, sign_locked_transaction},
+};
+use tari_utilities::SafePassword;
+use tokio::fs;
+
+use crate::signer::{SigningError, SigningRequest, TransactionSigner};
+
+/// Key derivation rounds for the in-memory key manager, the same as the console wallet uses.
+const KEY_MANAGER_ROUNDS: usize = 64;
+
+/// Signs in-process with `tari_transaction_components` offline signing, so no wallet binary is
+/// needed on the host. The key file holds the wallet's cipher seed, enciphered with the key
+/// password, as hex.
+pub struct LocalSigner {
+    key_manager: Arc<MemoryDbKeyManager>,
+    network: Network,
+}
+
+impl LocalSigner {
+    pub async fn from_key_file(path: &str, password: &str, network: Network) -> Result<Self, anyhow::Error> {
+        let contents = fs::read_to_string(path)
+            .await
+            .map_err(|e| anyhow!("Failed to read key file {}: {}", path, e))?;
+        let enciphered = hex::decode(contents.trim()).map_err(|e| anyhow!("Key file is not hex: {}", e))?;
+        let seed = CipherSeed::from_enciphered_bytes(&enciphered, Some(SafePassword::from(password)))
+            .map_err(|e| anyhow!("Failed to decrypt key file: {}", e))?;
+        let key_manager = create_memory_db_key_manager_from_seed(seed, KEY_MANAGER_ROUNDS)
+            .map_err(|e| anyhow!("Failed to set up key manager: {}", e))?;
+
+        Ok(LocalSigner {
+            key_manager: Arc::new(key_manager),
+            network,
+        })
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for LocalSigner {
+    async fn sign(&self, request: &SigningRequest<'_>) -> Result<String, SigningError> {
+        let unsigned_tx = PrepareOneSidedTransactionForSigningResult::from_json(request.unsigned_tx_json)
+            .map_err(|e| SigningError::Rejected(format!("Invalid unsigned transaction: {}", e)))?;
+
+        // Signing is CPU-bound, so it is kept off the async worker threads.
+        let key_manager = self.key_manager.clone();
+        let network = self.network;
+        let signed_tx =
+            tokio::task::spawn_blocking(move || sign_locked_transaction(&key_manager, network, unsigned_tx))
+                .await
+                .map_err(|e| SigningError::Unavailable(format!("Signing task failed: {}", e)))?
+                .map_err(|e| SigningError::Rejected(e.to_string()))?;
+
+        signed_tx
+            .to_json()
+            .map_err(|e| SigningError::Rejected(format!("Failed to serialize signed transaction: {}", e)))
+    }
+}
@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::signer::{SigningError, SigningRequest, TransactionSigner};
+
+/// Returns a fake signed transaction derived only from the request, so the same batch always gets
+/// the same output. Lets the pipeline be exercised without a wallet. Its output is not a valid
+/// transaction, so the broadcaster holds the batch in 'VERIFICATION_FAILED'.
+#[derive(Debug, Default)]
+pub struct MockSigner {
+    failure: Option<Failure>,
+}
+
+#[derive(Debug)]
+enum Failure {
+    Rejected(String),
+    Unavailable(String),
+}
+
+impl MockSigner {
+    pub fn new() -> Self {
+        MockSigner::default()
+    }
+
+    /// A signer that rejects every transaction with the given reason.
+    pub fn rejecting(reason: &str) -> Self {
+        MockSigner {
+            failure: Some(Failure::Rejected(reason.to_string())),
+        }
+    }
+
+    /// A signer that cannot be reached, failing every transaction with the given reason.
+    pub fn unavailable(reason: &str) -> Self {
+        MockSigner {
+            failure: Some(Failure::Unavailable(reason.to_string())),
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for MockSigner {
+    async fn sign(&self, request: &SigningRequest<'_>) -> Result<String, SigningError> {
+        match &self.failure {
+            Some(Failure::Rejected(reason)) => return Err(SigningError::Rejected(reason.clone())),
+            Some(Failure::Unavailable(reason)) => return Err(SigningError::Unavailable(reason.clone())),
+            None => {},
+        }
+        let signature = hex::encode(Sha256::digest(request.unsigned_tx_json.as_bytes()));
+        Ok(json!({
+            "mock": true,
+            "batch_id": request.batch_id,
+            "account_name": request.account_name,
+            "signature": signature,
+        })
+        .to_string())
+    }
+}
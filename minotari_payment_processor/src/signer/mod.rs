@@ -0,0 +1,191 @@
+//! Backends that sign the unsigned transactions prepared by the Payment Receiver.
+
+mod console_wallet;
+mod local;
+mod mock;
+mod remote;
+mod verification;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use std::{collections::HashMap, fmt, str::FromStr, sync::Arc};
+use tari_transaction_components::tari_common::configuration::Network;
+use thiserror::Error;
+
+pub use console_wallet::ConsoleWalletSigner;
+pub use local::LocalSigner;
+pub use mock::MockSigner;
+pub use remote::RemoteSigner;
+pub use verification::{verify_payouts, verify_signed_tx};
+
+/// A batch's transaction to be signed.
+#[derive(Debug, Clone)]
+pub struct SigningRequest<'a> {
+    pub batch_id: &'a str,
+    pub account_name: &'a str,
+    /// The `PrepareOneSidedTransactionForSigningResult` returned by the Payment Receiver, as JSON.
+    pub unsigned_tx_json: &'a str,
+}
+
+#[derive(Debug, Error)]
+pub enum SigningError {
+    /// The signer refused or failed to sign the transaction. Retrying will not help, so the batch fails.
+    #[error("Signing rejected: {0}")]
+    Rejected(String),
+    /// The signer could not be reached or was busy. The batch is signed again on a later pass.
+    #[error("Signer unavailable: {0}")]
+    Unavailable(String),
+}
+
+/// Signs one-sided transactions. Implementations must not keep any state between calls that a
+/// retry of the same batch depends on.
+#[async_trait]
+pub trait TransactionSigner: Send + Sync {
+    /// Returns the `SignedOneSidedTransactionResult` for the request, as JSON.
+    async fn sign(&self, request: &SigningRequest<'_>) -> Result<String, SigningError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignerBackend {
+    /// Runs `minotari_console_wallet sign-one-sided-transaction`.
+    ConsoleWallet,
+    /// Signs in-process with a wallet seed read from an encrypted key file.
+    Local,
+    /// Sends the transaction to a signing service over HTTP.
+    Remote,
+    /// Returns a deterministic fake signature, for tests. Its output fails verification before broadcast.
+    Mock,
+    /// Nothing signs automatically. The unsigned transaction is exported through the API, signed
+    /// on an offline machine and uploaded back.
+    Manual,
+}
+
+impl FromStr for SignerBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "console_wallet" => Ok(SignerBackend::ConsoleWallet),
+            "local" => Ok(SignerBackend::Local),
+            "remote" => Ok(SignerBackend::Remote),
+            "mock" => Ok(SignerBackend::Mock),
+            "manual" => Ok(SignerBackend::Manual),
+            _ => Err(format!(
+                "Unknown signer backend '{}', expected console_wallet, local, remote, mock or manual",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SignerBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignerBackend::ConsoleWallet => write!(f, "console_wallet"),
+            SignerBackend::Local => write!(f, "local"),
+            SignerBackend::Remote => write!(f, "remote"),
+            SignerBackend::Mock => write!(f, "mock"),
+            SignerBackend::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+/// Which backend signs each account's transactions, and the settings of those backends. Only the
+/// settings of backends that are in use are required.
+#[derive(Debug, Clone)]
+pub struct SignerConfig {
+    pub default_backend: SignerBackend,
+    pub account_backends: HashMap<String, SignerBackend>,
+    pub network: Network,
+    pub console_wallet_path: Option<String>,
+    pub console_wallet_password: Option<String>,
+    pub key_file: Option<String>,
+    pub key_password: Option<String>,
+    pub remote_url: Option<String>,
+    pub remote_token: Option<String>,
+}
+
+/// The signer of every account.
+pub struct Signers {
+    default: SignerBackend,
+    accounts: HashMap<String, SignerBackend>,
+    signers: HashMap<SignerBackend, Arc<dyn TransactionSigner>>,
+}
+
+impl Signers {
+    /// Sets up each backend in use once, failing if any of its settings are missing or invalid.
+    pub async fn from_config(config: &SignerConfig) -> Result<Self, anyhow::Error> {
+        let mut signers: HashMap<SignerBackend, Arc<dyn TransactionSigner>> = HashMap::new();
+        for backend in std::iter::once(&config.default_backend).chain(config.account_backends.values()) {
+            if !signers.contains_key(backend) {
+                let signer = create_signer(*backend, config)
+                    .await
+                    .map_err(|e| anyhow!("Failed to set up the {} signer: {}", backend, e))?;
+                if let Some(signer) = signer {
+                    signers.insert(*backend, signer);
+                }
+            }
+        }
+
+        Ok(Signers {
+            default: config.default_backend,
+            accounts: config.account_backends.clone(),
+            signers,
+        })
+    }
+
+    /// Returns the backend configured for the account.
+    pub fn backend_for(&self, account_name: &str) -> SignerBackend {
+        self.accounts.get(account_name).copied().unwrap_or(self.default)
+    }
+
+    /// Returns the backend that signs the account's transactions, or `None` if they are signed
+    /// manually.
+    pub fn for_account(&self, account_name: &str) -> Option<(SignerBackend, &dyn TransactionSigner)> {
+        let backend = self.backend_for(account_name);
+        self.signers.get(&backend).map(|signer| (backend, signer.as_ref()))
+    }
+}
+
+#[cfg(test)]
+impl Signers {
+    /// Signs the transactions of every account with `signer`, as the mock backend.
+    pub(crate) fn mock(signer: MockSigner) -> Self {
+        Signers {
+            default: SignerBackend::Mock,
+            accounts: HashMap::new(),
+            signers: HashMap::from([(SignerBackend::Mock, Arc::new(signer) as Arc<dyn TransactionSigner>)]),
+        }
+    }
+}
+
+async fn create_signer(
+    backend: SignerBackend,
+    config: &SignerConfig,
+) -> Result<Option<Arc<dyn TransactionSigner>>, anyhow::Error> {
+    let required = |value: &Option<String>, name: &str| {
+        value
+            .clone()
+            .ok_or_else(|| anyhow!("{} environment variable not set", name))
+    };
+    Ok(Some(match backend {
+        SignerBackend::ConsoleWallet => Arc::new(ConsoleWalletSigner::new(
+            required(&config.console_wallet_path, "CONSOLE_WALLET_PATH")?,
+            required(&config.console_wallet_password, "CONSOLE_WALLET_PASSWORD")?,
+        )),
+        SignerBackend::Local => Arc::new(
+            LocalSigner::from_key_file(
+                &required(&config.key_file, "SIGNER_KEY_FILE")?,
+                &required(&config.key_password, "SIGNER_KEY_PASSWORD")?,
+                config.network,
+            )
+            .await?,
+        ),
+        SignerBackend::Remote => Arc::new(RemoteSigner::new(
+            &required(&config.remote_url, "REMOTE_SIGNER_URL")?,
+            config.remote_token.clone(),
+        )?),
+        SignerBackend::Mock => Arc::new(MockSigner::new()),
+        SignerBackend::Manual => return Ok(None),
+    }))
+}
@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode, header};
+use serde_json::json;
+use url::Url;
+
+use crate::signer::{SigningError, SigningRequest, TransactionSigner};
+
+/// Sends transactions to a signing service. The service receives a `POST` of
+/// `{"batch_id", "account_name", "unsigned_tx"}` and answers `200 OK` with the signed transaction
+/// as the body. A `4xx` answer rejects the transaction, except for answers about the request rather than
+/// the transaction, see [`signing_result`]. Anything else is retried.
+pub struct RemoteSigner {
+    client: Client,
+    url: Url,
+    token: Option<String>,
+}
+
+impl RemoteSigner {
+    pub fn new(url: &str, token: Option<String>) -> Result<Self, anyhow::Error> {
+        let url = Url::parse(url)?;
+        Ok(RemoteSigner {
+            client: Client::new(),
+            url,
+            token,
+        })
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for RemoteSigner {
+    async fn sign(&self, request: &SigningRequest<'_>) -> Result<String, SigningError> {
+        let unsigned_tx: serde_json::Value = serde_json::from_str(request.unsigned_tx_json)
+            .map_err(|e| SigningError::Rejected(format!("Invalid unsigned transaction: {}", e)))?;
+        let body = json!({
+            "batch_id": request.batch_id,
+            "account_name": request.account_name,
+            "unsigned_tx": unsigned_tx,
+        });
+
+        let mut http_request = self
+            .client
+            .post(self.url.clone())
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        if let Some(token) = &self.token {
+            http_request = http_request.bearer_auth(token);
+        }
+        let response = http_request
+            .send()
+            .await
+            .map_err(|e| SigningError::Unavailable(format!("Request to remote signer failed: {}", e)))?;
+
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| SigningError::Unavailable(format!("Failed to read remote signer response: {}", e)))?;
+        signing_result(status, text)
+    }
+}
+
+/// Maps the signing service's answer onto the signed transaction or a [`SigningError`]. A timeout or rate limit
+/// says nothing about the transaction, and neither does a refused token, which an operator has to fix in
+/// `REMOTE_SIGNER_TOKEN` or the service; the batch is retried until then rather than failed.
+fn signing_result(status: StatusCode, text: String) -> Result<String, SigningError> {
+    match status {
+        StatusCode::OK => Ok(text),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(SigningError::Unavailable(format!(
+            "Remote signer refused our credentials with {}, check REMOTE_SIGNER_TOKEN: {}",
+            status, text
+        ))),
+        StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS => Err(SigningError::Unavailable(format!(
+            "Remote signer answered {}: {}",
+            status, text
+        ))),
+        status if status.is_client_error() => Err(SigningError::Rejected(format!(
+            "Remote signer answered {}: {}",
+            status, text
+        ))),
+        status => Err(SigningError::Unavailable(format!(
+            "Remote signer answered {}: {}",
+            status, text
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_answers_about_the_transaction_reject_it() {
+        let cases = [
+            (StatusCode::BAD_REQUEST, false),
+            (StatusCode::NOT_FOUND, false),
+            (StatusCode::UNPROCESSABLE_ENTITY, false),
+            (StatusCode::UNAUTHORIZED, true),
+            (StatusCode::FORBIDDEN, true),
+            (StatusCode::REQUEST_TIMEOUT, true),
+            (StatusCode::TOO_MANY_REQUESTS, true),
+            (StatusCode::INTERNAL_SERVER_ERROR, true),
+            (StatusCode::SERVICE_UNAVAILABLE, true),
+        ];
+        for (status, retried) in cases {
+            match signing_result(status, "refused".to_string()) {
+                Err(SigningError::Unavailable(_)) => assert!(retried, "{} was retried", status),
+                Err(SigningError::Rejected(_)) => assert!(!retried, "{} was rejected", status),
+                Ok(_) => panic!("{} was taken as signed", status),
+            }
+        }
+        assert_eq!(signing_result(StatusCode::OK, "signed".to_string()).unwrap(), "signed");
+    }
+
+    #[test]
+    fn refused_token_names_the_setting_to_fix() {
+        let Err(SigningError::Unavailable(message)) = signing_result(StatusCode::UNAUTHORIZED, String::new()) else {
+            panic!("401 was not retried");
+        };
+        assert!(message.contains("REMOTE_SIGNER_TOKEN"), "{}", message);
+    }
+}
@@ -0,0 +1,415 @@
+use std::{collections::HashMap, str::FromStr};
+use tari_transaction_components::{
+    consensus::ConsensusManager,
+    crypto_factories::CryptoFactories,
+    offline_signing::models::SignedOneSidedTransactionResult,
+    tari_common::configuration::Network,
+    tari_common_types::tari_address::TariAddress,
+    tari_script::{Opcode, TariScript},
+    transaction_components::Transaction,
+    validation::transaction::TransactionInternalConsistencyValidator,
+};
+use tari_utilities::byte_array::ByteArray;
+
+use crate::db::payment::Payment;
+
+/// Checks that `signed_tx_json` is a signed transaction for `unsigned_tx_json`, so a transaction signed outside
+/// the processor cannot stand in for a different batch. Returns the reason when it is not.
+pub fn verify_signed_tx(
+    unsigned_tx_json: &str,
+    signed_tx_json: &str,
+) -> Result<SignedOneSidedTransactionResult, String> {
+    let unsigned: serde_json::Value =
+        serde_json::from_str(unsigned_tx_json).map_err(|e| format!("Invalid unsigned transaction: {}", e))?;
+    let signed: serde_json::Value =
+        serde_json::from_str(signed_tx_json).map_err(|e| format!("Signed transaction is not JSON: {}", e))?;
+
+    // The signed result carries the request it was signed from, which must be this batch's unsigned transaction.
+    match signed.get("request") {
+        Some(request) if *request == unsigned => {},
+        Some(_) => return Err("Signed transaction was signed from a different unsigned transaction".to_string()),
+        None => return Err("Signed transaction does not include the request it was signed from".to_string()),
+    }
+
+    let signed_tx = SignedOneSidedTransactionResult::from_json(signed_tx_json)
+        .map_err(|e| format!("Invalid signed transaction: {}", e))?;
+    if signed_tx.signed_transaction.transaction.body.kernels().is_empty() {
+        return Err("Signed transaction has no kernel".to_string());
+    }
+
+    Ok(signed_tx)
+}
+
+/// Checks a verified signed transaction against the payments of its batch. The prepared transaction must pay
+/// exactly the batch's recipients and amounts, and the transaction must pass the base node's internal
+/// consistency checks, so its commitments balance and its kernel signatures are valid. It must have an output
+/// per payment plus at most one change output, every recipient must be paid by a one-sided output for each of
+/// its payments, and its fee must not exceed `max_fee_per_payment` per payment. Returns the reason when it fails.
+pub fn verify_payouts(
+    unsigned_tx_json: &str,
+    signed_tx: &SignedOneSidedTransactionResult,
+    payments: &[Payment],
+    network: Network,
+    max_fee_per_payment: u64,
+) -> Result<(), String> {
+    let unsigned: serde_json::Value =
+        serde_json::from_str(unsigned_tx_json).map_err(|e| format!("Invalid unsigned transaction: {}", e))?;
+
+    // Output amounts are hidden in commitments, so the payouts are read from the recipients PR prepared the
+    // transaction for. `verify_signed_tx` has already tied the signed transaction to that preparation.
+    let requested: Vec<(String, i64)> = payments
+        .iter()
+        .map(|payment| (payment.recipient_address.clone(), payment.amount))
+        .collect();
+    check_recipients(prepared_payouts(&unsigned)?, requested)?;
+
+    let transaction = &signed_tx.signed_transaction.transaction;
+    let body = &transaction.body;
+    check_output_count(body.outputs().len(), payments.len())?;
+    let recipient_keys = payments
+        .iter()
+        .map(|payment| {
+            TariAddress::from_str(&payment.recipient_address)
+                .map(|address| address.public_spend_key().as_bytes().to_vec())
+                .map_err(|e| format!("Invalid recipient address {}: {}", payment.recipient_address, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let output_keys: Vec<Option<Vec<u8>>> = body
+        .outputs()
+        .iter()
+        .map(|output| one_sided_key(&output.script))
+        .collect();
+    check_recipient_outputs(&recipient_keys, &output_keys)?;
+
+    let fee: u64 = body.kernels().iter().map(|kernel| kernel.fee.as_u64()).sum();
+    check_fee(fee, max_fee_per_payment, payments.len())?;
+
+    // The most expensive check comes last.
+    check_consistency(transaction, network)
+}
+
+/// Runs the base node's internal consistency validation: the inputs, outputs, fee and offset balance, the
+/// range proofs and kernel signatures are valid, and the scripts are accounted for.
+fn check_consistency(transaction: &Transaction, network: Network) -> Result<(), String> {
+    let consensus_manager = ConsensusManager::builder(network)
+        .build()
+        .map_err(|e| format!("Cannot load the consensus rules of {}: {}", network, e))?;
+    let validator = TransactionInternalConsistencyValidator::new(false, consensus_manager, CryptoFactories::default());
+    // The tip height is not known here, so the latest consensus rules apply. The base node checks the
+    // transaction against the actual tip when it is submitted.
+    validator
+        .validate(transaction, None, None, u64::MAX)
+        .map_err(|e| format!("Transaction is not internally consistent: {}", e))
+}
+
+/// The key an output is locked to. For a one-sided output to an address that is the address's public spend key,
+/// and for change a key of the sending account. `None` for other scripts, such as stealth outputs.
+fn one_sided_key(script: &TariScript) -> Option<Vec<u8>> {
+    match script.as_slice() {
+        [Opcode::PushPubKey(key)] => Some(key.as_bytes().to_vec()),
+        _ => None,
+    }
+}
+
+/// Checks that the prepared recipients match the batch's payments, compared as `(address, amount)` pairs.
+fn check_recipients(mut prepared: Vec<(String, i64)>, mut requested: Vec<(String, i64)>) -> Result<(), String> {
+    prepared.sort();
+    requested.sort();
+    if prepared != requested {
+        let missing = requested.iter().filter(|payout| !prepared.contains(payout)).count();
+        let unexpected = prepared.iter().filter(|payout| !requested.contains(payout)).count();
+        return Err(format!(
+            "Transaction pays {} recipients but the batch has {} payments ({} missing, {} unexpected)",
+            prepared.len(),
+            requested.len(),
+            missing,
+            unexpected
+        ));
+    }
+    Ok(())
+}
+
+/// A one-sided transaction has an output per recipient, and one for change unless the inputs were spent exactly.
+fn check_output_count(outputs: usize, payments: usize) -> Result<(), String> {
+    if outputs != payments && outputs != payments + 1 {
+        return Err(format!(
+            "Signed transaction has {} outputs for {} payments, expected {} or {} with change",
+            outputs,
+            payments,
+            payments,
+            payments + 1
+        ));
+    }
+    Ok(())
+}
+
+/// Attributes the outputs to recipients, given the key each payment's recipient is paid to and the key each
+/// output is locked to. Each recipient must be paid by exactly as many outputs as it has payments, and at most
+/// one other output, the change, may go to a key that is not a recipient's. An output that is not locked to a
+/// key cannot be told apart from a payment to someone else, so it is rejected even if it might be change.
+fn check_recipient_outputs<K: Eq + std::hash::Hash>(
+    recipient_keys: &[K],
+    output_keys: &[Option<K>],
+) -> Result<(), String> {
+    let unattributable = output_keys.iter().filter(|key| key.is_none()).count();
+    if unattributable > 0 {
+        return Err(format!(
+            "{} outputs are not locked to a key and cannot be attributed to a recipient or change",
+            unattributable
+        ));
+    }
+
+    let mut unpaid: HashMap<&K, usize> = HashMap::new();
+    for key in recipient_keys {
+        *unpaid.entry(key).or_default() += 1;
+    }
+    let mut overpaid = 0;
+    let mut other_outputs = 0;
+    for key in output_keys.iter().flatten() {
+        match unpaid.get_mut(key) {
+            Some(0) => overpaid += 1,
+            Some(payments) => *payments -= 1,
+            None => other_outputs += 1,
+        }
+    }
+
+    if overpaid > 0 {
+        return Err(format!(
+            "Recipients are paid by {} more outputs than they have payments",
+            overpaid
+        ));
+    }
+    let underpaid: usize = unpaid.values().sum();
+    if underpaid > 0 {
+        return Err(format!(
+            "{} payments are not paid by an output to their recipient",
+            underpaid
+        ));
+    }
+    if other_outputs > 1 {
+        return Err(format!(
+            "{} outputs go to keys that are not a recipient's, but only one change output is allowed",
+            other_outputs
+        ));
+    }
+    Ok(())
+}
+
+fn check_fee(fee: u64, max_fee_per_payment: u64, payments: usize) -> Result<(), String> {
+    let max_fee = max_fee_per_payment.saturating_mul(payments as u64);
+    if fee > max_fee {
+        return Err(format!(
+            "Transaction fee of {} µT exceeds the limit of {} µT for {} payments",
+            fee, max_fee, payments
+        ));
+    }
+    Ok(())
+}
+
+/// The `(address, amount)` pairs of the prepared transaction's `recipients`, with addresses in their
+/// canonical base58 form as stored on payments.
+fn prepared_payouts(unsigned: &serde_json::Value) -> Result<Vec<(String, i64)>, String> {
+    let recipients = unsigned
+        .get("recipients")
+        .and_then(|recipients| recipients.as_array())
+        .ok_or_else(|| "Unsigned transaction does not list its recipients".to_string())?;
+
+    recipients
+        .iter()
+        .map(|recipient| {
+            let address = recipient
+                .get("address")
+                .and_then(|address| address.as_str())
+                .ok_or_else(|| "Recipient has no address".to_string())?;
+            let address =
+                TariAddress::from_str(address).map_err(|e| format!("Invalid recipient address {}: {}", address, e))?;
+            let amount = recipient
+                .get("amount")
+                .and_then(|amount| amount.as_i64())
+                .ok_or_else(|| format!("Recipient {} has no amount", address))?;
+            Ok((address.to_base58(), amount))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::SqlitePool;
+    use tari_crypto::keys::PublicKey as _;
+    use tari_transaction_components::{
+        tari_amount::MicroMinotari,
+        tari_common_types::types::{PrivateKey, PublicKey},
+        transaction_components::{TransactionKernel, TransactionOutput},
+    };
+
+    use super::*;
+    use crate::{
+        db::{event::Actor, payment::NewPayment},
+        test_support,
+    };
+
+    fn payouts(payouts: &[(&str, i64)]) -> Vec<(String, i64)> {
+        payouts
+            .iter()
+            .map(|(address, amount)| (address.to_string(), *amount))
+            .collect()
+    }
+
+    #[test]
+    fn recipients_must_match_payments_exactly() {
+        let requested = payouts(&[("a", 100), ("b", 200), ("a", 100)]);
+
+        assert!(check_recipients(payouts(&[("a", 100), ("a", 100), ("b", 200)]), requested.clone()).is_ok());
+        let cases = [
+            payouts(&[("a", 100), ("b", 200)]),
+            payouts(&[("a", 100), ("b", 200), ("a", 100), ("c", 1)]),
+            payouts(&[("a", 100), ("b", 201), ("a", 100)]),
+            payouts(&[("a", 100), ("c", 200), ("a", 100)]),
+            vec![],
+        ];
+        for prepared in cases {
+            assert!(
+                check_recipients(prepared.clone(), requested.clone()).is_err(),
+                "{:?}",
+                prepared
+            );
+        }
+    }
+
+    #[test]
+    fn outputs_are_one_per_payment_and_at_most_one_change() {
+        assert!(check_output_count(3, 3).is_ok());
+        assert!(check_output_count(4, 3).is_ok());
+        assert!(check_output_count(2, 3).is_err());
+        assert!(check_output_count(5, 3).is_err());
+        assert!(check_output_count(0, 1).is_err());
+    }
+
+    #[test]
+    fn every_payment_is_paid_to_its_recipient() {
+        let recipients = ["a", "b", "a"];
+
+        assert!(check_recipient_outputs(&recipients, &[Some("a"), Some("b"), Some("a")]).is_ok());
+        // One output to a key that is not a recipient's is change, e.g. to the account's own key.
+        assert!(check_recipient_outputs(&recipients, &[Some("a"), Some("z"), Some("b"), Some("a")]).is_ok());
+
+        let cases: [&[Option<&str>]; 7] = [
+            // Everything went to someone else.
+            &[Some("z"), Some("z"), Some("z"), Some("z")],
+            // A recipient is short of a payment, which went elsewhere.
+            &[Some("a"), Some("b"), Some("y"), Some("z")],
+            &[Some("a"), Some("b"), Some("z")],
+            // The change went to a recipient.
+            &[Some("a"), Some("b"), Some("a"), Some("b")],
+            &[Some("a"), Some("a"), Some("a"), Some("b")],
+            // Stealth outputs cannot be attributed, whether they are payments or change.
+            &[None, None, None, None],
+            &[Some("a"), Some("b"), Some("a"), None],
+        ];
+        for outputs in cases {
+            assert!(check_recipient_outputs(&recipients, outputs).is_err(), "{:?}", outputs);
+        }
+    }
+
+    #[test]
+    fn fee_is_capped_per_payment() {
+        assert!(check_fee(30_000, 10_000, 3).is_ok());
+        assert!(check_fee(30_001, 10_000, 3).is_err());
+        assert!(check_fee(0, 0, 3).is_ok());
+        assert!(check_fee(1, 0, 3).is_err());
+        assert!(check_fee(u64::MAX, u64::MAX, 2).is_ok());
+    }
+
+    #[test]
+    fn prepared_payouts_need_recipients_with_valid_addresses_and_amounts() {
+        let cases = [
+            json!({}),
+            json!({ "recipients": "a" }),
+            json!({ "recipients": [{ "amount": 100 }] }),
+            json!({ "recipients": [{ "address": "not an address", "amount": 100 }] }),
+        ];
+        for unsigned in cases {
+            assert!(prepared_payouts(&unsigned).is_err(), "{}", unsigned);
+        }
+        assert_eq!(prepared_payouts(&json!({ "recipients": [] })), Ok(vec![]));
+    }
+
+    fn address(secret: u64) -> TariAddress {
+        let spend_key = PublicKey::from_secret_key(&PrivateKey::from(secret));
+        TariAddress::new_single_address_with_default_features(spend_key, Network::LocalNet)
+    }
+
+    /// A signed transaction for `unsigned` as a signer returns it, paying to the spend keys of `recipients`.
+    /// Its commitments and signatures are placeholders, so it never passes the consistency check.
+    fn signed_tx_json(unsigned: &serde_json::Value, recipients: &[&TariAddress]) -> String {
+        let outputs = recipients
+            .iter()
+            .map(|address| TransactionOutput {
+                script: TariScript::new(vec![Opcode::PushPubKey(Box::new(address.public_spend_key().clone()))])
+                    .unwrap(),
+                ..Default::default()
+            })
+            .collect();
+        let kernel = TransactionKernel {
+            fee: MicroMinotari::from(100),
+            ..Default::default()
+        };
+        let transaction = Transaction::new(
+            vec![],
+            outputs,
+            vec![kernel],
+            PrivateKey::default(),
+            PrivateKey::default(),
+        );
+        json!({ "request": unsigned, "signed_transaction": { "transaction": transaction } }).to_string()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn outputs_to_a_foreign_key_fail_verification(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let (alice, bob, change, mallory) = (address(1), address(2), address(3), address(4));
+        let mut payments = Vec::new();
+        for (i, recipient) in [&alice, &bob, &alice].into_iter().enumerate() {
+            let client_id = format!("order-{}", i);
+            let recipient_address = recipient.to_base58();
+            let new_payment = NewPayment {
+                client_id: &client_id,
+                account_name: test_support::ACCOUNT_NAME,
+                recipient_address: &recipient_address,
+                amount: 1_000,
+                payment_id: None,
+                callback_url: None,
+                request_fingerprint: None,
+                execute_after: None,
+            };
+            payments.push(Payment::create(&mut conn, &new_payment, &Actor::Api).await.unwrap());
+        }
+        let unsigned = json!({
+            "recipients": payments
+                .iter()
+                .map(|payment| json!({ "address": payment.recipient_address, "amount": payment.amount }))
+                .collect::<Vec<_>>(),
+        });
+        let unsigned_tx_json = unsigned.to_string();
+        let verify = |recipients: &[&TariAddress]| {
+            verify_signed_tx(&unsigned_tx_json, &signed_tx_json(&unsigned, recipients)).and_then(|signed_tx| {
+                verify_payouts(&unsigned_tx_json, &signed_tx, &payments, Network::LocalNet, 10_000)
+            })
+        };
+
+        // Paying the recipients gets as far as the consistency check, which the placeholders fail.
+        let error = verify(&[&alice, &bob, &alice, &change]).unwrap_err();
+        assert!(error.contains("not internally consistent"), "{}", error);
+
+        let cases: [&[&TariAddress]; 3] = [
+            &[&mallory, &mallory, &mallory],
+            &[&mallory, &mallory, &mallory, &mallory],
+            &[&alice, &bob, &mallory, &change],
+        ];
+        for recipients in cases {
+            let error = verify(recipients).unwrap_err();
+            assert!(error.contains("not paid by an output"), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Fixtures shared by the tests of the workers and the database layer.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use minotari_client::{apis::configuration::Configuration, models::CreateTransactionRequest};
+use sqlx::SqliteConnection;
+
+use crate::db::{
+    event::Actor,
+    payment::{NewPayment, Payment, PaymentStatus},
+    payment_batch::{PaymentBatch, PaymentBatchStatus},
+};
+
+pub(crate) const ACCOUNT_NAME: &str = "default";
+pub(crate) const CALLBACK_URL: &str = "http://127.0.0.1:9/callback";
+
+/// Creates `count` 'RECEIVED' payments of `amount` each for [`ACCOUNT_NAME`], reporting to [`CALLBACK_URL`].
+pub(crate) async fn create_payments(conn: &mut SqliteConnection, count: usize, amount: i64) -> Vec<Payment> {
+    let mut payments = Vec::with_capacity(count);
+    for i in 0..count {
+        let client_id = format!("order-{}", i);
+        let recipient_address = format!("recipient-{}", i);
+        let new_payment = NewPayment {
+            client_id: &client_id,
+            account_name: ACCOUNT_NAME,
+            recipient_address: &recipient_address,
+            amount,
+            payment_id: None,
+            callback_url: Some(CALLBACK_URL),
+            request_fingerprint: None,
+            execute_after: None,
+        };
+        payments.push(Payment::create(conn, &new_payment, &Actor::Api).await.unwrap());
+    }
+    payments
+}
+
+/// Creates a 'PENDING_BATCHING' batch of `count` new payments.
+pub(crate) async fn create_batch(conn: &mut SqliteConnection, count: usize, amount: i64) -> PaymentBatch {
+    let payment_ids = create_payments(conn, count, amount)
+        .await
+        .into_iter()
+        .map(|payment| payment.id)
+        .collect::<Vec<_>>();
+    let pr_idempotency_key = uuid::Uuid::new_v4().to_string();
+    PaymentBatch::create_with_payments(
+        conn,
+        ACCOUNT_NAME,
+        &pr_idempotency_key,
+        &payment_ids,
+        &Actor::BatchCreator,
+    )
+    .await
+    .unwrap()
+}
+
+/// Puts a batch in `status` directly, skipping the stages in between.
+pub(crate) async fn set_batch_status(conn: &mut SqliteConnection, batch_id: &str, status: PaymentBatchStatus) {
+    sqlx::query("UPDATE payment_batches SET status = ? WHERE id = ?")
+        .bind(status.to_string())
+        .bind(batch_id)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+}
+
+/// Stores `signed_tx_json` as a batch's signed transaction.
+pub(crate) async fn set_batch_signed_tx(conn: &mut SqliteConnection, batch_id: &str, signed_tx_json: &str) {
+    sqlx::query("UPDATE payment_batches SET signed_tx_json = ? WHERE id = ?")
+        .bind(signed_tx_json)
+        .bind(batch_id)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+}
+
+/// Records a batch's transaction as mined in the block `header_hash` at `height`.
+pub(crate) async fn set_batch_mined(conn: &mut SqliteConnection, batch_id: &str, height: i64, header_hash: &[u8]) {
+    sqlx::query(
+        r#"
+        UPDATE payment_batches
+        SET mined_height = ?, mined_header_hash = ?, mined_timestamp = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(height)
+    .bind(hex::encode(header_hash))
+    .bind(1_700_000_000_i64)
+    .bind(batch_id)
+    .execute(&mut *conn)
+    .await
+    .unwrap();
+}
+
+/// Puts every payment of a batch in `status` directly.
+pub(crate) async fn set_payments_status(conn: &mut SqliteConnection, batch_id: &str, status: PaymentStatus) {
+    sqlx::query("UPDATE payments SET status = ? WHERE payment_batch_id = ?")
+        .bind(status.to_string())
+        .bind(batch_id)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+}
+
+pub(crate) async fn get_batch(conn: &mut SqliteConnection, batch_id: &str) -> PaymentBatch {
+    PaymentBatch::find_by_id(conn, batch_id).await.unwrap().unwrap()
+}
+
+/// A local stand-in for the payment receiver. Every account has `balance` µT. Transactions to a refused
+/// recipient are rejected with `400 Bad Request` and those to a recipient PR fails on with `500 Internal Server
+/// Error`, both with a `FailedCreateUnsignedTx` error. Those to an unavailable one get `503 Service Unavailable`
+/// without an error body. Every other transaction is built.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MockPaymentReceiver {
+    balance: i64,
+    refused_recipients: HashSet<String>,
+    unavailable_recipients: HashSet<String>,
+    failing_recipients: HashSet<String>,
+    /// The recipients of every create_unsigned_transaction request, in order.
+    pub(crate) requests: Arc<Mutex<Vec<Vec<String>>>>,
+}
+
+impl MockPaymentReceiver {
+    pub(crate) fn new(balance: i64) -> Self {
+        MockPaymentReceiver {
+            balance,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn refusing(mut self, recipient_address: &str) -> Self {
+        self.refused_recipients.insert(recipient_address.to_string());
+        self
+    }
+
+    pub(crate) fn unavailable_for(mut self, recipient_address: &str) -> Self {
+        self.unavailable_recipients.insert(recipient_address.to_string());
+        self
+    }
+
+    pub(crate) fn failing_for(mut self, recipient_address: &str) -> Self {
+        self.failing_recipients.insert(recipient_address.to_string());
+        self
+    }
+
+    /// Serves the payment receiver on a local port and returns the client configuration that reaches it.
+    pub(crate) async fn spawn(self) -> Configuration {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_path = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route("/accounts/{name}/balance", get(balance))
+            .route(
+                "/accounts/{name}/create_unsigned_transaction",
+                post(create_unsigned_transaction),
+            )
+            .with_state(self);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Configuration {
+            base_path,
+            ..Default::default()
+        }
+    }
+}
+
+async fn balance(State(receiver): State<MockPaymentReceiver>, Path(_name): Path<String>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "total_credits": receiver.balance, "total_debits": 0 }))
+}
+
+async fn create_unsigned_transaction(
+    State(receiver): State<MockPaymentReceiver>,
+    Path(_name): Path<String>,
+    Json(request): Json<CreateTransactionRequest>,
+) -> Response {
+    let recipients: Vec<String> = request.recipients.into_iter().map(|r| r.address).collect();
+    receiver.requests.lock().unwrap().push(recipients.clone());
+    if recipients.iter().any(|r| receiver.unavailable_recipients.contains(r)) {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable").into_response()
+    } else if recipients.iter().any(|r| receiver.failing_recipients.contains(r)) {
+        let error = serde_json::json!({ "FailedCreateUnsignedTx": "failed" });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response()
+    } else if recipients.iter().any(|r| receiver.refused_recipients.contains(r)) {
+        let error = serde_json::json!({ "FailedCreateUnsignedTx": "refused" });
+        (StatusCode::BAD_REQUEST, Json(error)).into_response()
+    } else {
+        Json(serde_json::json!({ "recipients": recipients })).into_response()
+    }
+}
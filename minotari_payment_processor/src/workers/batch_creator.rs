@@ -1,18 +1,33 @@
-use sqlx::SqlitePool;
-use std::collections::HashMap;
+use anyhow::anyhow;
+use minotari_client::apis::{accounts_api, configuration::Configuration};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::sync::Arc;
 use tokio::time::{self, Duration};
 use uuid::Uuid;
 
-use crate::db::{payment::Payment, payment_batch::PaymentBatch};
+use crate::db::{
+    batch_policy::{AccountBatchPolicy, BatchPolicy},
+    event::{AccountAlert, Actor, AlertKind},
+    payment::{HoldReason, Payment, PaymentStatus},
+    payment_batch::PaymentBatch,
+};
 
 const DEFAULT_SLEEP_SECS: u64 = 10 * 60; // 10 minutes
-const MAX_BATCH_SIZE: i64 = 100;
+/// Reserved from the balance for the fee of each payment, in µT.
+pub(crate) const DEFAULT_FEE_MARGIN_PER_PAYMENT: u64 = 10_000;
 
-pub async fn run(db_pool: SqlitePool, sleep_secs: Option<u64>) {
+pub async fn run(
+    db_pool: SqlitePool,
+    client_config: Arc<Configuration>,
+    default_policy: BatchPolicy,
+    fee_margin_per_payment: Option<u64>,
+    sleep_secs: Option<u64>,
+) {
     let sleep_secs = sleep_secs.unwrap_or(DEFAULT_SLEEP_SECS);
+    let fee_margin = fee_margin_per_payment.unwrap_or(DEFAULT_FEE_MARGIN_PER_PAYMENT) as i64;
     loop {
         let mut should_sleep = true;
-        match process_batches(&db_pool).await {
+        match process_batches(&db_pool, &client_config, &default_policy, fee_margin).await {
             Ok(more_batches_expected) => {
                 if more_batches_expected {
                     should_sleep = false;
@@ -29,32 +44,190 @@ pub async fn run(db_pool: SqlitePool, sleep_secs: Option<u64>) {
     }
 }
 
-async fn process_batches(db_pool: &SqlitePool) -> Result<bool, anyhow::Error> {
+/// Creates at most one batch per account on each pass, so a busy account cannot starve the others.
+async fn process_batches(
+    db_pool: &SqlitePool,
+    client_config: &Configuration,
+    default_policy: &BatchPolicy,
+    fee_margin: i64,
+) -> Result<bool, anyhow::Error> {
     let mut conn = db_pool.acquire().await?;
-    let payments = Payment::find_receivable_payments(&mut conn, MAX_BATCH_SIZE).await?;
-    let payments_count = payments.len();
+    let released = Payment::release_due_scheduled_payments(&mut conn, &Actor::BatchCreator).await?;
+    if !released.is_empty() {
+        println!("Released {} scheduled payments that became due", released.len());
+    }
+    let account_names = Payment::find_accounts_with_receivable_payments(&mut conn).await?;
+
+    let mut more_batches_expected = false;
+    for account_name in account_names {
+        let policy = AccountBatchPolicy::find_by_account_name(&mut conn, &account_name)
+            .await?
+            .map_or_else(|| default_policy.clone(), |account_policy| account_policy.policy);
+        match create_account_batch(&mut conn, client_config, &account_name, &policy, fee_margin).await {
+            Ok(payments_left) => more_batches_expected |= payments_left,
+            Err(e) => eprintln!("Failed to create batch for account {}: {:?}", account_name, e),
+        }
+    }
 
-    if payments.is_empty() {
+    Ok(more_batches_expected)
+}
+
+/// Once the policy says the account's queue is due, batches as many of its payments as the policy
+/// and its balance allow, oldest first. Payments the balance does not cover are held with an
+/// `insufficient_funds` reason. Payments are batched strictly in order, so a large payment is not
+/// starved by smaller ones queued after it. Returns whether payments the balance covers were left
+/// for another batch, because the batch reached `max_recipients` or `max_total_amount`.
+async fn create_account_batch(
+    conn: &mut SqliteConnection,
+    client_config: &Configuration,
+    account_name: &str,
+    policy: &BatchPolicy,
+    fee_margin: i64,
+) -> Result<bool, anyhow::Error> {
+    let payments = Payment::find_receivable_payments(conn, account_name, policy.max_recipients).await?;
+    let Some(oldest) = payments.first() else {
+        return Ok(false);
+    };
+    let (queued_count, _) = Payment::total_by_status(conn, account_name, PaymentStatus::Received).await?;
+    // A scheduled payment only starts waiting once it is due.
+    let oldest_queued_at = oldest.execute_after.unwrap_or(oldest.created_at).max(oldest.created_at);
+    if !policy.is_due(queued_count, oldest_queued_at) {
         return Ok(false);
     }
 
-    let mut payments_by_account: HashMap<String, Vec<Payment>> = HashMap::new();
-    for payment in payments {
-        payments_by_account
-            .entry(payment.account_name.clone())
-            .or_default()
-            .push(payment);
+    let balance = accounts_api::api_get_balance(client_config, account_name)
+        .await
+        .map_err(|e| anyhow!("Failed to fetch balance: {:?}", e))?;
+    let balance = balance.total_credits.flatten().unwrap_or(0) - balance.total_debits.flatten().unwrap_or(0);
+    // Payments already batched may not be reflected in the balance until their transaction is
+    // mined, so they are deducted to stay on the safe side.
+    let (in_flight_count, in_flight_amount) =
+        Payment::total_by_status(conn, account_name, PaymentStatus::Batched).await?;
+    let mut available = balance - in_flight_amount - in_flight_count * fee_margin;
+
+    let mut payment_ids = Vec::new();
+    let mut batch_total: i64 = 0;
+    let mut insufficient_funds = false;
+    for payment in &payments {
+        let exceeds_max_total = policy
+            .max_total_amount
+            .is_some_and(|max| batch_total.saturating_add(payment.amount) > max);
+        if exceeds_max_total && !payment_ids.is_empty() {
+            break;
+        }
+        let cost = payment.amount.saturating_add(fee_margin);
+        if cost > available {
+            insufficient_funds = true;
+            break;
+        }
+        available -= cost;
+        batch_total += payment.amount;
+        payment_ids.push(payment.id.clone());
     }
 
-    for (account_name, account_payments) in payments_by_account {
-        let payment_ids: Vec<String> = account_payments.iter().map(|p| p.id.clone()).collect();
+    if !payment_ids.is_empty() {
         let pr_idempotency_key = Uuid::new_v4().to_string();
-        if let Err(e) =
-            PaymentBatch::create_with_payments(&mut conn, &account_name, &pr_idempotency_key, &payment_ids).await
-        {
-            eprintln!("Failed to create batch for account {}: {:?}", account_name, e);
+        PaymentBatch::create_with_payments(
+            conn,
+            account_name,
+            &pr_idempotency_key,
+            &payment_ids,
+            &Actor::BatchCreator,
+        )
+        .await?;
+    }
+
+    if insufficient_funds {
+        let newly_held = Payment::set_hold_reason(conn, account_name, Some(HoldReason::InsufficientFunds)).await?;
+        // Only alert when payments are newly held, not on every pass over an unchanged queue.
+        if newly_held > 0 {
+            let (queued_count, queued_amount) =
+                Payment::total_by_status(conn, account_name, PaymentStatus::Received).await?;
+            let detail = format!(
+                "Available balance of {} µT does not cover {} queued payments totalling {} µT plus a fee margin of {} µT each",
+                available.max(0),
+                queued_count,
+                queued_amount,
+                fee_margin
+            );
+            eprintln!("Account {} has insufficient funds: {}", account_name, detail);
+            AccountAlert::record(conn, account_name, AlertKind::InsufficientFunds, &detail).await?;
         }
+    } else {
+        Payment::set_hold_reason(conn, account_name, None).await?;
     }
 
-    Ok(payments_count == MAX_BATCH_SIZE as usize)
+    // Only `max_recipients` payments were fetched, so a full batch may have left more in the queue.
+    let cut_short = payment_ids.len() < payments.len() || payments.len() as i64 == policy.max_recipients;
+    Ok(!payment_ids.is_empty() && !insufficient_funds && cut_short)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::payment_batch::PaymentBatchStatus,
+        test_support::{self, MockPaymentReceiver},
+    };
+
+    async fn batch_sizes(conn: &mut SqliteConnection) -> Vec<usize> {
+        let batches = PaymentBatch::find_by_status(conn, PaymentBatchStatus::PendingBatching)
+            .await
+            .unwrap();
+        let mut sizes = Vec::new();
+        for batch in batches {
+            sizes.push(Payment::find_by_batch_id(conn, &batch.id).await.unwrap().len());
+        }
+        sizes.sort();
+        sizes
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn batch_cut_by_max_total_amount_expects_more(pool: SqlitePool) {
+        let client_config = MockPaymentReceiver::new(1_000_000).spawn().await;
+        let mut conn = pool.acquire().await.unwrap();
+        test_support::create_payments(&mut conn, 3, 1_000).await;
+        let policy = BatchPolicy {
+            max_total_amount: Some(2_000),
+            ..Default::default()
+        };
+
+        let more_expected = process_batches(&pool, &client_config, &policy, 0).await.unwrap();
+
+        assert!(more_expected);
+        assert_eq!(batch_sizes(&mut conn).await, vec![2]);
+
+        let more_expected = process_batches(&pool, &client_config, &policy, 0).await.unwrap();
+
+        assert!(!more_expected);
+        assert_eq!(batch_sizes(&mut conn).await, vec![1, 2]);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn full_batch_expects_more(pool: SqlitePool) {
+        let client_config = MockPaymentReceiver::new(1_000_000).spawn().await;
+        let mut conn = pool.acquire().await.unwrap();
+        test_support::create_payments(&mut conn, 3, 1_000).await;
+        let policy = BatchPolicy {
+            max_recipients: 2,
+            ..Default::default()
+        };
+
+        assert!(process_batches(&pool, &client_config, &policy, 0).await.unwrap());
+        assert_eq!(batch_sizes(&mut conn).await, vec![2]);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn payments_held_for_funds_do_not_expect_more(pool: SqlitePool) {
+        let client_config = MockPaymentReceiver::new(2_500).spawn().await;
+        let mut conn = pool.acquire().await.unwrap();
+        test_support::create_payments(&mut conn, 3, 1_000).await;
+
+        assert!(
+            !process_batches(&pool, &client_config, &BatchPolicy::default(), 0)
+                .await
+                .unwrap()
+        );
+        assert_eq!(batch_sizes(&mut conn).await, vec![2]);
+    }
 }
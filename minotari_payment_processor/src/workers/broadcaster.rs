@@ -1,18 +1,44 @@
-use minotari_node_wallet_client::{BaseNodeWalletClient, http::Client};
-use sqlx::SqlitePool;
-use tari_transaction_components::offline_signing::models::{SignedOneSidedTransactionResult, TransactionResult};
+use minotari_node_wallet_client::http::Client;
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+use tari_transaction_components::tari_common::configuration::Network;
 use tokio::time::{self, Duration};
 
-use crate::db::payment_batch::{PaymentBatch, PaymentBatchStatus};
+use crate::{
+    base_node::{BaseNode, Submission, TransactionLocation},
+    db::{
+        event::{AccountAlert, Actor, AlertKind},
+        payment::{FailureCode, Payment},
+        payment_batch::{Lease, PaymentBatch, PaymentBatchStatus, RetryBackoff},
+    },
+    signer::{verify_payouts, verify_signed_tx},
+};
 
 const DEFAULT_SLEEP_SECS: u64 = 15;
+/// The most fee a signed transaction may pay per payment, in µT.
+const DEFAULT_MAX_FEE_PER_PAYMENT: u64 = 10_000;
 
-pub async fn run(db_pool: SqlitePool, base_node_client: Client, sleep_secs: Option<u64>) {
+pub async fn run(
+    db_pool: SqlitePool,
+    base_node_client: Client,
+    network: Network,
+    max_fee_per_payment: Option<u64>,
+    retry_backoff: RetryBackoff,
+    sleep_secs: Option<u64>,
+) {
     let sleep_secs = sleep_secs.unwrap_or(DEFAULT_SLEEP_SECS);
+    let max_fee_per_payment = max_fee_per_payment.unwrap_or(DEFAULT_MAX_FEE_PER_PAYMENT);
     let mut interval = time::interval(Duration::from_secs(sleep_secs));
     loop {
         interval.tick().await;
-        if let Err(e) = process_transactions_to_broadcast(&db_pool, &base_node_client).await {
+        if let Err(e) = process_transactions_to_broadcast(
+            &db_pool,
+            &base_node_client,
+            network,
+            max_fee_per_payment,
+            &retry_backoff,
+        )
+        .await
+        {
             eprintln!("Transaction Broadcaster worker error: {:?}", e);
         }
     }
@@ -20,37 +46,168 @@ pub async fn run(db_pool: SqlitePool, base_node_client: Client, sleep_secs: Opti
 
 async fn process_transactions_to_broadcast(
     db_pool: &SqlitePool,
-    base_node_client: &Client,
+    base_node: &dyn BaseNode,
+    network: Network,
+    max_fee_per_payment: u64,
+    retry_backoff: &RetryBackoff,
 ) -> Result<(), anyhow::Error> {
     let mut conn = db_pool.acquire().await?;
     let batches = PaymentBatch::find_by_status(&mut conn, PaymentBatchStatus::AwaitingBroadcast).await?;
 
     for batch in batches {
         // Update its status to `BROADCASTING`.
-        PaymentBatch::update_to_broadcasting(&mut conn, &batch.id).await?;
+        let Some(lease) = PaymentBatch::update_to_broadcasting(&mut conn, &batch.id, &Actor::Broadcaster).await? else {
+            continue;
+        };
 
-        let batch_id = batch.id.clone();
-        let signed_tx_json = batch
-            .signed_tx_json
-            .clone()
-            .ok_or_else(|| anyhow::anyhow!("Batch {} has no signed_tx_json", batch_id))?;
-        let signed_tx = SignedOneSidedTransactionResult::from_json(&signed_tx_json)?;
+        if let Err(e) = broadcast_batch(
+            &mut conn,
+            &batch,
+            &lease,
+            base_node,
+            network,
+            max_fee_per_payment,
+            retry_backoff,
+        )
+        .await
+        {
+            let error_message = format!("Broadcast attempt failed for batch {}: {:?}", batch.id, e);
+            eprintln!("{}", error_message);
+            PaymentBatch::release_lease(
+                &mut conn,
+                &batch.id,
+                &lease,
+                FailureCode::BaseNodeUnavailable,
+                &error_message,
+                retry_backoff,
+                &Actor::Broadcaster,
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn broadcast_batch(
+    conn: &mut SqliteConnection,
+    batch: &PaymentBatch,
+    lease: &Lease,
+    base_node: &dyn BaseNode,
+    network: Network,
+    max_fee_per_payment: u64,
+    retry_backoff: &RetryBackoff,
+) -> Result<(), anyhow::Error> {
+    let batch_id = &batch.id;
+    let unsigned_tx_json = batch
+        .unsigned_tx_json
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Batch {} has no unsigned_tx_json", batch_id))?;
+    let signed_tx_json = batch
+        .signed_tx_json
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Batch {} has no signed_tx_json", batch_id))?;
 
-        let response = base_node_client
-            .submit_transaction(signed_tx.signed_transaction.transaction)
+    // Checked here rather than after signing, so the output of every signer, including uploads of
+    // manually signed transactions and operator retries, is verified right before it is submitted.
+    let payments = Payment::find_by_batch_id(conn, batch_id).await?;
+    let verified = verify_signed_tx(unsigned_tx_json, signed_tx_json)
+        .and_then(|signed_tx| verify_payouts(unsigned_tx_json, &signed_tx, &payments, network, max_fee_per_payment));
+    if let Err(reason) = verified {
+        let error_message = format!(
+            "Signed transaction for batch {} failed verification: {}",
+            batch_id, reason
+        );
+        eprintln!("{}", error_message);
+        let mut tx = conn.begin().await?;
+        if PaymentBatch::update_to_verification_failed(&mut tx, batch_id, lease, &error_message, &Actor::Broadcaster)
+            .await?
+        {
+            AccountAlert::record(
+                &mut tx,
+                &batch.account_name,
+                AlertKind::VerificationFailed,
+                &error_message,
+            )
             .await?;
+        }
+        tx.commit().await?;
+        return Ok(());
+    }
 
-        if response.accepted {
-            PaymentBatch::update_to_awaiting_confirmation(&mut conn, &batch_id).await?;
-        } else {
-            let error_message = format!(
-                "Tari base node rejected transaction for batch {}: {}",
-                batch_id, response.rejection_reason
-            );
-            eprintln!("{}", error_message);
-            PaymentBatch::increment_retry_count(&mut conn, &batch_id, &error_message).await?;
+    // A batch recovered from a broadcaster that crashed or lost its lease mid-broadcast may already be on the
+    // chain, and is not submitted again.
+    if let TransactionLocation::Mined(_) | TransactionLocation::InMempool =
+        base_node.transaction_location(signed_tx_json).await?
+    {
+        println!(
+            "Batch {} transaction is already on the base node, awaiting confirmation.",
+            batch_id
+        );
+        PaymentBatch::update_to_awaiting_confirmation(conn, batch_id, lease, &Actor::Broadcaster).await?;
+        return Ok(());
+    }
+
+    PaymentBatch::record_submission(conn, batch_id).await?;
+    let (code, reason) = match base_node.submit(signed_tx_json).await? {
+        // A transaction that is already mined was submitted by an earlier attempt whose outcome was lost.
+        Submission::Accepted | Submission::AlreadyMined => {
+            PaymentBatch::update_to_awaiting_confirmation(conn, batch_id, lease, &Actor::Broadcaster).await?;
+            return Ok(());
+        },
+        Submission::Rejected { code, reason } => (code, reason),
+    };
+    let mut error_message = format!("Tari base node rejected transaction for batch {}: {}", batch_id, reason);
+    eprintln!("{} ({})", error_message, code);
+
+    if code == FailureCode::InputsSpent {
+        // The inputs are also spent if an earlier attempt whose outcome was lost got the transaction mined,
+        // so the kernel decides whether they were spent by another transaction.
+        match base_node.transaction_location(signed_tx_json).await? {
+            TransactionLocation::Mined(_) | TransactionLocation::InMempool => {
+                println!(
+                    "Batch {} transaction is already on the base node, awaiting confirmation.",
+                    batch_id
+                );
+                PaymentBatch::update_to_awaiting_confirmation(conn, batch_id, lease, &Actor::Broadcaster).await?;
+                return Ok(());
+            },
+            TransactionLocation::Unknown => {},
+            TransactionLocation::NotStored => {
+                error_message.push_str(
+                    "; the base node has pruned the blocks that could show whether the transaction itself spent the \
+                     inputs",
+                );
+                PaymentBatch::release_lease(
+                    conn,
+                    batch_id,
+                    lease,
+                    code,
+                    &error_message,
+                    retry_backoff,
+                    &Actor::Broadcaster,
+                )
+                .await?;
+                return Ok(());
+            },
         }
     }
 
+    if code.is_retryable() {
+        PaymentBatch::release_lease(
+            conn,
+            batch_id,
+            lease,
+            code,
+            &error_message,
+            retry_backoff,
+            &Actor::Broadcaster,
+        )
+        .await?;
+    } else {
+        PaymentBatch::update_to_failed_under_lease(conn, batch_id, lease, code, &error_message, &Actor::Broadcaster)
+            .await?;
+    }
+
     Ok(())
 }
@@ -1,29 +1,42 @@
-use anyhow::anyhow;
-use minotari_node_wallet_client::{BaseNodeWalletClient, http::Client};
-use sqlx::{Acquire, SqlitePool};
-use tari_transaction_components::offline_signing::models::{SignedOneSidedTransactionResult, TransactionResult};
-use tari_transaction_components::rpc::models::TxLocation;
-use tari_utilities::byte_array::ByteArray;
+use chrono::Utc;
+use minotari_node_wallet_client::http::Client;
+use sqlx::{Acquire, SqliteConnection, SqlitePool};
 use tokio::time::{self, Duration};
 
+use crate::base_node::{BaseNode, Submission, TransactionLocation};
 use crate::db::payment_batch::PaymentBatchStatus;
-use crate::db::{payment::Payment, payment_batch::PaymentBatch};
+use crate::db::{
+    event::Actor,
+    payment::{FailureCode, Payment},
+    payment_batch::PaymentBatch,
+};
 
 const DEFAULT_SLEEP_SECS: u64 = 60;
 const REQUIRED_CONFIRMATIONS: u64 = 10;
+const DEFAULT_DROPPED_TX_TIMEOUT_SECS: u64 = 60 * 60;
 
-pub async fn run(db_pool: SqlitePool, base_node_client: Client, sleep_secs: Option<u64>) {
+pub async fn run(
+    db_pool: SqlitePool,
+    base_node_client: Client,
+    dropped_tx_timeout_secs: Option<u64>,
+    sleep_secs: Option<u64>,
+) {
     let sleep_secs = sleep_secs.unwrap_or(DEFAULT_SLEEP_SECS);
+    let dropped_tx_timeout = Duration::from_secs(dropped_tx_timeout_secs.unwrap_or(DEFAULT_DROPPED_TX_TIMEOUT_SECS));
     let mut interval = time::interval(Duration::from_secs(sleep_secs));
     loop {
         interval.tick().await;
-        if let Err(e) = check_transaction_confirmations(&db_pool, &base_node_client).await {
+        if let Err(e) = check_transaction_confirmations(&db_pool, &base_node_client, dropped_tx_timeout).await {
             eprintln!("Confirmation Checker worker error: {:?}", e);
         }
     }
 }
 
-async fn check_transaction_confirmations(db_pool: &SqlitePool, base_node_client: &Client) -> Result<(), anyhow::Error> {
+async fn check_transaction_confirmations(
+    db_pool: &SqlitePool,
+    base_node: &dyn BaseNode,
+    dropped_tx_timeout: Duration,
+) -> Result<(), anyhow::Error> {
     let mut conn = db_pool.acquire().await?;
     let batches = PaymentBatch::find_by_status(&mut conn, PaymentBatchStatus::AwaitingConfirmation).await?;
 
@@ -32,50 +45,41 @@ async fn check_transaction_confirmations(db_pool: &SqlitePool, base_node_client:
 
         let signed_tx_json = batch
             .signed_tx_json
-            .clone()
+            .as_deref()
             .ok_or_else(|| anyhow::anyhow!("Batch {} has no signed_tx_json", batch_id))?;
-        let signed_tx = SignedOneSidedTransactionResult::from_json(&signed_tx_json)?;
-        let sig = &signed_tx.signed_transaction.transaction.body.kernels()[0].excess_sig;
-        let excess_sig_nonce = sig.get_compressed_public_nonce().to_vec();
-        let excess_sig_sig = sig.get_signature().to_vec();
-        let tx_query_response = base_node_client
-            .transaction_query(excess_sig_nonce, excess_sig_sig)
-            .await?;
-
-        match tx_query_response.location {
-            TxLocation::Mined => {
-                let mined_height = tx_query_response
-                    .mined_height
-                    .ok_or_else(|| anyhow!("Mined transaction has no mined_height"))?;
-
-                let tip_info = base_node_client.get_tip_info().await?;
-                let best_block_height = tip_info
-                    .metadata
-                    .ok_or_else(|| anyhow!("Tip info has no metadata"))?
-                    .best_block_height();
-
-                let confirmations = best_block_height.saturating_sub(mined_height) + 1;
 
-                if confirmations >= REQUIRED_CONFIRMATIONS {
-                    let mined_header_hash = tx_query_response
-                        .mined_header_hash
-                        .ok_or_else(|| anyhow!("Mined transaction has no mined_header_hash"))?;
-                    let mined_timestamp = tx_query_response
-                        .mined_timestamp
-                        .ok_or_else(|| anyhow!("Mined transaction has no mined_timestamp"))?;
+        match base_node.transaction_location(signed_tx_json).await? {
+            TransactionLocation::Mined(block) => {
+                // Recorded on first sight, so the reorg watcher notices if the block is reorged out
+                // before the batch is confirmed.
+                if batch.mined_header_hash.is_none() {
+                    PaymentBatch::record_mined_block(&mut conn, &batch_id, &block).await?;
+                }
+                PaymentBatch::clear_missing(&mut conn, &batch_id).await?;
 
+                let best_block_height = base_node.best_block_height().await?;
+                let confirmations = best_block_height.saturating_sub(block.height) + 1;
+
+                if confirmations >= REQUIRED_CONFIRMATIONS {
                     let mut tx = conn.begin().await?;
                     PaymentBatch::update_to_confirmed(
                         &mut tx,
                         &batch_id,
-                        mined_height,
-                        mined_header_hash,
-                        mined_timestamp,
+                        block.height,
+                        block.header_hash,
+                        block.timestamp,
+                        &Actor::ConfirmationChecker,
                     )
                     .await?;
                     let associated_payments = Payment::find_by_batch_id(&mut tx, &batch_id).await?;
                     let payment_ids: Vec<String> = associated_payments.iter().map(|p| p.id.clone()).collect();
-                    Payment::update_payments_to_confirmed(&mut tx, &payment_ids).await?;
+                    Payment::update_payments_to_confirmed(
+                        &mut tx,
+                        &payment_ids,
+                        &batch_id,
+                        &Actor::ConfirmationChecker,
+                    )
+                    .await?;
                     tx.commit().await?;
                     println!("Batch {} confirmed successfully.", batch_id);
                 } else {
@@ -85,14 +89,284 @@ async fn check_transaction_confirmations(db_pool: &SqlitePool, base_node_client:
                     );
                 }
             },
-            TxLocation::InMempool => {
+            TransactionLocation::InMempool => {
+                PaymentBatch::clear_missing(&mut conn, &batch_id).await?;
                 println!("Batch {} is in mempool, awaiting mining.", batch_id);
             },
-            TxLocation::None | TxLocation::NotStored => {
+            // Once mined, a vanished transaction is left to the reorg watcher.
+            TransactionLocation::Unknown if batch.mined_header_hash.is_none() => {
                 eprintln!("Batch {} transaction not found on base node or mempool.", batch_id);
+                handle_missing_transaction(&mut conn, &batch, base_node, dropped_tx_timeout).await?;
+            },
+            TransactionLocation::Unknown => {},
+            // A pruned node cannot tell whether the transaction was mined, which is no evidence it is missing.
+            TransactionLocation::NotStored => {
+                eprintln!(
+                    "Batch {} transaction is in blocks the base node has pruned, cannot check it.",
+                    batch_id
+                );
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Resubmits the transaction of a batch the base node does not know. Once it has been missing for
+/// `dropped_tx_timeout` and the base node still rejects it, the transaction is declared dropped. Its
+/// payments are only batched again if the base node proves the inputs were spent by another
+/// transaction; otherwise they are failed, as batching them again could pay the recipients twice.
+async fn handle_missing_transaction(
+    conn: &mut SqliteConnection,
+    batch: &PaymentBatch,
+    base_node: &dyn BaseNode,
+    dropped_tx_timeout: Duration,
+) -> Result<(), anyhow::Error> {
+    let batch_id = &batch.id;
+    let signed_tx_json = batch
+        .signed_tx_json
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Batch {} has no signed_tx_json", batch_id))?;
+
+    let (code, reason) = match base_node.submit(signed_tx_json).await? {
+        Submission::Accepted => {
+            PaymentBatch::record_missing(conn, batch_id, true).await?;
+            println!("Batch {} transaction resubmitted to the base node.", batch_id);
+            return Ok(());
+        },
+        Submission::AlreadyMined => {
+            println!(
+                "Batch {} transaction is already mined, awaiting the base node.",
+                batch_id
+            );
+            return Ok(());
+        },
+        Submission::Rejected { code, reason } => (code, reason),
+    };
+
+    let Some(missing_since) = PaymentBatch::record_missing(conn, batch_id, false).await? else {
+        return Ok(());
+    };
+    let missing_for = (Utc::now() - missing_since).to_std().unwrap_or_default();
+    if missing_for < dropped_tx_timeout {
+        eprintln!("Batch {} transaction could not be resubmitted: {}", batch_id, reason);
+        return Ok(());
+    }
+
+    // A double spend rejection only says the inputs are spent, which they also are if this very
+    // transaction was mined since it was looked up. The kernel is looked up again, and the inputs only
+    // count as spent elsewhere if the base node positively does not know it.
+    let inputs_spent = if code == FailureCode::InputsSpent {
+        match base_node.transaction_location(signed_tx_json).await? {
+            TransactionLocation::Mined(_) | TransactionLocation::InMempool => {
+                PaymentBatch::clear_missing(conn, batch_id).await?;
+                println!(
+                    "Batch {} transaction reappeared on the base node after being rejected: {}",
+                    batch_id, reason
+                );
+                return Ok(());
             },
+            TransactionLocation::Unknown => true,
+            TransactionLocation::NotStored => false,
+        }
+    } else {
+        false
+    };
+
+    let mut error_message = format!(
+        "Transaction dropped after {} minutes missing from the base node: {}",
+        missing_for.as_secs() / 60,
+        reason
+    );
+    if code == FailureCode::InputsSpent && !inputs_spent {
+        error_message.push_str(
+            "; the base node has pruned the blocks that could show whether this transaction spent the inputs, \
+             check the chain before paying again",
+        );
+    } else if code == FailureCode::InputsUnknown {
+        error_message.push_str(
+            "; the base node does not know the inputs, so this transaction may still be mined, check the chain \
+             before paying again",
+        );
+    }
+    if PaymentBatch::update_to_dropped(
+        conn,
+        batch_id,
+        inputs_spent,
+        &error_message,
+        &Actor::ConfirmationChecker,
+    )
+    .await?
+    {
+        if inputs_spent {
+            eprintln!(
+                "Batch {} dropped, its payments will be batched again: {}",
+                batch_id, error_message
+            );
+        } else {
+            eprintln!("Batch {} dropped and its payments failed: {}", batch_id, error_message);
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{base_node::MockBaseNode, db::payment::PaymentStatus, test_support};
+
+    const SIGNED_TX_JSON: &str = r#"{"kernel":"confirmation-test"}"#;
+
+    fn inputs_spent() -> Submission {
+        Submission::Rejected {
+            code: FailureCode::InputsSpent,
+            reason: "DoubleSpend".to_string(),
+        }
+    }
+
+    async fn broadcast_batch(conn: &mut SqliteConnection) -> PaymentBatch {
+        let batch = test_support::create_batch(conn, 2, 1_000).await;
+        test_support::set_batch_status(conn, &batch.id, PaymentBatchStatus::AwaitingConfirmation).await;
+        test_support::set_batch_signed_tx(conn, &batch.id, SIGNED_TX_JSON).await;
+        test_support::get_batch(conn, &batch.id).await
+    }
+
+    async fn payment_statuses(conn: &mut SqliteConnection, batch_id: &str) -> Vec<(PaymentStatus, Option<String>)> {
+        Payment::find_by_batch_id(conn, batch_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|payment| (payment.status, payment.failure_code))
+            .collect()
+    }
+
+    async fn is_marked_missing(conn: &mut SqliteConnection, batch_id: &str) -> bool {
+        sqlx::query_scalar::<_, bool>("SELECT missing_since IS NOT NULL FROM payment_batches WHERE id = ?")
+            .bind(batch_id)
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn pruned_transaction_is_not_treated_as_missing(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = broadcast_batch(&mut conn).await;
+        let base_node = MockBaseNode::new(100)
+            .with_transaction(SIGNED_TX_JSON, TransactionLocation::NotStored)
+            .with_submission(SIGNED_TX_JSON, inputs_spent());
+
+        check_transaction_confirmations(&pool, &base_node, Duration::ZERO)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::AwaitingConfirmation);
+        assert!(!is_marked_missing(&mut conn, &batch.id).await);
+        let statuses = payment_statuses(&mut conn, &batch.id).await;
+        assert!(statuses.iter().all(|(status, _)| *status == PaymentStatus::Batched));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn missing_transaction_is_kept_until_timeout(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = broadcast_batch(&mut conn).await;
+        let base_node = MockBaseNode::new(100).with_submission(SIGNED_TX_JSON, inputs_spent());
+
+        check_transaction_confirmations(&pool, &base_node, Duration::from_secs(3600))
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::AwaitingConfirmation);
+        assert!(is_marked_missing(&mut conn, &batch.id).await);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn inputs_spent_by_another_transaction_rebatch_payments(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = broadcast_batch(&mut conn).await;
+        let base_node = MockBaseNode::new(100).with_submission(SIGNED_TX_JSON, inputs_spent());
+
+        check_transaction_confirmations(&pool, &base_node, Duration::ZERO)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Dropped);
+        let statuses = payment_statuses(&mut conn, &batch.id).await;
+        assert!(statuses.iter().all(|(status, _)| *status == PaymentStatus::Received));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn orphan_rejection_does_not_rebatch_payments(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = broadcast_batch(&mut conn).await;
+        // A base node that is behind does not know the transaction or its inputs.
+        let base_node = MockBaseNode::new(100).with_submission(
+            SIGNED_TX_JSON,
+            Submission::Rejected {
+                code: FailureCode::InputsUnknown,
+                reason: "Orphan".to_string(),
+            },
+        );
+
+        check_transaction_confirmations(&pool, &base_node, Duration::ZERO)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Dropped);
+        assert!(batch.error_message.unwrap().contains("may still be mined"));
+        let statuses = payment_statuses(&mut conn, &batch.id).await;
+        assert!(
+            statuses.iter().all(
+                |(status, code)| *status == PaymentStatus::Failed && code.as_deref() == Some("transaction_dropped")
+            )
+        );
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn transaction_found_after_rejection_is_not_dropped(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = broadcast_batch(&mut conn).await;
+        // The transaction was mined between the lookup and the resubmission, which then saw its own
+        // inputs spent.
+        let base_node = MockBaseNode::new(100)
+            .with_transaction(SIGNED_TX_JSON, TransactionLocation::InMempool)
+            .with_submission(SIGNED_TX_JSON, inputs_spent());
+
+        handle_missing_transaction(&mut conn, &batch, &base_node, Duration::ZERO)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::AwaitingConfirmation);
+        assert!(!is_marked_missing(&mut conn, &batch.id).await);
+        let statuses = payment_statuses(&mut conn, &batch.id).await;
+        assert!(statuses.iter().all(|(status, _)| *status == PaymentStatus::Batched));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn unprovable_spent_inputs_fail_payments(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = broadcast_batch(&mut conn).await;
+        let base_node = MockBaseNode::new(100)
+            .with_transaction(SIGNED_TX_JSON, TransactionLocation::NotStored)
+            .with_submission(SIGNED_TX_JSON, inputs_spent());
+
+        handle_missing_transaction(&mut conn, &batch, &base_node, Duration::ZERO)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Dropped);
+        let statuses = payment_statuses(&mut conn, &batch.id).await;
+        assert!(
+            statuses.iter().all(
+                |(status, code)| *status == PaymentStatus::Failed && code.as_deref() == Some("transaction_dropped")
+            )
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use sqlx::SqlitePool;
+use tokio::time::{self, Duration};
+
+use crate::db::{event::Actor, payment_batch::PaymentBatch};
+
+const DEFAULT_SLEEP_SECS: u64 = 60;
+
+pub async fn run(db_pool: SqlitePool, sleep_secs: Option<u64>) {
+    let sleep_secs = sleep_secs.unwrap_or(DEFAULT_SLEEP_SECS);
+    let mut interval = time::interval(Duration::from_secs(sleep_secs));
+    loop {
+        interval.tick().await;
+        if let Err(e) = recover_leases(&db_pool, false).await {
+            eprintln!("Lease Reaper worker error: {:?}", e);
+        }
+    }
+}
+
+/// Recovers every in-flight batch left behind by a previous run of the service.
+/// Must be called before the signing and broadcast workers are started.
+pub async fn recover_on_startup(db_pool: &SqlitePool) -> Result<(), anyhow::Error> {
+    recover_leases(db_pool, true).await
+}
+
+async fn recover_leases(db_pool: &SqlitePool, include_active: bool) -> Result<(), anyhow::Error> {
+    let mut conn = db_pool.acquire().await?;
+    let recovered = PaymentBatch::recover_leases(&mut conn, include_active, &Actor::LeaseReaper).await?;
+
+    for batch in recovered {
+        println!(
+            "Recovered batch {} from {} (lease expired at {:?}).",
+            batch.id, batch.status, batch.lease_expires_at
+        );
+    }
+
+    Ok(())
+}
@@ -1,5 +1,9 @@
 pub mod batch_creator;
 pub mod broadcaster;
 pub mod confirmation_checker;
+pub mod lease_reaper;
+pub mod payout_scheduler;
+pub mod reorg_watcher;
 pub mod transaction_signer;
 pub mod unsigned_tx_creator;
+pub mod webhook_dispatcher;
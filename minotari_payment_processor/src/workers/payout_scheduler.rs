@@ -0,0 +1,205 @@
+use anyhow::anyhow;
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+use tokio::time::{self, Duration};
+
+use crate::db::{
+    cadence::Cadence,
+    event::{AccountAlert, Actor, AlertKind},
+    payment::{NewPayment, Payment},
+    payout_schedule::{PayoutRun, PayoutRunStatus, PayoutSchedule},
+};
+
+const DEFAULT_SLEEP_SECS: u64 = 60;
+const SCHEDULES_PER_PASS: i64 = 100;
+/// The most occurrences of one schedule handled per pass, bounding the work after a long downtime.
+const MAX_OCCURRENCES_PER_PASS: usize = 1_000;
+
+pub async fn run(db_pool: SqlitePool, sleep_secs: Option<u64>) {
+    let sleep_secs = sleep_secs.unwrap_or(DEFAULT_SLEEP_SECS);
+    // An occurrence may come due just after a pass and wait out a sleep, or two under load, before it is
+    // seen. Only occurrences older than that were missed.
+    let grace = TimeDelta::seconds(2 * sleep_secs as i64);
+    loop {
+        let mut should_sleep = true;
+        match process_due_schedules(&db_pool, grace).await {
+            Ok(more_runs_expected) => {
+                if more_runs_expected {
+                    should_sleep = false;
+                }
+            },
+            Err(e) => {
+                eprintln!("Payout Scheduler worker error: {:?}", e);
+            },
+        }
+
+        if should_sleep {
+            time::sleep(Duration::from_secs(sleep_secs)).await;
+        }
+    }
+}
+
+async fn process_due_schedules(db_pool: &SqlitePool, grace: TimeDelta) -> Result<bool, anyhow::Error> {
+    let mut conn = db_pool.acquire().await?;
+    let schedules = PayoutSchedule::find_due(&mut conn, SCHEDULES_PER_PASS).await?;
+
+    let mut more_runs_expected = schedules.len() as i64 == SCHEDULES_PER_PASS;
+    for schedule in schedules {
+        match run_schedule(&mut conn, &schedule, grace).await {
+            Ok(caught_up) => more_runs_expected |= !caught_up,
+            Err(e) => eprintln!("Failed to run payout schedule {}: {:?}", schedule.id, e),
+        }
+    }
+
+    Ok(more_runs_expected)
+}
+
+/// Creates the payments for the schedule's latest due occurrence and any others that came due within
+/// `grace`. Older occurrences that were not run, e.g. because the processor was down, are recorded as
+/// missed and reported in an account alert rather than paid late. Returns whether the schedule has
+/// caught up.
+async fn run_schedule(
+    conn: &mut SqliteConnection,
+    schedule: &PayoutSchedule,
+    grace: TimeDelta,
+) -> Result<bool, anyhow::Error> {
+    let cadence: Cadence = schedule
+        .cadence
+        .parse()
+        .map_err(|e| anyhow!("Invalid cadence: {}", e))?;
+    let now = Utc::now();
+
+    let mut occurrences = vec![schedule.next_run_at];
+    let mut next_run_at = cadence.next_after(schedule.next_run_at);
+    while let Some(at) = next_run_at
+        && at <= now
+        && occurrences.len() < MAX_OCCURRENCES_PER_PASS
+    {
+        occurrences.push(at);
+        next_run_at = cadence.next_after(at);
+    }
+    let next_run_at = next_run_at.ok_or_else(|| anyhow!("Cadence {} has no further occurrences", cadence))?;
+    let caught_up = next_run_at > now;
+    // The latest occurrence due is paid however late, as are those that came due within the grace window.
+    let latest = occurrences.last().copied().filter(|_| caught_up);
+    let (to_run, missed): (Vec<DateTime<Utc>>, Vec<DateTime<Utc>>) = occurrences
+        .into_iter()
+        .partition(|at| *at > now - grace || Some(*at) == latest);
+
+    let mut tx = conn.begin().await?;
+    for at in &missed {
+        PayoutRun::record(&mut tx, &schedule.id, *at, PayoutRunStatus::Missed, None).await?;
+    }
+    for at in &to_run {
+        let payment = create_payment(&mut tx, schedule, *at).await?;
+        PayoutRun::record(&mut tx, &schedule.id, *at, PayoutRunStatus::Created, Some(&payment.id)).await?;
+    }
+    if !PayoutSchedule::advance(&mut tx, &schedule.id, schedule.next_run_at, next_run_at).await? {
+        // Edited, paused or deleted since it was read. Dropping the transaction discards the
+        // runs, and the schedule is looked at again on the next pass.
+        return Ok(true);
+    }
+    if let (Some(first), Some(last)) = (missed.first(), missed.last()) {
+        let detail = format!(
+            "Payout schedule {} missed {} runs from {} to {}, which were not paid",
+            schedule.id,
+            missed.len(),
+            first,
+            last
+        );
+        eprintln!("Account {}: {}", schedule.account_name, detail);
+        AccountAlert::record(&mut tx, &schedule.account_name, AlertKind::MissedPayoutRuns, &detail).await?;
+    }
+
+    tx.commit().await?;
+    Ok(caught_up)
+}
+
+/// Creates the payment for one occurrence. The `client_id` is derived from the schedule and the
+/// occurrence, so an occurrence is never paid twice.
+async fn create_payment(
+    conn: &mut SqliteConnection,
+    schedule: &PayoutSchedule,
+    scheduled_for: DateTime<Utc>,
+) -> Result<Payment, sqlx::Error> {
+    let client_id = format!(
+        "payout-schedule:{}:{}",
+        schedule.id,
+        scheduled_for.format("%Y%m%dT%H%MZ")
+    );
+    if let Some(payment) = Payment::get_by_client_id(conn, &client_id, &schedule.account_name).await? {
+        return Ok(payment);
+    }
+
+    let new_payment = NewPayment {
+        client_id: &client_id,
+        account_name: &schedule.account_name,
+        recipient_address: &schedule.recipient_address,
+        amount: schedule.amount,
+        payment_id: schedule.payment_id.as_deref(),
+        callback_url: schedule.callback_url.as_deref(),
+        request_fingerprint: None,
+        execute_after: None,
+    };
+    Payment::create(conn, &new_payment, &Actor::PayoutScheduler).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Timelike;
+
+    use super::*;
+    use crate::{db::payout_schedule::NewPayoutSchedule, test_support};
+
+    async fn create_schedule(conn: &mut SqliteConnection, cadence: &str, next_run_at: DateTime<Utc>) -> PayoutSchedule {
+        let cadence: Cadence = cadence.parse().unwrap();
+        let new_schedule = NewPayoutSchedule {
+            account_name: test_support::ACCOUNT_NAME,
+            recipient_address: "recipient",
+            amount: 1_000,
+            payment_id: None,
+            callback_url: None,
+            cadence: &cadence,
+            is_paused: false,
+            next_run_at,
+        };
+        PayoutSchedule::create(conn, &new_schedule).await.unwrap()
+    }
+
+    /// The statuses of the schedule's runs, oldest first.
+    async fn run_statuses(conn: &mut SqliteConnection, schedule_id: &str) -> Vec<PayoutRunStatus> {
+        let mut runs = PayoutRun::find_by_schedule_id(conn, schedule_id, 100).await.unwrap();
+        runs.reverse();
+        runs.into_iter().map(|run| run.status).collect()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn occurrences_within_grace_are_paid(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let now = Utc::now().with_second(0).unwrap().with_nanosecond(0).unwrap();
+        // Every minute, with at least the last three occurrences due, all within the grace window.
+        let schedule = create_schedule(&mut conn, "* * * * *", now - TimeDelta::minutes(2)).await;
+
+        let caught_up = run_schedule(&mut conn, &schedule, TimeDelta::minutes(3)).await.unwrap();
+
+        assert!(caught_up);
+        let statuses = run_statuses(&mut conn, &schedule.id).await;
+        assert!(statuses.len() >= 3);
+        assert!(statuses.iter().all(|status| *status == PayoutRunStatus::Created));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn occurrences_older_than_grace_are_missed(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let now = Utc::now().with_second(0).unwrap().with_nanosecond(0).unwrap();
+        let schedule = create_schedule(&mut conn, "0 * * * *", now - TimeDelta::hours(3)).await;
+
+        run_schedule(&mut conn, &schedule, TimeDelta::minutes(2)).await.unwrap();
+
+        let statuses = run_statuses(&mut conn, &schedule.id).await;
+        let (latest, earlier) = statuses.split_last().unwrap();
+        assert_eq!(*latest, PayoutRunStatus::Created);
+        assert!(earlier.len() >= 2);
+        assert!(earlier.iter().all(|status| *status == PayoutRunStatus::Missed));
+    }
+}
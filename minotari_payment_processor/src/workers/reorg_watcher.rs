@@ -0,0 +1,221 @@
+use anyhow::anyhow;
+use minotari_node_wallet_client::http::Client;
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+use tokio::time::{self, Duration};
+
+use crate::{
+    base_node::{BaseNode, TransactionLocation},
+    db::{
+        event::{AccountAlert, Actor, AlertKind},
+        payment_batch::{PaymentBatch, PaymentBatchStatus},
+    },
+};
+
+const DEFAULT_SLEEP_SECS: u64 = 120;
+/// How far below the tip confirmed batches are still re-checked, about a day of blocks. Reorgs
+/// deeper than this go unnoticed.
+const REORG_WATCH_DEPTH: u64 = 720;
+
+pub async fn run(db_pool: SqlitePool, base_node_client: Client, sleep_secs: Option<u64>) {
+    let sleep_secs = sleep_secs.unwrap_or(DEFAULT_SLEEP_SECS);
+    let mut interval = time::interval(Duration::from_secs(sleep_secs));
+    loop {
+        interval.tick().await;
+        if let Err(e) = check_for_reorgs(&db_pool, &base_node_client).await {
+            eprintln!("Reorg Watcher worker error: {:?}", e);
+        }
+    }
+}
+
+async fn check_for_reorgs(db_pool: &SqlitePool, base_node: &dyn BaseNode) -> Result<(), anyhow::Error> {
+    let mut conn = db_pool.acquire().await?;
+
+    let best_block_height = base_node.best_block_height().await?;
+    let min_confirmed_height = best_block_height.saturating_sub(REORG_WATCH_DEPTH) as i64;
+
+    let batches = PaymentBatch::find_reorg_candidates(&mut conn, min_confirmed_height).await?;
+    for batch in batches {
+        if let Err(e) = check_batch(&mut conn, &batch, base_node).await {
+            eprintln!("Reorg check failed for batch {}: {:?}", batch.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn check_batch(
+    conn: &mut SqliteConnection,
+    batch: &PaymentBatch,
+    base_node: &dyn BaseNode,
+) -> Result<(), anyhow::Error> {
+    let batch_id = &batch.id;
+    let recorded_hash = batch
+        .mined_header_hash
+        .as_deref()
+        .ok_or_else(|| anyhow!("Batch {} has no mined_header_hash", batch_id))?;
+    let recorded_height = batch.mined_height.unwrap_or_default();
+    let signed_tx_json = batch
+        .signed_tx_json
+        .as_deref()
+        .ok_or_else(|| anyhow!("Batch {} has no signed_tx_json", batch_id))?;
+    let (to, block, detail) = match base_node.transaction_location(signed_tx_json).await? {
+        TransactionLocation::Mined(block) if hex::encode(&block.header_hash) == recorded_hash => return Ok(()),
+        TransactionLocation::Mined(block) => {
+            let detail = format!(
+                "Reorg: transaction moved from block {} at height {} to block {} at height {}",
+                recorded_hash,
+                recorded_height,
+                hex::encode(&block.header_hash),
+                block.height
+            );
+            (PaymentBatchStatus::AwaitingConfirmation, Some(block), detail)
+        },
+        TransactionLocation::InMempool => {
+            let detail = format!(
+                "Reorg: block {} at height {} left the best chain, transaction is back in the mempool",
+                recorded_hash, recorded_height
+            );
+            (PaymentBatchStatus::AwaitingConfirmation, None, detail)
+        },
+        TransactionLocation::Unknown => {
+            let detail = format!(
+                "Reorg: block {} at height {} left the best chain and the base node no longer knows the transaction",
+                recorded_hash, recorded_height
+            );
+            (PaymentBatchStatus::Reorged, None, detail)
+        },
+        // A pruned node cannot tell, which is no evidence of a reorg.
+        TransactionLocation::NotStored => return Ok(()),
+    };
+
+    let mut tx = conn.begin().await?;
+    let updated = PaymentBatch::update_after_reorg(
+        &mut tx,
+        batch_id,
+        batch.status.clone(),
+        recorded_hash,
+        to.clone(),
+        block.as_ref(),
+        &detail,
+        &Actor::ReorgWatcher,
+    )
+    .await?;
+    if updated && to == PaymentBatchStatus::Reorged {
+        AccountAlert::record(&mut tx, &batch.account_name, AlertKind::BatchReorged, &detail).await?;
+    }
+    tx.commit().await?;
+
+    if updated {
+        eprintln!("Batch {} moved from {} to {}: {}", batch_id, batch.status, to, detail);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::{
+        base_node::MockBaseNode,
+        db::{
+            event::AccountAlert,
+            payment::{Payment, PaymentStatus},
+            payment_batch::MinedBlock,
+        },
+        test_support::{self, ACCOUNT_NAME},
+    };
+
+    const SIGNED_TX_JSON: &str = r#"{"kernel":"reorg-test"}"#;
+    const MINED_HEIGHT: i64 = 100;
+    const TIP_HEIGHT: u64 = 105;
+
+    async fn mined_batch(conn: &mut SqliteConnection, status: PaymentBatchStatus) -> PaymentBatch {
+        let batch = test_support::create_batch(conn, 2, 1_000).await;
+        test_support::set_batch_status(conn, &batch.id, status).await;
+        test_support::set_batch_signed_tx(conn, &batch.id, SIGNED_TX_JSON).await;
+        test_support::set_batch_mined(conn, &batch.id, MINED_HEIGHT, &[1; 32]).await;
+        batch
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn shallow_reorg_moves_batch_to_new_block(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = mined_batch(&mut conn, PaymentBatchStatus::AwaitingConfirmation).await;
+        let new_block = MinedBlock {
+            height: MINED_HEIGHT as u64 + 1,
+            header_hash: vec![2; 32],
+            timestamp: 1_700_000_100,
+        };
+        let base_node = MockBaseNode::new(TIP_HEIGHT)
+            .with_transaction(SIGNED_TX_JSON, TransactionLocation::Mined(new_block.clone()));
+
+        check_for_reorgs(&pool, &base_node).await.unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::AwaitingConfirmation);
+        assert_eq!(batch.mined_height, Some(new_block.height as i64));
+        assert_eq!(batch.mined_header_hash, Some(hex::encode(&new_block.header_hash)));
+        assert_eq!(batch.mined_timestamp, Some(new_block.timestamp as i64));
+        let alerts = AccountAlert::find_by_account_name(&mut conn, ACCOUNT_NAME, 10)
+            .await
+            .unwrap();
+        assert!(alerts.is_empty());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn deep_reorg_marks_batch_reorged_and_alerts(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = mined_batch(&mut conn, PaymentBatchStatus::AwaitingConfirmation).await;
+        let base_node = MockBaseNode::new(TIP_HEIGHT);
+
+        check_for_reorgs(&pool, &base_node).await.unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Reorged);
+        assert_eq!(batch.mined_header_hash, None);
+        let alerts = AccountAlert::find_by_account_name(&mut conn, ACCOUNT_NAME, 10)
+            .await
+            .unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, AlertKind::BatchReorged);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn reorged_confirmed_batch_unconfirms_payments(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = mined_batch(&mut conn, PaymentBatchStatus::Confirmed).await;
+        test_support::set_payments_status(&mut conn, &batch.id, PaymentStatus::Confirmed).await;
+        let base_node = MockBaseNode::new(TIP_HEIGHT);
+
+        check_for_reorgs(&pool, &base_node).await.unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Reorged);
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+        assert_eq!(payments.len(), 2);
+        assert!(payments.iter().all(|payment| payment.status == PaymentStatus::Batched));
+        let reorged_deliveries: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM webhook_deliveries WHERE event_type = 'payment.reorged' AND url = ?",
+        )
+        .bind(test_support::CALLBACK_URL)
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(reorged_deliveries, 2);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn pruned_node_leaves_batch_alone(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = mined_batch(&mut conn, PaymentBatchStatus::Confirmed).await;
+        let base_node = MockBaseNode::new(TIP_HEIGHT).with_transaction(SIGNED_TX_JSON, TransactionLocation::NotStored);
+
+        check_for_reorgs(&pool, &base_node).await.unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Confirmed);
+        assert_eq!(batch.mined_header_hash, Some(hex::encode([1; 32])));
+    }
+}
@@ -1,24 +1,28 @@
-use sqlx::SqlitePool;
-use std::io::Write;
-use tempfile::NamedTempFile;
-use tokio::fs;
+use anyhow::anyhow;
+use sqlx::{SqliteConnection, SqlitePool};
+use std::sync::Arc;
 use tokio::time::{self, Duration};
 
-use crate::db::payment_batch::{PaymentBatch, PaymentBatchStatus};
+use crate::{
+    db::{
+        event::Actor,
+        payment::FailureCode,
+        payment_batch::{LEASE_DURATION_SECS, Lease, PaymentBatch, PaymentBatchStatus, RetryBackoff},
+    },
+    signer::{SignerBackend, Signers, SigningError, SigningRequest, TransactionSigner},
+};
 
 const DEFAULT_SLEEP_SECS: u64 = 10;
+// Signing is abandoned well before the signing lease expires, so a reclaimed batch can never
+// end up with a second signature from a run that outlived its lease.
+const SIGNING_TIMEOUT_SECS: u64 = LEASE_DURATION_SECS / 2;
 
-pub async fn run(
-    db_pool: SqlitePool,
-    console_wallet_path: String,
-    console_wallet_password: String,
-    sleep_secs: Option<u64>,
-) {
+pub async fn run(db_pool: SqlitePool, signers: Arc<Signers>, retry_backoff: RetryBackoff, sleep_secs: Option<u64>) {
     let sleep_secs = sleep_secs.unwrap_or(DEFAULT_SLEEP_SECS);
     let mut interval = time::interval(Duration::from_secs(sleep_secs));
     loop {
         interval.tick().await;
-        if let Err(e) = process_transactions_to_sign(&db_pool, &console_wallet_path, &console_wallet_password).await {
+        if let Err(e) = process_transactions_to_sign(&db_pool, &signers, &retry_backoff).await {
             eprintln!("Transaction Signer worker error: {:?}", e);
         }
     }
@@ -26,72 +30,221 @@ pub async fn run(
 
 async fn process_transactions_to_sign(
     db_pool: &SqlitePool,
-    console_wallet_path: &str,
-    console_wallet_password: &str,
+    signers: &Signers,
+    retry_backoff: &RetryBackoff,
 ) -> Result<(), anyhow::Error> {
     let mut conn = db_pool.acquire().await?;
     let batches = PaymentBatch::find_by_status(&mut conn, PaymentBatchStatus::AwaitingSignature).await?;
 
     for batch in batches {
+        // Batches of manually signed accounts wait for their signed transaction to be uploaded.
+        let Some((backend, signer)) = signers.for_account(&batch.account_name) else {
+            continue;
+        };
+
         // Update its status to `SIGNING_IN_PROGRESS` to prevent other workers from picking it up.
-        PaymentBatch::update_to_signing_in_progress(&mut conn, &batch.id).await?;
-
-        let batch_id = batch.id.clone();
-        let unsigned_tx_json = batch
-            .unsigned_tx_json
-            .clone()
-            .ok_or_else(|| anyhow::anyhow!("Batch {} has no unsigned_tx_json", batch_id))?;
-
-        // Create temporary input file
-        let mut input_file = NamedTempFile::with_prefix("unsigned-tx-")?;
-        input_file.write_all(unsigned_tx_json.as_bytes())?;
-        let input_file_path = input_file.path().to_path_buf();
-
-        // Create temporary output file
-        let output_file = NamedTempFile::with_prefix("signed-tx-")?;
-        let output_file_path = output_file.path().to_path_buf();
-
-        let batch_id_clone = batch_id.clone();
-        let input_path_clone = input_file_path.clone();
-        let output_path_clone = output_file_path.clone();
-
-        let console_wallet_path: String = console_wallet_path.to_string().clone();
-        let console_wallet_password = console_wallet_password.to_string().clone();
-        let signing_result = tokio::task::spawn_blocking(move || {
-            std::process::Command::new(console_wallet_path)
-                .env("MINOTARI_WALLET_PASSWORD", console_wallet_password)
-                .arg("sign-one-sided-transaction")
-                .arg("--input-file")
-                .arg(&input_path_clone)
-                .arg("--output-file")
-                .arg(&output_path_clone)
-                .output()
-        })
-        .await?;
-
-        match signing_result {
-            Ok(output) => {
-                if output.status.success() {
-                    // On CLI Success (exit code 0)
-                    let signed_tx_json = fs::read_to_string(&output_file_path).await?;
-                    PaymentBatch::update_to_awaiting_broadcast(&mut conn, &batch_id_clone, &signed_tx_json).await?;
-                } else {
-                    // On CLI Failure (non-zero exit code)
-                    let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-                    eprintln!("CLI signing failed for batch {}: {}", batch_id_clone, error_message);
-                    PaymentBatch::update_to_failed(&mut conn, &batch_id_clone, &error_message).await?;
-                }
-            },
-            Err(e) => {
+        let Some(lease) =
+            PaymentBatch::update_to_signing_in_progress(&mut conn, &batch.id, &Actor::TransactionSigner).await?
+        else {
+            continue;
+        };
+
+        if let Err(e) = sign_batch(&mut conn, &batch, &lease, backend, signer).await {
+            let error_message = format!("Signing attempt failed for batch {}: {:?}", batch.id, e);
+            eprintln!("{}", error_message);
+            PaymentBatch::release_lease(
+                &mut conn,
+                &batch.id,
+                &lease,
+                FailureCode::SignerUnavailable,
+                &error_message,
+                retry_backoff,
+                &Actor::TransactionSigner,
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn sign_batch(
+    conn: &mut SqliteConnection,
+    batch: &PaymentBatch,
+    lease: &Lease,
+    backend: SignerBackend,
+    signer: &dyn TransactionSigner,
+) -> Result<(), anyhow::Error> {
+    let batch_id = &batch.id;
+    let unsigned_tx_json = batch
+        .unsigned_tx_json
+        .as_deref()
+        .ok_or_else(|| anyhow!("Batch {} has no unsigned_tx_json", batch_id))?;
+
+    let request = SigningRequest {
+        batch_id,
+        account_name: &batch.account_name,
+        unsigned_tx_json,
+    };
+    let signing_result = time::timeout(Duration::from_secs(SIGNING_TIMEOUT_SECS), signer.sign(&request))
+        .await
+        .map_err(|_| anyhow!("{} signer timed out after {}s", backend, SIGNING_TIMEOUT_SECS))?;
+
+    match signing_result {
+        Ok(signed_tx_json) => {
+            if !PaymentBatch::update_to_awaiting_broadcast(
+                conn,
+                batch_id,
+                lease,
+                &signed_tx_json,
+                &Actor::TransactionSigner,
+            )
+            .await?
+            {
                 eprintln!(
-                    "Failed to execute minotari_console_wallet for batch {}: {:?}",
-                    batch_id_clone, e
+                    "Signing lease for batch {} was lost, discarding the signed transaction.",
+                    batch_id
                 );
-                PaymentBatch::update_to_failed(&mut conn, &batch_id_clone, &format!("CLI execution error: {:?}", e))
-                    .await?;
-            },
-        }
+            }
+        },
+        Err(SigningError::Rejected(error_message)) => {
+            eprintln!("{} signer rejected batch {}: {}", backend, batch_id, error_message);
+            if !PaymentBatch::update_to_failed_under_lease(
+                conn,
+                batch_id,
+                lease,
+                FailureCode::SigningRejected,
+                &error_message,
+                &Actor::TransactionSigner,
+            )
+            .await?
+            {
+                eprintln!(
+                    "Signing lease for batch {} was lost, leaving the rejection to the current holder.",
+                    batch_id
+                );
+            }
+        },
+        // Released by the caller, so the batch is signed again on a later pass.
+        Err(e @ SigningError::Unavailable(_)) => return Err(anyhow!("{} signer: {}", backend, e)),
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::payment::{Payment, PaymentStatus},
+        signer::MockSigner,
+        test_support,
+    };
+
+    const RETRY_BACKOFF: RetryBackoff = RetryBackoff {
+        base_secs: 60,
+        max_secs: 600,
+    };
+
+    /// Creates a batch whose unsigned transaction awaits signing.
+    async fn create_batch_to_sign(conn: &mut SqliteConnection) -> PaymentBatch {
+        let batch = test_support::create_batch(conn, 2, 1_000).await;
+        assert!(
+            PaymentBatch::update_to_awaiting_signature(
+                conn,
+                &batch.id,
+                &batch.pr_idempotency_key,
+                r#"{"unsigned": true}"#,
+                None,
+                3_600,
+                &Actor::UnsignedTxCreator,
+            )
+            .await
+            .unwrap()
+        );
+        batch
+    }
+
+    async fn payment_statuses(conn: &mut SqliteConnection, batch_id: &str) -> Vec<PaymentStatus> {
+        Payment::find_by_batch_id(conn, batch_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|payment| payment.status)
+            .collect()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn signed_batch_awaits_broadcast(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = create_batch_to_sign(&mut conn).await;
+
+        process_transactions_to_sign(&pool, &Signers::mock(MockSigner::new()), &RETRY_BACKOFF)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::AwaitingBroadcast);
+        let signed_tx: serde_json::Value = serde_json::from_str(batch.signed_tx_json.as_deref().unwrap()).unwrap();
+        assert_eq!(signed_tx["batch_id"], batch.id.as_str());
+        assert_eq!(batch.lease_expires_at, None);
+        assert_eq!(
+            payment_statuses(&mut conn, &batch.id).await,
+            vec![PaymentStatus::Batched; 2]
+        );
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn rejected_batch_fails(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = create_batch_to_sign(&mut conn).await;
+        let signers = Signers::mock(MockSigner::rejecting("key not authorised"));
+
+        process_transactions_to_sign(&pool, &signers, &RETRY_BACKOFF)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Failed);
+        assert_eq!(batch.signed_tx_json, None);
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+        assert_eq!(payments.len(), 2);
+        for payment in payments {
+            assert_eq!(payment.status, PaymentStatus::Failed);
+            assert_eq!(payment.failure_code.as_deref(), Some("signing_rejected"));
+            assert_eq!(payment.failure_reason.as_deref(), Some("key not authorised"));
+        }
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn unavailable_signer_releases_batch_with_backoff(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = create_batch_to_sign(&mut conn).await;
+        let signers = Signers::mock(MockSigner::unavailable("wallet busy"));
+
+        process_transactions_to_sign(&pool, &signers, &RETRY_BACKOFF)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::AwaitingSignature);
+        assert_eq!(batch.retry_count, 1);
+        assert_eq!(batch.lease_expires_at, None);
+        assert!(batch.error_message.unwrap().contains("wallet busy"));
+        // The first retry waits the base delay plus at most a quarter of it as jitter.
+        let wait = (batch.next_retry_at.unwrap() - chrono::Utc::now()).num_seconds();
+        assert!((55..=75).contains(&wait), "retry in {}s", wait);
+        assert_eq!(
+            payment_statuses(&mut conn, &batch.id).await,
+            vec![PaymentStatus::Batched; 2]
+        );
+
+        // Not picked up again before then.
+        let signers = Signers::mock(MockSigner::new());
+        process_transactions_to_sign(&pool, &signers, &RETRY_BACKOFF)
+            .await
+            .unwrap();
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::AwaitingSignature);
+    }
+}
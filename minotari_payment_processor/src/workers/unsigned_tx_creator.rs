@@ -1,21 +1,41 @@
-use minotari_client::apis::{Error as ApiError, accounts_api, configuration::Configuration};
-use minotari_client::models::{CreateTransactionRequest, RecipientRequest};
-use sqlx::SqlitePool;
+use minotari_client::apis::{
+    Error as ApiError,
+    accounts_api::{self, ApiCreateUnsignedTransactionError},
+    configuration::Configuration,
+};
+use minotari_client::models::{self, CreateTransactionRequest, RecipientRequest};
+use reqwest::StatusCode;
+use sqlx::{SqliteConnection, SqlitePool};
 use std::sync::Arc;
 use tokio::time::{self, Duration};
 
-use crate::db::payment_batch::PaymentBatchStatus;
-use crate::db::{payment::Payment, payment_batch::PaymentBatch};
+use crate::db::payment_batch::{Lease, PaymentBatchStatus, RetryBackoff};
+use crate::db::{
+    approval::AccountApprovalPolicy,
+    event::Actor,
+    payment::{FailureCode, Payment},
+    payment_batch::PaymentBatch,
+};
 
 const DEFAULT_SLEEP_SECS: u64 = 15;
+/// How long PR reserves the UTXOs of an unsigned transaction by default. Long enough for approvals and
+/// manual signing; a batch rebuilt after a cancel that PR cannot fund without them waits this long.
+const DEFAULT_UTXO_LOCK_SECS: u64 = 24 * 60 * 60;
 
-pub async fn run(db_pool: SqlitePool, client_config: Arc<Configuration>, sleep_secs: Option<u64>) {
+pub async fn run(
+    db_pool: SqlitePool,
+    client_config: Arc<Configuration>,
+    retry_backoff: RetryBackoff,
+    utxo_lock_secs: Option<u64>,
+    sleep_secs: Option<u64>,
+) {
+    let utxo_lock_secs = utxo_lock_secs.unwrap_or(DEFAULT_UTXO_LOCK_SECS);
     let sleep_secs = sleep_secs.unwrap_or(DEFAULT_SLEEP_SECS);
     let mut interval = time::interval(Duration::from_secs(sleep_secs));
 
     loop {
         interval.tick().await;
-        if let Err(e) = process_unsigned_transactions(&db_pool, &client_config).await {
+        if let Err(e) = process_unsigned_transactions(&db_pool, &client_config, &retry_backoff, utxo_lock_secs).await {
             eprintln!("Unsigned Transaction Creator worker error: {:?}", e);
         }
     }
@@ -24,12 +44,29 @@ pub async fn run(db_pool: SqlitePool, client_config: Arc<Configuration>, sleep_s
 async fn process_unsigned_transactions(
     db_pool: &SqlitePool,
     client_config: &Configuration,
+    retry_backoff: &RetryBackoff,
+    utxo_lock_secs: u64,
 ) -> Result<(), anyhow::Error> {
     let mut conn = db_pool.acquire().await?;
     let batches = PaymentBatch::find_by_status(&mut conn, PaymentBatchStatus::PendingBatching).await?;
 
     for batch in batches {
+        // Keeps the batch from being cancelled from while PR builds its transaction.
+        let Some(lease) = PaymentBatch::claim_for_unsigned_tx(&mut conn, &batch.id, &batch.pr_idempotency_key).await?
+        else {
+            continue;
+        };
         let associated_payments = Payment::find_by_batch_id(&mut conn, &batch.id).await?;
+        // Evaluated on the batch as it is now, so a batch rebuilt smaller may no longer need approval.
+        let total_amount: i64 = associated_payments.iter().map(|p| p.amount).sum();
+        let approvals_required = AccountApprovalPolicy::find_by_account_name(&mut conn, &batch.account_name)
+            .await?
+            .filter(|account_policy| {
+                account_policy
+                    .policy
+                    .applies_to(total_amount, associated_payments.len() as i64)
+            })
+            .map(|account_policy| account_policy.policy.required_approvals);
         let recipients: Vec<RecipientRequest> = associated_payments
             .into_iter()
             .map(|p| RecipientRequest {
@@ -38,36 +75,493 @@ async fn process_unsigned_transactions(
                 payment_id: p.payment_id.map(Some),
             })
             .collect();
+        let payment_count = recipients.len();
 
         let request_body = CreateTransactionRequest {
-            idempotency_key: Some(Some(batch.pr_idempotency_key)),
+            idempotency_key: Some(Some(batch.pr_idempotency_key.clone())),
             recipients,
-            seconds_to_lock_utxos: None, // Use default lock timeout
+            // Set explicitly, so the batch knows when the UTXOs are free again.
+            seconds_to_lock_utxos: Some(Some(utxo_lock_secs as i64)),
         };
 
         match accounts_api::api_create_unsigned_transaction(client_config, &batch.account_name, request_body).await {
             Ok(response) => {
                 let response_text = serde_json::to_string(&response)?;
-                PaymentBatch::update_to_awaiting_signature(&mut conn, &batch.id, &response_text).await?;
-            },
-            Err(ApiError::ResponseError(response_content)) => {
-                let status = response_content.status;
-                let response_text = response_content.content;
-
-                let error_message = format!(
-                    "PR API returned unexpected status for batch {}: {} - {}",
-                    batch.id, status, response_text
-                );
-                eprintln!("{}", error_message);
-                PaymentBatch::increment_retry_count(&mut conn, &batch.id, &error_message).await?;
+                if !PaymentBatch::update_to_awaiting_signature(
+                    &mut conn,
+                    &batch.id,
+                    &batch.pr_idempotency_key,
+                    &response_text,
+                    approvals_required,
+                    utxo_lock_secs,
+                    &Actor::UnsignedTxCreator,
+                )
+                .await?
+                {
+                    eprintln!(
+                        "Batch {} was rebuilt while its unsigned transaction was created, discarding it",
+                        batch.id
+                    );
+                }
             },
             Err(e) => {
-                let error_message = format!("Network error calling PR API for batch {}: {:?}", batch.id, e);
-                eprintln!("{}", error_message);
-                PaymentBatch::increment_retry_count(&mut conn, &batch.id, &error_message).await?;
+                let code = classify_pr_error(&e);
+                let error_message = match e {
+                    ApiError::ResponseError(response_content) => format!(
+                        "PR API returned unexpected status for batch {}: {} - {}",
+                        batch.id, response_content.status, response_content.content
+                    ),
+                    e => format!("Network error calling PR API for batch {}: {:?}", batch.id, e),
+                };
+                eprintln!("{} ({})", error_message, code);
+                record_failure(
+                    &mut conn,
+                    &batch.id,
+                    &lease,
+                    payment_count,
+                    code,
+                    &error_message,
+                    retry_backoff,
+                )
+                .await?;
             },
         }
     }
 
     Ok(())
 }
+
+/// Maps a failed call to PR onto a [`FailureCode`]. PR's error body names the failure. A response without one
+/// did not come from PR's handler, e.g. a `404` or `401` from a wrong `PAYMENT_RECEIVER` path or a proxy, so it
+/// is treated as PR being unavailable and retried rather than blamed on the request.
+fn classify_pr_error(error: &ApiError<ApiCreateUnsignedTransactionError>) -> FailureCode {
+    let ApiError::ResponseError(response_content) = error else {
+        return FailureCode::PaymentReceiverUnavailable;
+    };
+    let status = response_content.status;
+    // The entity is parsed from the body alone, so any of the status variants may hold any error.
+    let pr_error = match &response_content.entity {
+        Some(
+            ApiCreateUnsignedTransactionError::Status400(pr_error)
+            | ApiCreateUnsignedTransactionError::Status404(pr_error)
+            | ApiCreateUnsignedTransactionError::Status500(pr_error),
+        ) => Some(pr_error),
+        _ => None,
+    };
+
+    match pr_error {
+        Some(models::ApiError::ApiErrorOneOf2(_)) => FailureCode::AccountNotFound,
+        Some(models::ApiError::ApiErrorOneOf3(_)) if status == StatusCode::BAD_REQUEST => {
+            FailureCode::InvalidTransactionRequest
+        },
+        Some(models::ApiError::ApiErrorOneOf3(_)) => FailureCode::UnsignedTxFailed,
+        // InternalServerError and DbError
+        Some(models::ApiError::ApiErrorOneOf(_) | models::ApiError::ApiErrorOneOf1(_)) | None => {
+            FailureCode::PaymentReceiverUnavailable
+        },
+    }
+}
+
+/// Retries a batch after a retryable failure and fails it at once after a permanent one. A request PR refuses
+/// may be down to a single recipient, so a batch of several payments is split in halves instead, and the halves
+/// are split again until the refused payments fail in batches of their own. The same goes for a batch PR keeps
+/// failing to build until its retries are used up. PR being unavailable is never down to a recipient, so such a
+/// batch fails whole once its retries are used up. A batch rebuilt while PR still reserves the UTXOs of its
+/// previous transaction may only fail to build for want of them, so it waits for them instead. Nothing is
+/// recorded if the batch is no longer held under `lease`, as the failure no longer applies to it.
+async fn record_failure(
+    conn: &mut SqliteConnection,
+    batch_id: &str,
+    lease: &Lease,
+    payment_count: usize,
+    code: FailureCode,
+    error_message: &str,
+    retry_backoff: &RetryBackoff,
+) -> Result<(), sqlx::Error> {
+    if code == FailureCode::UnsignedTxFailed
+        && let Some(waits_until) =
+            PaymentBatch::wait_for_utxo_lock(conn, batch_id, lease, error_message, &Actor::UnsignedTxCreator).await?
+    {
+        println!(
+            "Batch {} waits until {} for PR to release the UTXOs of its previous transaction",
+            batch_id, waits_until
+        );
+        return Ok(());
+    }
+
+    let isolate = match code {
+        FailureCode::InvalidTransactionRequest => true,
+        FailureCode::UnsignedTxFailed => PaymentBatch::is_last_retry(conn, batch_id).await?,
+        _ => false,
+    };
+    if isolate
+        && payment_count > 1
+        && let Some(halves) =
+            PaymentBatch::split(conn, batch_id, lease, error_message, &Actor::UnsignedTxCreator).await?
+    {
+        let half_ids: Vec<&str> = halves.iter().map(|half| half.id.as_str()).collect();
+        println!(
+            "Split batch {} into batches {} to isolate the payments PR fails on",
+            batch_id,
+            half_ids.join(" and ")
+        );
+        return Ok(());
+    }
+
+    let recorded = if code.is_retryable() {
+        PaymentBatch::increment_retry_count(
+            conn,
+            batch_id,
+            lease,
+            code,
+            error_message,
+            retry_backoff,
+            &Actor::UnsignedTxCreator,
+        )
+        .await?
+    } else {
+        PaymentBatch::update_to_failed_under_lease(
+            conn,
+            batch_id,
+            lease,
+            code,
+            error_message,
+            &Actor::UnsignedTxCreator,
+        )
+        .await?
+    };
+    if !recorded {
+        eprintln!(
+            "Batch {} changed while its unsigned transaction was created, discarding the failure",
+            batch_id
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use minotari_client::apis::ResponseContent;
+
+    use super::*;
+    use crate::{
+        db::payment::PaymentStatus,
+        test_support::{self, MockPaymentReceiver},
+    };
+
+    const BACKOFF: RetryBackoff = RetryBackoff {
+        base_secs: 30,
+        max_secs: 3600,
+    };
+
+    fn response_error(status: StatusCode, content: &str) -> ApiError<ApiCreateUnsignedTransactionError> {
+        ApiError::ResponseError(ResponseContent {
+            status,
+            content: content.to_string(),
+            entity: serde_json::from_str(content).ok(),
+        })
+    }
+
+    #[test]
+    fn only_pr_errors_blame_the_request() {
+        let cases = [
+            (
+                StatusCode::BAD_REQUEST,
+                r#"{"FailedCreateUnsignedTx":"invalid address"}"#,
+                FailureCode::InvalidTransactionRequest,
+            ),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                r#"{"FailedCreateUnsignedTx":"funds locked"}"#,
+                FailureCode::UnsignedTxFailed,
+            ),
+            (
+                StatusCode::NOT_FOUND,
+                r#"{"AccountNotFound":"default"}"#,
+                FailureCode::AccountNotFound,
+            ),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                r#"{"DbError":"locked"}"#,
+                FailureCode::PaymentReceiverUnavailable,
+            ),
+            // Responses that did not come from PR's handler.
+            (StatusCode::NOT_FOUND, "", FailureCode::PaymentReceiverUnavailable),
+            (
+                StatusCode::NOT_FOUND,
+                "Not Found",
+                FailureCode::PaymentReceiverUnavailable,
+            ),
+            (StatusCode::UNAUTHORIZED, "", FailureCode::PaymentReceiverUnavailable),
+            (
+                StatusCode::FORBIDDEN,
+                r#"{"message":"forbidden"}"#,
+                FailureCode::PaymentReceiverUnavailable,
+            ),
+            (
+                StatusCode::METHOD_NOT_ALLOWED,
+                "",
+                FailureCode::PaymentReceiverUnavailable,
+            ),
+            (
+                StatusCode::BAD_REQUEST,
+                "Bad Request",
+                FailureCode::PaymentReceiverUnavailable,
+            ),
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                "",
+                FailureCode::PaymentReceiverUnavailable,
+            ),
+            (StatusCode::BAD_GATEWAY, "", FailureCode::PaymentReceiverUnavailable),
+        ];
+        for (status, content, code) in cases {
+            assert_eq!(
+                classify_pr_error(&response_error(status, content)),
+                code,
+                "{} {}",
+                status,
+                content
+            );
+        }
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn bodyless_not_found_is_retried(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 2, 1_000).await;
+        // Nothing is served under this path, as when PAYMENT_RECEIVER points at the wrong place.
+        let mut client_config = MockPaymentReceiver::new(1_000_000).spawn().await;
+        client_config.base_path.push_str("/wrong");
+
+        process_unsigned_transactions(&pool, &client_config, &BACKOFF, 3600)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::PendingBatching);
+        assert_eq!(batch.retry_count, 1);
+        assert!(batch.next_retry_at.is_some());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn rebuilt_batch_pr_cannot_build_waits_for_utxo_lock(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 1, 1_000).await;
+        // Rebuilt after a cancel while PR still reserves the UTXOs of its previous transaction.
+        sqlx::query("UPDATE payment_batches SET utxos_locked_until = datetime('now', '+3600 seconds') WHERE id = ?")
+            .bind(&batch.id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        let client_config = MockPaymentReceiver::new(1_000_000)
+            .failing_for("recipient-0")
+            .spawn()
+            .await;
+
+        process_unsigned_transactions(&pool, &client_config, &BACKOFF, 3600)
+            .await
+            .unwrap();
+
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::PendingBatching);
+        assert_eq!(batch.retry_count, 0);
+        assert_eq!(batch.lease_expires_at, None);
+        let wait = batch.next_retry_at.unwrap() - chrono::Utc::now();
+        assert!(wait > chrono::Duration::seconds(3_500) && wait <= chrono::Duration::seconds(3_600));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn stale_failure_does_not_touch_rebuilt_batch(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 3, 1_000).await;
+        let payments = Payment::find_by_batch_id(&mut conn, &batch.id).await.unwrap();
+        let stale_lease = PaymentBatch::claim_for_unsigned_tx(&mut conn, &batch.id, &batch.pr_idempotency_key)
+            .await
+            .unwrap()
+            .unwrap();
+        // The PR call outlived its lease, and a payment was cancelled in the meantime.
+        sqlx::query("UPDATE payment_batches SET lease_expires_at = datetime('now', '-1 seconds') WHERE id = ?")
+            .bind(&batch.id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        assert!(
+            PaymentBatch::cancel_payment(
+                &mut conn,
+                &batch.id,
+                PaymentBatchStatus::PendingBatching,
+                &payments[0].id,
+                &Actor::Api,
+                None,
+            )
+            .await
+            .unwrap()
+        );
+
+        for code in [
+            FailureCode::InvalidTransactionRequest,
+            FailureCode::PaymentReceiverUnavailable,
+            FailureCode::AccountNotFound,
+        ] {
+            record_failure(&mut conn, &batch.id, &stale_lease, 3, code, "stale", &BACKOFF)
+                .await
+                .unwrap();
+        }
+
+        let rebuilt = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(rebuilt.status, PaymentBatchStatus::PendingBatching);
+        assert_eq!(rebuilt.retry_count, 0);
+        assert_eq!(rebuilt.next_retry_at, None);
+        let batches: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM payment_batches")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(batches, 1);
+        for payment in &payments[1..] {
+            let payment = Payment::get_by_id(&mut conn, &payment.id).await.unwrap().unwrap();
+            assert_eq!(payment.status, PaymentStatus::Batched);
+        }
+    }
+
+    /// Runs the worker until no batch is left waiting for its unsigned transaction, skipping the backoff
+    /// between attempts.
+    async fn process_until_settled(pool: &SqlitePool, client_config: &Configuration) {
+        for _ in 0..100 {
+            process_unsigned_transactions(pool, client_config, &BACKOFF, 3600)
+                .await
+                .unwrap();
+            sqlx::query("UPDATE payment_batches SET next_retry_at = NULL")
+                .execute(pool)
+                .await
+                .unwrap();
+            let pending: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM payment_batches WHERE status = ?")
+                .bind(PaymentBatchStatus::PendingBatching.to_string())
+                .fetch_one(pool)
+                .await
+                .unwrap();
+            if pending == 0 {
+                return;
+            }
+        }
+        panic!("Batches are still pending after 100 passes");
+    }
+
+    /// The status of every batch that was not split, with the recipients of its payments.
+    async fn settled_batches(pool: &SqlitePool) -> Vec<(String, Vec<String>)> {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch_ids: Vec<String> = sqlx::query_scalar("SELECT id FROM payment_batches WHERE status != ? ORDER BY id")
+            .bind(PaymentBatchStatus::Split.to_string())
+            .fetch_all(&mut *conn)
+            .await
+            .unwrap();
+        let mut batches = Vec::with_capacity(batch_ids.len());
+        for batch_id in batch_ids {
+            let batch = test_support::get_batch(&mut conn, &batch_id).await;
+            let mut recipients: Vec<String> = Payment::find_by_batch_id(&mut conn, &batch_id)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|payment| payment.recipient_address)
+                .collect();
+            recipients.sort();
+            batches.push((batch.status.to_string(), recipients));
+        }
+        batches.sort();
+        batches
+    }
+
+    async fn payment_statuses(pool: &SqlitePool) -> Vec<(String, String)> {
+        sqlx::query_as("SELECT recipient_address, status FROM payments ORDER BY recipient_address")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    /// Checks that `failed_recipient` is the only payment of the only failed batch, and that every other
+    /// payment reached 'AWAITING_SIGNATURE'. Which of them share a batch depends on how the splits fell.
+    async fn assert_failed_alone(pool: &SqlitePool, failed_recipient: &str, payment_count: usize) {
+        let batches = settled_batches(pool).await;
+        let (failed, built): (Vec<_>, Vec<_>) = batches
+            .into_iter()
+            .partition(|(status, _)| *status == PaymentBatchStatus::Failed.to_string());
+        assert_eq!(
+            failed,
+            vec![(
+                PaymentBatchStatus::Failed.to_string(),
+                vec![failed_recipient.to_string()]
+            )]
+        );
+        let mut built_recipients = Vec::new();
+        for (status, recipients) in built {
+            assert_eq!(
+                status,
+                PaymentBatchStatus::AwaitingSignature.to_string(),
+                "{:?}",
+                recipients
+            );
+            built_recipients.extend(recipients);
+        }
+        built_recipients.sort();
+        let expected: Vec<String> = (0..payment_count)
+            .map(|i| format!("recipient-{}", i))
+            .filter(|recipient| recipient != failed_recipient)
+            .collect();
+        assert_eq!(built_recipients, expected);
+
+        for (recipient, status) in payment_statuses(pool).await {
+            let expected = if recipient == failed_recipient {
+                PaymentStatus::Failed
+            } else {
+                PaymentStatus::Batched
+            };
+            assert_eq!(status, expected.to_string(), "{}", recipient);
+        }
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn refused_recipient_fails_alone(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        test_support::create_batch(&mut conn, 5, 1_000).await;
+        let client_config = MockPaymentReceiver::new(1_000_000)
+            .refusing("recipient-2")
+            .spawn()
+            .await;
+
+        process_until_settled(&pool, &client_config).await;
+
+        assert_failed_alone(&pool, "recipient-2", 5).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn recipient_pr_fails_to_build_for_fails_alone_after_its_retries(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        test_support::create_batch(&mut conn, 4, 1_000).await;
+        let client_config = MockPaymentReceiver::new(1_000_000)
+            .failing_for("recipient-1")
+            .spawn()
+            .await;
+
+        process_until_settled(&pool, &client_config).await;
+
+        assert_failed_alone(&pool, "recipient-1", 4).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn unavailable_pr_fails_whole_batch_after_one_budget(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let batch = test_support::create_batch(&mut conn, 4, 1_000).await;
+        let receiver = MockPaymentReceiver::new(1_000_000).unavailable_for("recipient-1");
+        let requests = receiver.requests.clone();
+        let client_config = receiver.spawn().await;
+
+        process_until_settled(&pool, &client_config).await;
+
+        let batches = settled_batches(&pool).await;
+        let recipients: Vec<String> = (0..4).map(|i| format!("recipient-{}", i)).collect();
+        assert_eq!(batches, vec![(PaymentBatchStatus::Failed.to_string(), recipients)]);
+        let batch = test_support::get_batch(&mut conn, &batch.id).await;
+        assert_eq!(batch.status, PaymentBatchStatus::Failed);
+        assert_eq!(requests.lock().unwrap().len(), 10);
+    }
+}
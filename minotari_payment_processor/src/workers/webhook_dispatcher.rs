@@ -0,0 +1,259 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{Client, header};
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use tokio::time::{self, Duration};
+
+use crate::db::webhook::WebhookDelivery;
+
+const DEFAULT_SLEEP_SECS: u64 = 5;
+const DELIVERIES_PER_TICK: i64 = 50;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+// Retries back off from 30 seconds, doubling up to 6 hours. A delivery is dead-lettered after
+// MAX_ATTEMPTS failures, roughly 15 hours after it was queued.
+const MAX_ATTEMPTS: i64 = 12;
+const BASE_RETRY_SECS: u64 = 30;
+const MAX_RETRY_SECS: u64 = 6 * 60 * 60;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub async fn run(db_pool: SqlitePool, signing_secret: String, sleep_secs: Option<u64>) {
+    let sleep_secs = sleep_secs.unwrap_or(DEFAULT_SLEEP_SECS);
+    let http_client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()
+        .expect("Failed to build webhook HTTP client");
+    let mut interval = time::interval(Duration::from_secs(sleep_secs));
+    loop {
+        interval.tick().await;
+        if let Err(e) = dispatch_webhooks(&db_pool, &http_client, &signing_secret).await {
+            eprintln!("Webhook Dispatcher worker error: {:?}", e);
+        }
+    }
+}
+
+async fn dispatch_webhooks(
+    db_pool: &SqlitePool,
+    http_client: &Client,
+    signing_secret: &str,
+) -> Result<(), anyhow::Error> {
+    let mut conn = db_pool.acquire().await?;
+    let deliveries = WebhookDelivery::find_due(&mut conn, DELIVERIES_PER_TICK).await?;
+
+    for delivery in deliveries {
+        match send(http_client, signing_secret, &delivery).await {
+            Ok(()) => {
+                WebhookDelivery::mark_delivered(&mut conn, delivery.id).await?;
+            },
+            Err(error) => {
+                let attempts = delivery.attempts + 1;
+                if attempts >= MAX_ATTEMPTS {
+                    eprintln!(
+                        "Webhook delivery {} to {} dead-lettered after {} attempts: {}",
+                        delivery.id, delivery.url, attempts, error
+                    );
+                    WebhookDelivery::dead_letter(&mut conn, delivery.id, &error).await?;
+                } else {
+                    WebhookDelivery::schedule_retry(&mut conn, delivery.id, &error, retry_delay_secs(attempts)).await?;
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Posts the delivery's payload, returning the reason on anything but a 2xx response.
+async fn send(http_client: &Client, signing_secret: &str, delivery: &WebhookDelivery) -> Result<(), String> {
+    let timestamp = Utc::now().timestamp();
+    let signature = sign_payload(signing_secret, timestamp, &delivery.payload);
+
+    let response = http_client
+        .post(&delivery.url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Id", delivery.id.to_string())
+        .header("X-Webhook-Event", &delivery.event_type)
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", format!("sha256={}", signature))
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("Receiver responded with {}", response.status()))
+    }
+}
+
+/// Signs `<timestamp>.<payload>` so receivers can reject both forged and replayed deliveries.
+fn sign_payload(signing_secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(signing_secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn retry_delay_secs(attempts: i64) -> u64 {
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    BASE_RETRY_SECS.saturating_mul(1 << exponent).min(MAX_RETRY_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc, Mutex,
+        atomic::{AtomicU16, Ordering},
+    };
+
+    use axum::{
+        Router,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+    };
+    use sqlx::SqliteConnection;
+
+    use super::*;
+    use crate::test_support;
+
+    const SIGNING_SECRET: &str = "test-signing-secret";
+
+    /// A local webhook receiver that records every request and answers with `status`.
+    #[derive(Clone)]
+    struct Receiver {
+        status: Arc<AtomicU16>,
+        requests: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    }
+
+    async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: String) -> StatusCode {
+        receiver.requests.lock().unwrap().push((headers, body));
+        StatusCode::from_u16(receiver.status.load(Ordering::SeqCst)).unwrap()
+    }
+
+    async fn spawn_receiver(status: StatusCode) -> (String, Receiver) {
+        let receiver = Receiver {
+            status: Arc::new(AtomicU16::new(status.as_u16())),
+            requests: Arc::default(),
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = Router::new().route("/hook", post(receive)).with_state(receiver.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, receiver)
+    }
+
+    /// Queues a delivery of a new payment to `url` and returns the delivery's ID.
+    async fn queue_delivery(conn: &mut SqliteConnection, url: &str) -> i64 {
+        let payment = test_support::create_payments(conn, 1, 1_000).await.remove(0);
+        sqlx::query("UPDATE payments SET callback_url = ? WHERE id = ?")
+            .bind(url)
+            .bind(&payment.id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        WebhookDelivery::enqueue_for_payments(conn, &[payment.id.clone()])
+            .await
+            .unwrap();
+        sqlx::query_scalar("SELECT id FROM webhook_deliveries WHERE payment_id = ?")
+            .bind(&payment.id)
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap()
+    }
+
+    /// The delivery's status, attempts, last error and seconds until its next attempt.
+    async fn delivery_state(conn: &mut SqliteConnection, id: i64) -> (String, i64, Option<String>, i64) {
+        sqlx::query_as(
+            r#"
+            SELECT status, attempts, last_error,
+                CAST(strftime('%s', next_attempt_at) - strftime('%s', 'now') AS INTEGER)
+            FROM webhook_deliveries
+            WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn delivery_is_signed_and_acknowledged(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let (url, receiver) = spawn_receiver(StatusCode::NO_CONTENT).await;
+        let id = queue_delivery(&mut conn, &url).await;
+
+        dispatch_webhooks(&pool, &Client::new(), SIGNING_SECRET).await.unwrap();
+
+        let requests = receiver.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1);
+        let (headers, body) = &requests[0];
+        let header = |name: &str| headers.get(name).unwrap().to_str().unwrap().to_string();
+        assert_eq!(header("X-Webhook-Id"), id.to_string());
+        assert_eq!(header("X-Webhook-Event"), "payment.received");
+        let timestamp: i64 = header("X-Webhook-Timestamp").parse().unwrap();
+        let mut mac = HmacSha256::new_from_slice(SIGNING_SECRET.as_bytes()).unwrap();
+        mac.update(format!("{}.{}", timestamp, body).as_bytes());
+        let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        assert_eq!(header("X-Webhook-Signature"), expected);
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["event"], "payment.received");
+
+        let (status, attempts, last_error, _) = delivery_state(&mut conn, id).await;
+        assert_eq!(status, "DELIVERED");
+        assert_eq!(attempts, 1);
+        assert_eq!(last_error, None);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn failed_delivery_is_retried_with_backoff(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let (url, receiver) = spawn_receiver(StatusCode::INTERNAL_SERVER_ERROR).await;
+        let id = queue_delivery(&mut conn, &url).await;
+
+        dispatch_webhooks(&pool, &Client::new(), SIGNING_SECRET).await.unwrap();
+        // Not due again until the backoff has passed.
+        dispatch_webhooks(&pool, &Client::new(), SIGNING_SECRET).await.unwrap();
+
+        assert_eq!(receiver.requests.lock().unwrap().len(), 1);
+        let (status, attempts, last_error, retry_in_secs) = delivery_state(&mut conn, id).await;
+        assert_eq!(status, "PENDING");
+        assert_eq!(attempts, 1);
+        assert!(last_error.unwrap().contains("500"));
+        assert!((BASE_RETRY_SECS as i64 - 2..=BASE_RETRY_SECS as i64).contains(&retry_in_secs));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn delivery_is_dead_lettered_after_last_attempt(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let (url, receiver) = spawn_receiver(StatusCode::SERVICE_UNAVAILABLE).await;
+        let id = queue_delivery(&mut conn, &url).await;
+        sqlx::query("UPDATE webhook_deliveries SET attempts = ? WHERE id = ?")
+            .bind(MAX_ATTEMPTS - 1)
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        dispatch_webhooks(&pool, &Client::new(), SIGNING_SECRET).await.unwrap();
+
+        assert_eq!(receiver.requests.lock().unwrap().len(), 1);
+        let (status, attempts, last_error, _) = delivery_state(&mut conn, id).await;
+        assert_eq!(status, "DEAD_LETTERED");
+        assert_eq!(attempts, MAX_ATTEMPTS);
+        assert!(last_error.unwrap().contains("503"));
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_cap() {
+        assert_eq!(retry_delay_secs(1), BASE_RETRY_SECS);
+        assert_eq!(retry_delay_secs(2), BASE_RETRY_SECS * 2);
+        assert_eq!(retry_delay_secs(3), BASE_RETRY_SECS * 4);
+        assert_eq!(retry_delay_secs(MAX_ATTEMPTS - 1), MAX_RETRY_SECS);
+        assert_eq!(retry_delay_secs(i64::MAX), MAX_RETRY_SECS);
+    }
+}